@@ -0,0 +1,92 @@
+//! Reallocations and time of capturing a large output, `read_to_end`
+//! against `read_to_end_hinted`.
+//!
+//! Runs without a harness: `cargo bench --bench read_to_end`.
+
+#[cfg(windows)]
+mod imp {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_anon_pipe::{anon_pipe, AnonPipeRead};
+
+    const LEN: usize = 50 * 1024 * 1024;
+    const CHUNK: usize = 64 * 1024;
+
+    /// Counts the reallocations of the whole process.
+    struct Counting;
+
+    static REALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            REALLOCS.fetch_add(1, Ordering::Relaxed);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: Counting = Counting;
+
+    async fn capture(hinted: bool) -> std::io::Result<(usize, usize)> {
+        let (mut r, mut w) = anon_pipe().await?;
+        let writer = tokio::spawn(async move {
+            let chunk = vec![0x5a; CHUNK];
+            for _ in 0..LEN / CHUNK {
+                w.write_all(&chunk).await?;
+            }
+            w.close().await
+        });
+
+        let before = REALLOCS.load(Ordering::Relaxed);
+        let mut out = vec![];
+        let len = read(&mut r, &mut out, hinted).await?;
+        let reallocs = REALLOCS.load(Ordering::Relaxed) - before;
+        writer.await.unwrap()?;
+        assert_eq!(len, LEN);
+        Ok((len, reallocs))
+    }
+
+    async fn read(r: &mut AnonPipeRead, out: &mut Vec<u8>, hinted: bool) -> std::io::Result<usize> {
+        if hinted {
+            r.read_to_end_hinted(out).await
+        } else {
+            r.read_to_end(out).await
+        }
+    }
+
+    pub(crate) fn main() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            for (name, hinted) in [("read_to_end", false), ("read_to_end_hinted", true)] {
+                let start = Instant::now();
+                let (len, reallocs) = capture(hinted).await.unwrap();
+                println!(
+                    "{:<20} {} bytes, {} reallocations, {:?}",
+                    name,
+                    len,
+                    reallocs,
+                    start.elapsed()
+                );
+            }
+        });
+    }
+}
+
+fn main() {
+    #[cfg(windows)]
+    imp::main();
+}
@@ -0,0 +1,181 @@
+//! Registry of the live ends, to detect leaked handles.
+//!
+//! Compiled to nothing without the `handle-audit` feature.
+#[cfg(feature = "handle-audit")]
+pub use imp::{live_pipes, LivePipeInfo};
+#[cfg(feature = "handle-audit")]
+pub(crate) use imp::{track, untrack};
+
+#[cfg(not(feature = "handle-audit"))]
+#[inline(always)]
+pub(crate) fn track<T>(end: T, _name: Option<&str>) -> T {
+    end
+}
+
+#[cfg(not(feature = "handle-audit"))]
+#[inline(always)]
+pub(crate) fn untrack<T>(_end: &T) {}
+
+#[cfg(feature = "handle-audit")]
+mod imp {
+    use std::backtrace::{Backtrace, BacktraceStatus};
+    use std::collections::HashMap;
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    use crate::{AnonPipeDuplex, AnonPipeRead, AnonPipeWrite, AsRawHandle};
+
+    /// Live end, as listed by [`live_pipes`].
+    #[derive(Debug, Clone)]
+    pub struct LivePipeInfo {
+        handle: usize,
+        type_name: &'static str,
+        server: bool,
+        name: Option<String>,
+        backtrace: Option<String>,
+    }
+
+    impl LivePipeInfo {
+        /// Handle value.
+        pub fn handle(&self) -> usize {
+            self.handle
+        }
+
+        /// Type of the end, e.g. `AnonPipeRead`.
+        pub fn type_name(&self) -> &'static str {
+            self.type_name
+        }
+
+        /// Whether the end is the server role.
+        pub fn is_server(&self) -> bool {
+            self.server
+        }
+
+        /// Pipe name, unknown for the ends reconstructed from a raw handle.
+        pub fn name(&self) -> Option<&str> {
+            self.name.as_deref()
+        }
+
+        /// Where the end was created, captured only if enabled by
+        /// `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`.
+        pub fn backtrace(&self) -> Option<&str> {
+            self.backtrace.as_deref()
+        }
+    }
+
+    pub(crate) trait Audited: AsRawHandle {
+        const TYPE_NAME: &'static str;
+
+        fn is_server(&self) -> bool;
+    }
+
+    macro_rules! audited {
+        ($ty:ident) => {
+            impl Audited for $ty {
+                const TYPE_NAME: &'static str = stringify!($ty);
+
+                fn is_server(&self) -> bool {
+                    matches!(self, Self::Server(..))
+                }
+            }
+
+            impl Drop for $ty {
+                fn drop(&mut self) {
+                    untrack(self);
+                }
+            }
+        };
+    }
+
+    audited!(AnonPipeRead);
+    audited!(AnonPipeWrite);
+    audited!(AnonPipeDuplex);
+
+    static LIVE: Mutex<Option<HashMap<usize, LivePipeInfo>>> = Mutex::new(None);
+
+    fn live() -> MutexGuard<'static, Option<HashMap<usize, LivePipeInfo>>> {
+        LIVE.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Register a created end.
+    pub(crate) fn track<T: Audited>(end: T, name: Option<&str>) -> T {
+        let backtrace = Backtrace::capture();
+        let info = LivePipeInfo {
+            handle: end.as_raw_handle() as usize,
+            type_name: T::TYPE_NAME,
+            server: end.is_server(),
+            name: name.map(ToOwned::to_owned),
+            backtrace: match backtrace.status() {
+                BacktraceStatus::Captured => Some(backtrace.to_string()),
+                _ => None,
+            },
+        };
+        // a stale entry of a reused handle value is overwritten.
+        live()
+            .get_or_insert_with(HashMap::new)
+            .insert(info.handle, info);
+        end
+    }
+
+    /// Unregister an end dropped or converted into a raw handle.
+    pub(crate) fn untrack<T: AsRawHandle>(end: &T) {
+        if let Some(live) = live().as_mut() {
+            live.remove(&(end.as_raw_handle() as usize));
+        }
+    }
+
+    /// Ends created by this crate and not dropped yet.
+    ///
+    /// Ends converted into a raw handle are no longer listed, the ones
+    /// reconstructed from it are listed again. Assert it is empty at the end
+    /// of a test to detect leaks:
+    ///
+    /// ```no_run
+    /// assert!(
+    ///     tokio_anon_pipe::live_pipes().is_empty(),
+    ///     "{:#?}",
+    ///     tokio_anon_pipe::live_pipes()
+    /// );
+    /// ```
+    pub fn live_pipes() -> Vec<LivePipeInfo> {
+        live()
+            .as_ref()
+            .map(|live| live.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(all(test, feature = "handle-audit"))]
+mod tests {
+    use super::*;
+    use crate::{anon_pipe, AsRawHandle, IntoRawHandle};
+
+    fn is_live<T: AsRawHandle>(end: &T) -> Option<LivePipeInfo> {
+        let handle = end.as_raw_handle() as usize;
+        live_pipes()
+            .into_iter()
+            .find(|info| info.handle() == handle)
+    }
+
+    #[tokio::test]
+    async fn test_live_pipes() -> std::io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        let info = is_live(&r).unwrap();
+        assert_eq!(info.type_name(), "AnonPipeRead");
+        assert!(info.is_server());
+        assert!(info.name().unwrap().starts_with(r"\\.\pipe\"));
+        let info = is_live(&w).unwrap();
+        assert_eq!(info.type_name(), "AnonPipeWrite");
+        assert!(!info.is_server());
+
+        let handle = r.as_raw_handle() as usize;
+        drop(r);
+        assert!(live_pipes().iter().all(|info| info.handle() != handle));
+
+        let handle = w.into_raw_handle();
+        assert!(live_pipes()
+            .iter()
+            .all(|info| info.handle() != handle as usize));
+        unsafe { crate::sys::CloseHandle(handle as _) };
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+//! Round trip telling that the peer caught up.
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+
+use crate::AnonPipeDuplex;
+
+/// Byte [`AnonPipeDuplex::barrier`] sends, ASCII `ENQ`.
+pub const BARRIER_REQUEST: u8 = 0x05;
+
+/// Byte [`AnonPipeDuplex::answer_barrier`] replies with, ASCII `ACK`.
+pub const BARRIER_ACK: u8 = 0x06;
+
+impl AnonPipeDuplex {
+    /// Wait until the peer processed everything written before, e.g.
+    /// before acting on a side effect of the data sent.
+    ///
+    /// Writes [`BARRIER_REQUEST`] and waits for the [`BARRIER_ACK`] of the
+    /// peer, which replies with [`answer_barrier`](Self::answer_barrier).
+    /// As the pipe is in order, the peer reads the request only after the
+    /// data before, and the guarantee is that of its code: the peer must
+    /// answer only once it processed that data.
+    ///
+    /// The bytes are not escaped in the user data: both sides must agree on
+    /// where a barrier may appear, e.g. instead of the next message of a
+    /// request/response protocol, or as one tag of their own framing, which
+    /// then reads the tag and replies `BARRIER_ACK` itself. The peer must
+    /// not have anything else in transit towards this end: fails with
+    /// `ErrorKind::InvalidData` if the byte read is not `BARRIER_ACK`, and
+    /// with `ErrorKind::UnexpectedEof` if the peer closed instead.
+    pub async fn barrier(&mut self) -> io::Result<()> {
+        self.write_all(&[BARRIER_REQUEST]).await?;
+        match self.read_u8().await? {
+            BARRIER_ACK => Ok(()),
+            byte => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected barrier acknowledgement, got {:#04x}", byte),
+            )),
+        }
+    }
+
+    /// Answer the [`barrier`](Self::barrier) of the peer, to be called
+    /// once everything it sent before was processed.
+    ///
+    /// Reads the next byte, which must be [`BARRIER_REQUEST`], and replies
+    /// [`BARRIER_ACK`]. Fails with `ErrorKind::InvalidData` if it is
+    /// another byte, which is consumed, and with
+    /// `ErrorKind::UnexpectedEof` if the peer closed instead. Returns once
+    /// the reply was written.
+    pub async fn answer_barrier(&mut self) -> io::Result<()> {
+        match self.read_u8().await? {
+            BARRIER_REQUEST => {}
+            byte => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("expected barrier request, got {:#04x}", byte),
+                ))
+            }
+        }
+        self.write_all(&[BARRIER_ACK]).await?;
+        std::future::poll_fn(|cx| self.poll_write_ready(cx)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe_duplex_pair;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_barrier() -> io::Result<()> {
+        let (mut a, mut b) = anon_pipe_duplex_pair().await?;
+        let processed = Arc::new(AtomicBool::new(false));
+        let peer = tokio::spawn({
+            let processed = processed.clone();
+            async move {
+                let mut buf = [0; 5];
+                b.read_exact(&mut buf).await?;
+                assert_eq!(&buf, b"Hello");
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                processed.store(true, Ordering::SeqCst);
+                b.answer_barrier().await
+            }
+        });
+
+        a.write_all(b"Hello").await?;
+        a.barrier().await?;
+        assert!(processed.load(Ordering::SeqCst));
+        peer.await.unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_barrier_unexpected() -> io::Result<()> {
+        let (mut a, mut b) = anon_pipe_duplex_pair().await?;
+        a.write_all(b"x").await?;
+        let err = b.answer_barrier().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        b.write_all(b"y").await?;
+        let err = a.barrier().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // the peer closes instead of answering.
+        assert_eq!(b.read_u8().await?, BARRIER_REQUEST);
+        let peer = tokio::spawn(async move {
+            b.read_u8().await?;
+            drop(b);
+            Ok::<_, io::Error>(())
+        });
+        let err = a.barrier().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        peer.await.unwrap()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+//! Handle values for processes of the other bitness.
+use crate::{AnonPipeRead, AnonPipeWrite, AsRawHandle, RawHandle};
+
+/// Bitness of the process receiving a handle value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bitness {
+    /// 32-bit process, including one running under WOW64.
+    Bits32,
+    /// 64-bit process.
+    Bits64,
+}
+
+/// `handle` formatted for a process of `bitness`.
+///
+/// Kernel handles only have 32 significant bits, so that they can be shared
+/// between 32-bit and 64-bit processes. A 64-bit value is the sign
+/// extension of the 32-bit one (`HandleToLong` / `LongToHandle`), which
+/// keeps pseudo handles such as `INVALID_HANDLE_VALUE` (`-1`) intact.
+fn handle_value(handle: RawHandle, bitness: Bitness) -> u64 {
+    let value = handle as usize as u32;
+    match bitness {
+        Bitness::Bits32 => value as u64,
+        Bitness::Bits64 => value as i32 as i64 as u64,
+    }
+}
+
+impl AnonPipeRead {
+    /// Value of the handle for a process of `bitness`, e.g. to pass on its
+    /// command line after duplicating the handle into it.
+    ///
+    /// See [`AnonPipeWrite::handle_value_for_bitness`].
+    pub fn handle_value_for_bitness(&self, bitness: Bitness) -> u64 {
+        handle_value(self.as_raw_handle(), bitness)
+    }
+}
+
+impl AnonPipeWrite {
+    /// Value of the handle for a process of `bitness`, e.g. to pass on its
+    /// command line after duplicating the handle into it.
+    ///
+    /// Handle values are valid in both 32-bit and 64-bit processes of the
+    /// same handle table; only the upper 32 bits of a 64-bit value are
+    /// meaningless, and must be the sign extension of bit 31. A 32-bit
+    /// receiver gets the low 32 bits, a 64-bit receiver the sign-extended
+    /// value. Parse it back with the receiver's pointer width.
+    pub fn handle_value_for_bitness(&self, bitness: Bitness) -> u64 {
+        handle_value(self.as_raw_handle(), bitness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io;
+
+    #[test]
+    fn test_handle_value() {
+        let handle = 0x1234 as RawHandle;
+        assert_eq!(handle_value(handle, Bitness::Bits32), 0x1234);
+        assert_eq!(handle_value(handle, Bitness::Bits64), 0x1234);
+
+        // INVALID_HANDLE_VALUE
+        let handle = -1isize as RawHandle;
+        assert_eq!(handle_value(handle, Bitness::Bits32), 0xffff_ffff);
+        assert_eq!(handle_value(handle, Bitness::Bits64), u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_handle_value_for_bitness() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        let value = w.handle_value_for_bitness(Bitness::Bits64);
+        assert_eq!(value, w.as_raw_handle() as usize as u64);
+        assert_eq!(w.handle_value_for_bitness(Bitness::Bits32), value);
+        assert_eq!(
+            r.handle_value_for_bitness(Bitness::Bits32),
+            r.as_raw_handle() as u64
+        );
+        Ok(())
+    }
+}
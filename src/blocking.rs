@@ -0,0 +1,143 @@
+//! Synchronous write for the paths which cannot await.
+use std::convert::TryFrom;
+use std::ptr;
+use std::time::Duration;
+
+use tokio::io;
+
+use crate::{sys, AnonPipeWrite, AsRawHandle};
+
+/// Bound of [`AnonPipeWrite::write_blocking`].
+const WRITE_BLOCKING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Manual reset event, closed on drop.
+struct Event(sys::HANDLE);
+
+impl Event {
+    fn new() -> io::Result<Self> {
+        let event = unsafe { sys::CreateEventW(ptr::null_mut(), 1, 0, ptr::null()) };
+        if event.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(event))
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe { sys::CloseHandle(self.0) };
+    }
+}
+
+impl AnonPipeWrite {
+    /// Write `buf` synchronously, without the runtime, waiting at most one
+    /// second for the reader to make room.
+    ///
+    /// A last resort for the paths which cannot await, e.g. a `Drop` impl
+    /// or a panic hook pushing a final small record. Waits on an event of
+    /// its own, not on the reactor, so it does not deadlock on a worker
+    /// thread of the runtime, but it blocks that thread. The write is
+    /// issued aside of the async writes of this end: it may land between
+    /// the chunks of a pending `write_all` and garble both.
+    ///
+    /// Fails with `ErrorKind::TimedOut` if the write did not complete in
+    /// time, in which case it is cancelled; part of `buf` may have been
+    /// delivered.
+    pub fn write_blocking(&self, buf: &[u8]) -> io::Result<usize> {
+        // https://www.rpi.edu/dept/cis/software/g77-mingw32/include/winerror.h
+        const ERROR_IO_PENDING: i32 = 997;
+
+        const WAIT_OBJECT_0: u32 = 0;
+        const WAIT_TIMEOUT: u32 = 258;
+        const INFINITE: u32 = 0xffff_ffff;
+
+        let handle = self.as_raw_handle() as sys::HANDLE;
+        let len = u32::try_from(buf.len()).unwrap_or(u32::MAX);
+        let event = Event::new()?;
+        let mut overlapped = sys::OVERLAPPED {
+            Internal: 0,
+            InternalHigh: 0,
+            Offset: 0,
+            OffsetHigh: 0,
+            // the low-order bit keeps the completion off the completion
+            // port, where the reactor would take it for one of its own.
+            hEvent: (event.0 as usize | 1) as sys::HANDLE,
+        };
+        let mut written = 0;
+        let ok = unsafe {
+            sys::WriteFile(
+                handle,
+                buf.as_ptr().cast(),
+                len,
+                &mut written,
+                &mut overlapped,
+            )
+        };
+        if ok == 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(ERROR_IO_PENDING) {
+                return Err(err);
+            }
+            let timeout = WRITE_BLOCKING_TIMEOUT.as_millis() as u32;
+            let waited = unsafe { sys::WaitForSingleObject(event.0, timeout) };
+            let wait_err = io::Error::last_os_error();
+            if waited != WAIT_OBJECT_0 {
+                // `overlapped` and `buf` must outlive the write.
+                unsafe {
+                    sys::CancelIoEx(handle, &mut overlapped as *mut _ as *mut _);
+                    sys::WaitForSingleObject(event.0, INFINITE);
+                }
+            }
+            let ok = unsafe { sys::GetOverlappedResult(handle, &mut overlapped, &mut written, 0) };
+            // succeeds if completed before the cancellation.
+            if ok == 0 {
+                return Err(match waited {
+                    WAIT_OBJECT_0 => io::Error::last_os_error(),
+                    WAIT_TIMEOUT => {
+                        io::Error::new(io::ErrorKind::TimedOut, "blocking write timed out")
+                    }
+                    _ => wait_err,
+                });
+            }
+        }
+        Ok(written as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_write_blocking_from_thread() -> io::Result<()> {
+        let (mut r, w) = anon_pipe().await?;
+        let n = std::thread::scope(|s| s.spawn(|| w.write_blocking(b"Hello")).join()).unwrap()?;
+        assert_eq!(n, 5);
+        drop(w);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"Hello");
+        Ok(())
+    }
+
+    struct Farewell(AnonPipeWrite);
+
+    impl Drop for Farewell {
+        fn drop(&mut self) {
+            self.0.write_blocking(b"bye").unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_write_blocking_in_drop() -> io::Result<()> {
+        let (mut r, w) = anon_pipe().await?;
+        // on the only thread of the runtime.
+        drop(Farewell(w));
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"bye");
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+//! Cancellation aware wrapper.
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr;
+use std::task::{Context, Poll};
+
+use tokio::io::{self, AsyncRead, AsyncWrite};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+use crate::{sys, AsRawHandle, RawHandle};
+
+/// Read or write half which fails once the token is cancelled.
+///
+/// Created by [`AnonPipeRead::with_cancellation`](crate::AnonPipeRead::with_cancellation)
+/// or [`AnonPipeWrite::with_cancellation`](crate::AnonPipeWrite::with_cancellation).
+///
+/// Once the token is cancelled, every `poll_read` / `poll_write` (including
+/// the one already pending) resolves with `ErrorKind::Interrupted`.
+/// On the first observation of cancellation, in-flight operations on the
+/// handle are cancelled with `CancelIoEx`, so the buffers are released
+/// without waiting for the peer. A cancelled write may have been partially
+/// delivered.
+#[derive(Debug)]
+pub struct Cancellable<T> {
+    inner: T,
+    cancelled: Pin<Box<WaitForCancellationFutureOwned>>,
+    done: bool,
+}
+
+impl<T> Cancellable<T>
+where
+    T: AsRawHandle,
+{
+    pub(crate) fn new(inner: T, token: CancellationToken) -> Self {
+        Self {
+            inner,
+            cancelled: Box::pin(token.cancelled_owned()),
+            done: false,
+        }
+    }
+
+    /// Gets a reference to the underlying half.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Unwrap the underlying half.
+    ///
+    /// After cancellation, the next operation on it may fail with
+    /// `ERROR_OPERATION_ABORTED`.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn poll_cancelled(&mut self, cx: &mut Context<'_>) -> Poll<io::Error> {
+        if !self.done {
+            if self.cancelled.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.done = true;
+            // fails with ERROR_NOT_FOUND if nothing is in flight.
+            unsafe { sys::CancelIoEx(self.inner.as_raw_handle() as _, ptr::null_mut()) };
+        }
+        Poll::Ready(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "operation cancelled",
+        ))
+    }
+}
+
+impl<T> AsyncRead for Cancellable<T>
+where
+    T: AsyncRead + AsRawHandle + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Poll::Ready(err) = this.poll_cancelled(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T> AsyncWrite for Cancellable<T>
+where
+    T: AsyncWrite + AsRawHandle + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        if let Poll::Ready(err) = this.poll_cancelled(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Pin::new(&mut this.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+impl<T> AsRawHandle for Cancellable<T>
+where
+    T: AsRawHandle,
+{
+    fn as_raw_handle(&self) -> RawHandle {
+        self.inner.as_raw_handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_cancel_pending_read() -> io::Result<()> {
+        let (r, _w) = anon_pipe().await?;
+        let token = CancellationToken::new();
+        let mut r = r.with_cancellation(token.clone());
+
+        let read = tokio::spawn(async move {
+            let mut buf = [0; 16];
+            r.read(&mut buf).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!read.is_finished());
+
+        token.cancel();
+        let result = tokio::time::timeout(Duration::from_secs(1), read)
+            .await
+            .expect("read was not cancelled promptly")
+            .unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_before_write() -> io::Result<()> {
+        let (mut r, w) = anon_pipe().await?;
+        let token = CancellationToken::new();
+        let mut w = w.with_cancellation(token.clone());
+
+        w.write_all(b"ok").await?;
+        let mut buf = [0; 2];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"ok");
+
+        token.cancel();
+        let err = w.write_all(b"ng").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! Read to EOF, bounded in memory.
+use std::error;
+use std::fmt;
+
+use tokio::io::{self, AsyncReadExt};
+
+use crate::AnonPipeRead;
+
+/// Failure of [`AnonPipeRead::read_to_end_capped`], carrying the data read
+/// up to the cap.
+///
+/// Converts into an `io::Error` of kind `FileTooLarge` with `?`, get it
+/// back with `io::Error::into_inner` and a downcast.
+#[derive(Debug)]
+pub struct CapExceeded {
+    cap: usize,
+    data: Vec<u8>,
+}
+
+impl CapExceeded {
+    /// The cap exceeded.
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    /// The first `cap` bytes read.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Take the first `cap` bytes read.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl fmt::Display for CapExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "data exceeds {} bytes", self.cap)
+    }
+}
+
+impl error::Error for CapExceeded {}
+
+impl From<CapExceeded> for io::Error {
+    fn from(err: CapExceeded) -> Self {
+        io::Error::new(io::ErrorKind::FileTooLarge, err)
+    }
+}
+
+impl AnonPipeRead {
+    /// Read until EOF, keeping at most `cap` bytes in memory.
+    ///
+    /// Fails with `ErrorKind::FileTooLarge` as soon as more than `cap`
+    /// bytes arrived, with a [`CapExceeded`] holding the first `cap` bytes.
+    /// The bytes past those are not consumed, except for the one detecting
+    /// the overflow.
+    pub async fn read_to_end_capped(&mut self, cap: usize) -> io::Result<Vec<u8>> {
+        let mut data = vec![];
+        let limit = (cap as u64).saturating_add(1);
+        (&mut *self).take(limit).read_to_end(&mut data).await?;
+        if data.len() > cap {
+            data.truncate(cap);
+            return Err(CapExceeded { cap, data }.into());
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_read_to_end_capped() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        w.write_all(b"Hello").await?;
+        drop(w);
+        // exactly the cap.
+        assert_eq!(r.read_to_end_capped(5).await?, b"Hello");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_to_end_capped_exceeded() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        w.write_all(b"Hello, World!").await?;
+        drop(w);
+
+        let err = r.read_to_end_capped(5).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::FileTooLarge);
+        let err = err.into_inner().unwrap().downcast::<CapExceeded>().unwrap();
+        assert_eq!(err.cap(), 5);
+        assert_eq!(err.into_data(), b"Hello");
+
+        let mut rest = vec![];
+        r.read_to_end(&mut rest).await?;
+        assert_eq!(&rest, b"World!");
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Read halves concatenated into one stream.
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{self, AsyncRead};
+
+use crate::{AnonPipeRead, AsRawHandle, RawHandle};
+
+/// Read halves read one after another, each to EOF.
+///
+/// Created by [`AnonPipeRead::chain`]. Errors are returned from whichever
+/// read half is active; EOF is returned once the last one reaches EOF.
+#[derive(Debug)]
+pub struct ChainedRead {
+    current: AnonPipeRead,
+    rest: VecDeque<AnonPipeRead>,
+}
+
+impl ChainedRead {
+    pub(crate) fn new(first: AnonPipeRead, next: AnonPipeRead) -> Self {
+        Self {
+            current: first,
+            rest: VecDeque::from(vec![next]),
+        }
+    }
+
+    /// Continue with `next` after the read halves chained so far.
+    pub fn chain(mut self, next: AnonPipeRead) -> Self {
+        self.rest.push_back(next);
+        self
+    }
+
+    /// Number of bytes in the pipe buffer of the active read half, as
+    /// reported by `PeekNamedPipe`.
+    ///
+    /// Bytes already taken by the read kept in flight by the completion port
+    /// driver are not counted, so `0` does not mean a read would block.
+    /// Returns `0` once the writer of the active read half is gone.
+    pub fn bytes_available(&self) -> io::Result<u32> {
+        self.current.bytes_available()
+    }
+
+    /// Gets a reference to the active read half.
+    pub fn get_ref(&self) -> &AnonPipeRead {
+        &self.current
+    }
+}
+
+impl AsyncRead for ChainedRead {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            let filled = buf.filled().len();
+            match Pin::new(&mut this.current).poll_read(cx, buf)? {
+                Poll::Ready(()) => {}
+                Poll::Pending => return Poll::Pending,
+            }
+            if buf.filled().len() == filled && buf.remaining() > 0 {
+                // EOF of the active read half.
+                if let Some(next) = this.rest.pop_front() {
+                    this.current = next;
+                    continue;
+                }
+            }
+            return Poll::Ready(Ok(()));
+        }
+    }
+}
+
+/// Handle of the active read half.
+impl AsRawHandle for ChainedRead {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.current.as_raw_handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anon_pipe;
+    use std::time::Duration;
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_chain() -> io::Result<()> {
+        let (r1, mut w1) = anon_pipe().await?;
+        let (r2, mut w2) = anon_pipe().await?;
+        let (r3, mut w3) = anon_pipe().await?;
+        let mut r = r1.chain(r2).chain(r3);
+
+        // the last writer finishes first, the middle one last.
+        w3.write_all(b"three").await?;
+        drop(w3);
+        let w2 = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            w2.write_all(b"two ").await?;
+            Ok::<_, io::Error>(())
+        });
+        w1.write_all(b"one ").await?;
+        drop(w1);
+
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        w2.await.unwrap()?;
+        assert_eq!(&buf, b"one two three");
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! Length-prefixed frames with a CRC32 trailer.
+//!
+//! The length header counts the payload and the 4-byte little-endian
+//! CRC32 (IEEE) of the payload following it.
+use std::convert::TryFrom;
+
+use bytes::Bytes;
+use tokio::io::{self, AsyncReadExt};
+
+use crate::{msg, AnonPipeRead, AnonPipeWrite};
+
+const TRAILER_LEN: usize = 4;
+
+/// CRC32 (IEEE 802.3, reflected) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    const POLY: u32 = 0xedb8_8320;
+
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+impl AnonPipeRead {
+    /// Receive a frame sent by [`AnonPipeWrite::write_frame_checked`],
+    /// verify its CRC32 trailer and return the payload.
+    ///
+    /// Fails with `ErrorKind::InvalidData` if the payload exceeds `max`
+    /// bytes, the frame is too short for the trailer, or the checksum does
+    /// not match, and with `ErrorKind::UnexpectedEof` on EOF before a
+    /// whole frame arrived.
+    pub async fn read_frame_checked(&mut self, max: usize) -> io::Result<Bytes> {
+        let len = match msg::read_len(self).await? {
+            Some(len) => len as usize,
+            None => return Err(io::ErrorKind::UnexpectedEof.into()),
+        };
+        if len < TRAILER_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame length {} too short for the checksum", len),
+            ));
+        }
+        if len - TRAILER_LEN > max {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame length {} exceeds {}", len - TRAILER_LEN, max),
+            ));
+        }
+        let mut frame = vec![0; len];
+        self.read_exact(&mut frame).await?;
+
+        let mut trailer = [0; TRAILER_LEN];
+        trailer.copy_from_slice(&frame[len - TRAILER_LEN..]);
+        frame.truncate(len - TRAILER_LEN);
+        let expected = u32::from_le_bytes(trailer);
+        let actual = crc32(&frame);
+        if expected != actual {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("checksum mismatch {:08x} != {:08x}", actual, expected),
+            ));
+        }
+        Ok(Bytes::from(frame))
+    }
+}
+
+impl AnonPipeWrite {
+    /// Send `payload` as a length-prefixed frame with a CRC32 trailer.
+    ///
+    /// Receive it with [`AnonPipeRead::read_frame_checked`].
+    pub async fn write_frame_checked(&mut self, payload: &[u8]) -> io::Result<()> {
+        let len = payload
+            .len()
+            .checked_add(TRAILER_LEN)
+            .and_then(|len| u32::try_from(len).ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("frame length {} exceeds {}", payload.len(), u32::MAX),
+                )
+            })?;
+        let trailer = crc32(payload).to_le_bytes();
+        self.write_all_chunks(&[&len.to_le_bytes(), payload, &trailer])
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[tokio::test]
+    async fn test_frame_checked() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_frame_checked(b"Hello, World!").await?;
+        w.write_frame_checked(b"").await?;
+        assert_eq!(&r.read_frame_checked(16).await?[..], b"Hello, World!");
+        assert_eq!(&r.read_frame_checked(16).await?[..], b"");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frame_checked_mismatch() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        let mut trailer = crc32(b"hello").to_le_bytes();
+        trailer[0] ^= 1;
+        w.write_all(&9u32.to_le_bytes()).await?;
+        w.write_all(b"hello").await?;
+        w.write_all(&trailer).await?;
+        let err = r.read_frame_checked(16).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frame_checked_oversize() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_frame_checked(&[0; 17]).await?;
+        let err = r.read_frame_checked(16).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+//! Spawn a child with one end of a pipe as its stdio.
+use std::fs::{File, OpenOptions};
+use std::process::{Child, Command};
+
+use tokio::io::{self, AsyncWriteExt};
+
+use crate::{
+    audit, connect_server, error, errors, retry_open_async, try_new_server_async, Access,
+    AnonPipeBuilder, AnonPipeRead, AnonPipeWrite, Deadline, Error, NamedPipeServer,
+};
+
+/// Create a connected pipe, with a synchronous client end for the child.
+async fn child_pipe(access: Access) -> io::Result<(String, NamedPipeServer, File)> {
+    let deadline = Deadline::default();
+    let (name, server) = try_new_server_async(&AnonPipeBuilder::new(), access, deadline).await?;
+    // not overlapped, as the stdio of a child is expected to be. std opens
+    // it non-inheritable, and duplicates it for the child only.
+    let client = retry_open_async(deadline, || {
+        OpenOptions::new()
+            .read(access.outbound())
+            .write(access.inbound())
+            .open(&name)
+    })
+    .await
+    .map_err(|err| error::classify(err, Error::OpenClient))?;
+    connect_server(&server, deadline)
+        .await
+        .map_err(|err| error::classify(err, Error::Connect))?;
+    Ok((name, server, client))
+}
+
+/// Spawn `cmd` reading `data` from its stdin, then EOF.
+///
+/// The child inherits the read end as its stdin and no other end: the ends
+/// of this crate are not inheritable, and `cmd` is consumed so that the
+/// copy of the read end it holds is closed once spawned. `data` is written
+/// before returning, and the write end is closed, so the child observes
+/// EOF after `data`. A child exiting before reading all of `data` is not
+/// an error.
+///
+/// As `data` must fit in the pipe or be read before returning, do not pipe
+/// the output of `cmd` when it may write much before reading all of its
+/// input, as nothing reads it yet.
+pub async fn spawn_with_child_reading(mut cmd: Command, data: &[u8]) -> io::Result<Child> {
+    let (name, server, client) = child_pipe(Access::Outbound).await?;
+    let mut w = audit::track(AnonPipeWrite::Server(server), Some(&name));
+    let child = cmd.stdin(client).spawn()?;
+    drop(cmd);
+
+    match w.write_all(data).await {
+        Err(err) if !errors::is_broken_pipe(&err) => Err(err),
+        _ => Ok(child),
+    }
+}
+
+/// Spawn `cmd` writing its stdout into the returned read end.
+///
+/// The child inherits the write end as its stdout and no other end, see
+/// [`spawn_with_child_reading`]. The read end observes EOF once the child,
+/// and any process it passed its stdout on to, exited.
+pub async fn spawn_with_child_writing(mut cmd: Command) -> io::Result<(Child, AnonPipeRead)> {
+    let (name, server, client) = child_pipe(Access::Inbound).await?;
+    let r = audit::track(AnonPipeRead::Server(server), Some(&name));
+    let child = cmd.stdout(client).spawn()?;
+    drop(cmd);
+    Ok((child, r))
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_spawn_with_child_reading() -> io::Result<()> {
+        let mut cmd = Command::new("findstr");
+        cmd.args(["/R", "^"]).stdout(Stdio::piped());
+        let child = spawn_with_child_reading(cmd, b"Hello\r\nWorld\r\n").await?;
+
+        // returns only on EOF of stdin.
+        let output = tokio::time::timeout(
+            Duration::from_secs(10),
+            tokio::task::spawn_blocking(move || child.wait_with_output()),
+        )
+        .await?
+        .unwrap()?;
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"Hello\r\nWorld\r\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_spawn_with_child_writing() -> io::Result<()> {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "echo Hello"]);
+        let (mut child, mut r) = spawn_with_child_writing(cmd).await?;
+
+        let mut buf = vec![];
+        tokio::time::timeout(Duration::from_secs(10), r.read_to_end(&mut buf)).await??;
+        assert!(child.wait()?.success());
+        assert_eq!(buf, b"Hello\r\n");
+        Ok(())
+    }
+}
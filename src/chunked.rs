@@ -0,0 +1,123 @@
+//! Cap the size of each write submission.
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{self, AsyncWrite};
+
+use crate::AnonPipeWrite;
+
+/// Write half submitting at most `max_chunk` bytes per write.
+///
+/// Created by [`AnonPipeWrite::chunked_writes`]. A larger write is
+/// accepted partially and reports the number of bytes written, as allowed
+/// by `AsyncWrite`, so `write_all` and friends continue with the rest.
+#[derive(Debug)]
+pub struct ChunkedWrite {
+    inner: AnonPipeWrite,
+    max_chunk: usize,
+}
+
+impl ChunkedWrite {
+    pub(crate) fn new(inner: AnonPipeWrite, max_chunk: usize) -> Self {
+        assert!(max_chunk > 0, "max_chunk must be positive");
+        Self { inner, max_chunk }
+    }
+
+    /// Maximum number of bytes per write.
+    pub fn max_chunk(&self) -> usize {
+        self.max_chunk
+    }
+
+    /// Gets a reference to the underlying write half.
+    pub fn get_ref(&self) -> &AnonPipeWrite {
+        &self.inner
+    }
+
+    /// Unwraps the underlying write half.
+    pub fn into_inner(self) -> AnonPipeWrite {
+        self.inner
+    }
+}
+
+impl AsyncWrite for ChunkedWrite {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let len = buf.len().min(this.max_chunk);
+        Pin::new(&mut this.inner).poll_write(cx, &buf[..len])
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        // keep whole slices up to the cap, cut the first one exceeding it.
+        let mut capped = Vec::with_capacity(bufs.len());
+        let mut len = 0;
+        for buf in bufs {
+            if len == this.max_chunk {
+                break;
+            }
+            let n = buf.len().min(this.max_chunk - len);
+            capped.push(std::io::IoSlice::new(&buf[..n]));
+            len += n;
+        }
+        Pin::new(&mut this.inner).poll_write_vectored(cx, &capped)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anon_pipe;
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_chunked_writes() -> io::Result<()> {
+        let (mut r, w) = anon_pipe().await?;
+        let mut w = w.chunked_writes(1000);
+        let data = (0..100_000).map(|n| n as u8).collect::<Vec<_>>();
+
+        let reader = tokio::spawn(async move {
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            Ok::<_, io::Error>(buf)
+        });
+        let n = w.write(&data).await?;
+        assert!(n <= 1000);
+        w.write_all(&data[n..]).await?;
+        drop(w);
+        assert_eq!(reader.await.unwrap()?, data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunked_writes_vectored() -> io::Result<()> {
+        let (mut r, w) = anon_pipe().await?;
+        let mut w = w.chunked_writes(3);
+
+        let bufs = [std::io::IoSlice::new(b"He"), std::io::IoSlice::new(b"llo")];
+        let n = w.write_vectored(&bufs).await?;
+        assert!(n <= 3);
+        let mut buf = vec![0; n];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&buf, &b"Hel"[..n]);
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+//! zlib (deflate) compression of pipe traffic, with [`flate2`].
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use tokio::io::{self, AsyncRead, AsyncWrite};
+
+use crate::{AnonPipeRead, AnonPipeWrite, AsRawHandle, RawHandle};
+
+/// Input compressed per write, and compressed output buffered before
+/// writing it to the pipe.
+const BLOCK: usize = 32 * 1024;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Write half compressing everything written with zlib.
+///
+/// Created by [`CompressedWrite::new`]. Read the other end with
+/// [`CompressedRead`].
+///
+/// `flush` makes everything written so far decompressible by the peer, at
+/// the cost of a few bytes. `shutdown` ends the compressed stream; dropping
+/// the write half without shutting it down truncates the stream, and the
+/// peer fails with `ErrorKind::InvalidData`.
+#[derive(Debug)]
+pub struct CompressedWrite {
+    inner: AnonPipeWrite,
+    compress: Compress,
+    out: Vec<u8>,
+    written: usize,
+    dirty: bool,
+    finished: bool,
+}
+
+impl CompressedWrite {
+    /// Compress with `level`, from `0` (store only) to `9` (best).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is greater than `9`.
+    pub fn new(inner: AnonPipeWrite, level: u32) -> Self {
+        assert!(level <= 9, "level must be 0 to 9");
+        Self {
+            inner,
+            compress: Compress::new(Compression::new(level), true),
+            out: Vec::with_capacity(BLOCK),
+            written: 0,
+            dirty: false,
+            finished: false,
+        }
+    }
+
+    /// Gets a reference to the underlying write half.
+    pub fn get_ref(&self) -> &AnonPipeWrite {
+        &self.inner
+    }
+
+    /// Compress all of `input` into the output, then `flush`.
+    fn deflate(&mut self, mut input: &[u8], flush: FlushCompress) -> io::Result<()> {
+        loop {
+            self.out.reserve(BLOCK);
+            let before = self.compress.total_in();
+            let status = self
+                .compress
+                .compress_vec(input, &mut self.out, flush)
+                .map_err(io::Error::other)?;
+            input = &input[(self.compress.total_in() - before) as usize..];
+            // done once the output is not filled up.
+            if status == Status::StreamEnd
+                || (input.is_empty() && self.out.len() < self.out.capacity())
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Write the compressed bytes produced so far.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.out.len() {
+            let n = match Pin::new(&mut self.inner).poll_write(cx, &self.out[self.written..]) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => return Poll::Pending,
+            };
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        self.out.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for CompressedWrite {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(Err(io::Error::other("write after shutdown")));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        if this.out.len() - this.written >= BLOCK {
+            match this.poll_drain(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let n = buf.len().min(BLOCK);
+        this.deflate(&buf[..n], FlushCompress::None)?;
+        this.dirty = true;
+        if let Poll::Ready(Err(err)) = this.poll_drain(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.dirty && !this.finished {
+            this.deflate(&[], FlushCompress::Sync)?;
+            this.dirty = false;
+        }
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            this.deflate(&[], FlushCompress::Finish)?;
+            this.finished = true;
+        }
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_shutdown(cx),
+            other => other,
+        }
+    }
+}
+
+impl AsRawHandle for CompressedWrite {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.inner.as_raw_handle()
+    }
+}
+
+/// Read half decompressing what a [`CompressedWrite`] wrote.
+///
+/// Created by [`CompressedRead::new`]. Fails with `ErrorKind::InvalidData`
+/// if the peer doesn't write a zlib stream, or closes before ending it.
+#[derive(Debug)]
+pub struct CompressedRead {
+    inner: AnonPipeRead,
+    decompress: Decompress,
+    input: Vec<u8>,
+    pos: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl CompressedRead {
+    /// Decompress what is read from `inner`.
+    pub fn new(inner: AnonPipeRead) -> Self {
+        Self {
+            inner,
+            decompress: Decompress::new(true),
+            input: vec![],
+            pos: 0,
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Gets a reference to the underlying read half.
+    pub fn get_ref(&self) -> &AnonPipeRead {
+        &self.inner
+    }
+}
+
+impl AsyncRead for CompressedRead {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.done || buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            // also without input, the output of the last call may be pending.
+            let (before_in, before_out) = (this.decompress.total_in(), this.decompress.total_out());
+            let status = this
+                .decompress
+                .decompress(
+                    &this.input[this.pos..],
+                    buf.initialize_unfilled(),
+                    FlushDecompress::None,
+                )
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            this.pos += (this.decompress.total_in() - before_in) as usize;
+            let n = (this.decompress.total_out() - before_out) as usize;
+            buf.advance(n);
+            if status == Status::StreamEnd {
+                this.done = true;
+            }
+            if n > 0 || this.done {
+                return Poll::Ready(Ok(()));
+            }
+            if this.decompress.total_in() > before_in {
+                continue;
+            }
+            if this.eof {
+                return Poll::Ready(Err(invalid_data("truncated zlib stream")));
+            }
+
+            let mut chunk = [0; 8 * 1024];
+            let mut chunk = io::ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+            if chunk.filled().is_empty() {
+                this.eof = true;
+            }
+            this.input.drain(..this.pos);
+            this.pos = 0;
+            this.input.extend_from_slice(chunk.filled());
+        }
+    }
+}
+
+impl AsRawHandle for CompressedRead {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.inner.as_raw_handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn text() -> Vec<u8> {
+        (0..20000)
+            .flat_map(|i| format!("line {}: the quick brown fox\n", i % 37).into_bytes())
+            .collect()
+    }
+
+    fn noise(len: usize) -> Vec<u8> {
+        let mut x = 0x1234_5678u64;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_compressed() -> io::Result<()> {
+        for data in [text(), noise(256 * 1024)] {
+            let (r, w) = anon_pipe().await?;
+            let mut r = CompressedRead::new(r);
+            let mut w = CompressedWrite::new(w, 6);
+            let expected = data.clone();
+            let writer = tokio::spawn(async move {
+                for chunk in data.chunks(10_000) {
+                    w.write_all(chunk).await?;
+                }
+                w.shutdown().await
+            });
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            writer.await.unwrap()?;
+            assert_eq!(buf, expected);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compressed_flush() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        let mut r = CompressedRead::new(r);
+        let mut w = CompressedWrite::new(w, 1);
+
+        for msg in [&b"ping"[..], b"Hello, World!"] {
+            w.write_all(msg).await?;
+            w.flush().await?;
+            let mut buf = vec![0; msg.len()];
+            r.read_exact(&mut buf).await?;
+            assert_eq!(buf, msg);
+        }
+
+        drop(w);
+        let err = r.read(&mut [0; 16]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compressed_mismatch() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let mut r = CompressedRead::new(r);
+
+        w.write_all(b"Hello, World!").await?;
+        let err = r.read(&mut [0; 16]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+}
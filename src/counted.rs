@@ -0,0 +1,181 @@
+//! Byte counters shared by the ends of a pair.
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::io::{self, AsyncRead, AsyncWrite};
+
+/// Byte counters of a pair, cheap to clone.
+///
+/// Shared by both ends created by
+/// [`anon_pipe_counted`](crate::anon_pipe_counted), so that
+/// [`in_flight`](Self::in_flight) tells the queue depth of the pipe.
+#[derive(Debug, Clone, Default)]
+pub struct PipeStats(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    written: AtomicU64,
+    read: AtomicU64,
+}
+
+impl PipeStats {
+    /// Create new counters, all zero.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Number of bytes written.
+    pub fn written(&self) -> u64 {
+        self.0.written.load(Ordering::Acquire)
+    }
+
+    /// Number of bytes read.
+    pub fn read(&self) -> u64 {
+        self.0.read.load(Ordering::Acquire)
+    }
+
+    /// Number of bytes written and not read yet.
+    pub fn in_flight(&self) -> u64 {
+        // bytes are written before they are read, load in reverse order.
+        let read = self.read();
+        self.written().saturating_sub(read)
+    }
+}
+
+/// End counting the bytes through it into a [`PipeStats`].
+///
+/// Created in pairs by [`anon_pipe_counted`](crate::anon_pipe_counted),
+/// sharing one [`PipeStats`]. An end wrapped with [`new`](Self::new) gets
+/// its own.
+#[derive(Debug)]
+pub struct Counted<T> {
+    inner: T,
+    stats: PipeStats,
+}
+
+impl<T> Counted<T> {
+    /// Count the bytes through `inner` into new counters.
+    pub fn new(inner: T) -> Self {
+        Self::with_stats(inner, PipeStats::new())
+    }
+
+    /// Count the bytes through `inner` into `stats`.
+    pub fn with_stats(inner: T, stats: PipeStats) -> Self {
+        Self { inner, stats }
+    }
+
+    /// Counters of this end, shared with its peer if created as a pair.
+    pub fn stats(&self) -> PipeStats {
+        self.stats.clone()
+    }
+
+    /// Gets a reference to the underlying end.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Unwraps the underlying end.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Counted<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        let n = (buf.filled().len() - filled) as u64;
+        this.stats.0.read.fetch_add(n, Ordering::AcqRel);
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Counted<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            this.stats.0.written.fetch_add(n as u64, Ordering::AcqRel);
+        }
+        result
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(n)) = result {
+            this.stats.0.written.fetch_add(n as u64, Ordering::AcqRel);
+        }
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{anon_pipe, anon_pipe_counted};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_counted_in_flight() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe_counted().await?;
+        let stats = r.stats();
+
+        w.write_all(&[0; 100]).await?;
+        w.write_all(&[0; 50]).await?;
+        assert_eq!(stats.written(), 150);
+        assert_eq!(stats.in_flight(), 150);
+
+        let mut buf = [0; 120];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(stats.read(), 120);
+        assert_eq!(stats.in_flight(), 30);
+
+        w.write_all(&[0; 10]).await?;
+        assert_eq!(w.stats().in_flight(), 40);
+        let mut buf = [0; 40];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(stats.in_flight(), 0);
+        assert_eq!(stats.written(), 160);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_counted_independent() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        let r = Counted::new(r);
+        let mut w = Counted::new(w);
+
+        w.write_all(b"Hello").await?;
+        assert_eq!(w.stats().written(), 5);
+        assert_eq!(r.stats().written(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,407 @@
+//! Exact reads and writes bounded by a deadline.
+use std::error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{self, AsyncRead, AsyncWrite};
+use tokio::time::{Instant, Sleep};
+
+use crate::{AnonPipeRead, AnonPipeWrite};
+
+/// Failure of [`AnonPipeRead::read_exact_timeout`],
+/// [`AnonPipeRead::read_idle_timeout`],
+/// [`AnonPipeWrite::write_all_timeout`] or `AnonPipeWrite::write_owned`,
+/// with the number of bytes transferred before it.
+///
+/// Converts into an `io::Error` of the same kind with `?`.
+#[derive(Debug)]
+pub struct TimeoutPartial {
+    transferred: usize,
+    source: io::Error,
+}
+
+impl TimeoutPartial {
+    pub(crate) fn new(transferred: usize, source: io::Error) -> Self {
+        Self {
+            transferred,
+            source,
+        }
+    }
+
+    /// Number of bytes read into the buffer, or written from it.
+    pub fn transferred(&self) -> usize {
+        self.transferred
+    }
+
+    /// `true` if the deadline elapsed, `false` for an I/O error.
+    pub fn is_timeout(&self) -> bool {
+        self.source.kind() == io::ErrorKind::TimedOut
+    }
+
+    /// Kind of the failure.
+    pub fn kind(&self) -> io::ErrorKind {
+        self.source.kind()
+    }
+}
+
+impl fmt::Display for TimeoutPartial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} after {} bytes transferred",
+            self.source, self.transferred
+        )
+    }
+}
+
+impl error::Error for TimeoutPartial {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<TimeoutPartial> for io::Error {
+    fn from(err: TimeoutPartial) -> Self {
+        io::Error::new(err.source.kind(), err)
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "deadline elapsed")
+}
+
+/// Future of [`AnonPipeRead::read_exact_timeout`].
+///
+/// Poll it by reference (it is `Unpin`) to be able to give up on it before
+/// the deadline: [`filled`](Self::filled) then tells how many bytes at the
+/// start of the buffer were read. Nothing is lost, every chunk read is in
+/// the buffer and counted before the next poll.
+///
+/// ```no_run
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// use std::time::Duration;
+/// use tokio::time::Instant;
+///
+/// let (mut r, w) = tokio_anon_pipe::anon_pipe().await?;
+/// let mut buf = [0; 16];
+/// let mut read = r.read_exact_timeout(&mut buf, Instant::now() + Duration::from_secs(10));
+/// tokio::select! {
+///     result = &mut read => { /* ... */ }
+///     _ = tokio::time::sleep(Duration::from_secs(1)) => {
+///         let filled = read.filled();
+///         // ... `buf[..filled]` was read ...
+///     }
+/// }
+/// # drop(w);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReadExactTimeout<'a> {
+    inner: &'a mut AnonPipeRead,
+    buf: &'a mut [u8],
+    filled: usize,
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl ReadExactTimeout<'_> {
+    /// Number of bytes read into the buffer so far.
+    pub fn filled(&self) -> usize {
+        self.filled
+    }
+}
+
+impl Future for ReadExactTimeout<'_> {
+    type Output = Result<(), TimeoutPartial>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        while this.filled < this.buf.len() {
+            let mut read = io::ReadBuf::new(&mut this.buf[this.filled..]);
+            let source = match Pin::new(&mut *this.inner).poll_read(cx, &mut read) {
+                Poll::Ready(Ok(())) if read.filled().is_empty() => {
+                    io::ErrorKind::UnexpectedEof.into()
+                }
+                Poll::Ready(Ok(())) => {
+                    this.filled += read.filled().len();
+                    continue;
+                }
+                Poll::Ready(Err(err)) => err,
+                Poll::Pending => match this.sleep.as_mut().poll(cx) {
+                    Poll::Ready(()) => timed_out(),
+                    Poll::Pending => return Poll::Pending,
+                },
+            };
+            return Poll::Ready(Err(TimeoutPartial::new(this.filled, source)));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Future of [`AnonPipeWrite::write_all_timeout`].
+///
+/// Poll it by reference (it is `Unpin`) to be able to give up on it before
+/// the deadline: [`written`](Self::written) then tells how many bytes at
+/// the start of the buffer the reader may observe.
+#[derive(Debug)]
+pub struct WriteAllTimeout<'a> {
+    inner: &'a mut AnonPipeWrite,
+    buf: &'a [u8],
+    written: usize,
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl WriteAllTimeout<'_> {
+    /// Number of bytes written from the buffer so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+}
+
+impl Future for WriteAllTimeout<'_> {
+    type Output = Result<(), TimeoutPartial>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        while this.written < this.buf.len() {
+            let source = match Pin::new(&mut *this.inner).poll_write(cx, &this.buf[this.written..])
+            {
+                Poll::Ready(Ok(0)) => io::ErrorKind::WriteZero.into(),
+                Poll::Ready(Ok(n)) => {
+                    this.written += n;
+                    continue;
+                }
+                Poll::Ready(Err(err)) => err,
+                Poll::Pending => match this.sleep.as_mut().poll(cx) {
+                    Poll::Ready(()) => timed_out(),
+                    Poll::Pending => return Poll::Pending,
+                },
+            };
+            return Poll::Ready(Err(TimeoutPartial::new(this.written, source)));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AnonPipeRead {
+    /// Read exactly `buf.len()` bytes, or give up at `deadline`.
+    ///
+    /// On failure, [`TimeoutPartial::transferred`] tells how many bytes at
+    /// the start of `buf` were filled; they are consumed from the pipe.
+    /// After a timeout the rest may still arrive and is returned by the
+    /// next read. Fails with `ErrorKind::UnexpectedEof` if the writer closes
+    /// first.
+    ///
+    /// The returned [`ReadExactTimeout`] can also be given up on before the
+    /// deadline without losing track of the bytes filled, see
+    /// [`ReadExactTimeout::filled`].
+    ///
+    /// Must be called within a tokio runtime with the time driver enabled.
+    pub fn read_exact_timeout<'a>(
+        &'a mut self,
+        buf: &'a mut [u8],
+        deadline: Instant,
+    ) -> ReadExactTimeout<'a> {
+        ReadExactTimeout {
+            inner: self,
+            buf,
+            filled: 0,
+            sleep: Box::pin(tokio::time::sleep_until(deadline)),
+        }
+    }
+
+    /// Read until `buf` is full or EOF, or give up after `idle` without
+    /// any byte arriving.
+    ///
+    /// Unlike [`read_exact_timeout`](Self::read_exact_timeout), the timer
+    /// restarts whenever bytes arrive, so a slow but steady stream never
+    /// times out. Returns the number of bytes filled, less than `buf.len()`
+    /// only at EOF. On failure, [`TimeoutPartial::transferred`] tells how
+    /// many bytes at the start of `buf` were filled.
+    ///
+    /// Must be called within a tokio runtime with the time driver enabled.
+    pub async fn read_idle_timeout(
+        &mut self,
+        buf: &mut [u8],
+        idle: Duration,
+    ) -> Result<usize, TimeoutPartial> {
+        let sleep = tokio::time::sleep(idle);
+        tokio::pin!(sleep);
+        let mut filled = 0;
+        let result = std::future::poll_fn(|cx| {
+            while filled < buf.len() {
+                let mut read = io::ReadBuf::new(&mut buf[filled..]);
+                match Pin::new(&mut *self).poll_read(cx, &mut read) {
+                    Poll::Ready(Ok(())) if read.filled().is_empty() => break, // EOF
+                    Poll::Ready(Ok(())) => {
+                        filled += read.filled().len();
+                        sleep.as_mut().reset(Instant::now() + idle);
+                    }
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => {
+                        return match sleep.as_mut().poll(cx) {
+                            Poll::Ready(()) => Poll::Ready(Err(timed_out())),
+                            Poll::Pending => Poll::Pending,
+                        }
+                    }
+                }
+            }
+            Poll::Ready(Ok(()))
+        })
+        .await;
+        match result {
+            Ok(()) => Ok(filled),
+            Err(source) => Err(TimeoutPartial::new(filled, source)),
+        }
+    }
+}
+
+impl AnonPipeWrite {
+    /// Write all of `buf`, or give up at `deadline`.
+    ///
+    /// On failure, [`TimeoutPartial::transferred`] tells how many bytes at
+    /// the start of `buf` were written. After a timeout the reader observes
+    /// exactly those; writing the rest later continues the stream.
+    ///
+    /// The returned [`WriteAllTimeout`] can also be given up on before the
+    /// deadline without losing track of the bytes written, see
+    /// [`WriteAllTimeout::written`].
+    ///
+    /// Must be called within a tokio runtime with the time driver enabled.
+    pub fn write_all_timeout<'a>(
+        &'a mut self,
+        buf: &'a [u8],
+        deadline: Instant,
+    ) -> WriteAllTimeout<'a> {
+        WriteAllTimeout {
+            inner: self,
+            buf,
+            written: 0,
+            sleep: Box::pin(tokio::time::sleep_until(deadline)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_read_exact_timeout() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"abc").await?;
+        let mut buf = [0; 10];
+        let deadline = Instant::now() + Duration::from_millis(100);
+        let err = r.read_exact_timeout(&mut buf, deadline).await.unwrap_err();
+        assert!(err.is_timeout());
+        assert_eq!(err.transferred(), 3);
+        assert_eq!(&buf[..3], b"abc");
+
+        // the rest arrives later.
+        w.write_all(b"defghij").await?;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        r.read_exact_timeout(&mut buf[3..], deadline).await?;
+        assert_eq!(&buf, b"abcdefghij");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_exact_timeout_given_up() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"abc").await?;
+        let mut buf = [0; 10];
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut read = r.read_exact_timeout(&mut buf, deadline);
+        tokio::select! {
+            _ = &mut read => unreachable!(),
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+        assert_eq!(read.filled(), 3);
+        assert_eq!(&buf[..3], b"abc");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_exact_timeout_eof() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"ab").await?;
+        drop(w);
+        let mut buf = [0; 4];
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let err = r.read_exact_timeout(&mut buf, deadline).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(err.transferred(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_idle_timeout() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        let writer = tokio::spawn(async move {
+            // longer than `idle` in total, but never silent for that long.
+            for b in 0..10 {
+                w.write_all(&[b]).await?;
+                tokio::time::sleep(Duration::from_millis(30)).await;
+            }
+            Ok::<_, io::Error>(w)
+        });
+
+        let mut buf = [0; 10];
+        let n = r
+            .read_idle_timeout(&mut buf, Duration::from_millis(200))
+            .await?;
+        assert_eq!(n, 10);
+        assert_eq!(buf, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+        // silence.
+        let w = writer.await.unwrap()?;
+        let err = r
+            .read_idle_timeout(&mut buf, Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(err.is_timeout());
+        assert_eq!(err.transferred(), 0);
+
+        drop(w);
+        assert_eq!(
+            r.read_idle_timeout(&mut buf, Duration::from_secs(10))
+                .await?,
+            0
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_all_timeout() -> io::Result<()> {
+        let (_r, mut w) = anon_pipe().await?;
+
+        // the reader never reads, the first write stays in flight.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        w.write_all_timeout(&[0; 1024 * 1024], deadline).await?;
+        let deadline = Instant::now() + Duration::from_millis(100);
+        let err = w.write_all_timeout(b"more", deadline).await.unwrap_err();
+        assert!(err.is_timeout());
+        assert_eq!(err.transferred(), 0);
+        let err = io::Error::from(err);
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut write = w.write_all_timeout(b"more", deadline);
+        tokio::select! {
+            _ = &mut write => unreachable!(),
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+        assert_eq!(write.written(), 0);
+        Ok(())
+    }
+}
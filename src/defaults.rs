@@ -0,0 +1,98 @@
+//! Process-wide defaults.
+use std::sync::OnceLock;
+
+use crate::{ServerOptions, DEFAULT_MAX_CONCURRENT_CREATIONS};
+
+static GLOBAL_DEFAULTS: OnceLock<AnonPipeDefaults> = OnceLock::new();
+
+/// Defaults applied to every pipe created by this crate.
+///
+/// Installed by [`set_global_defaults`]. Options set on
+/// [`AnonPipeBuilder`](crate::AnonPipeBuilder), including its
+/// `configure_server` hook, take precedence.
+#[derive(Debug, Clone, Default)]
+pub struct AnonPipeDefaults {
+    in_buffer_size: Option<u32>,
+    out_buffer_size: Option<u32>,
+    max_instances: Option<usize>,
+    max_concurrent_creations: Option<usize>,
+}
+
+impl AnonPipeDefaults {
+    /// Create new defaults, same as the crate defaults.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Input buffer size of the server end.
+    pub fn in_buffer_size(&mut self, size: u32) -> &mut Self {
+        self.in_buffer_size = Some(size);
+        self
+    }
+
+    /// Output buffer size of the server end.
+    pub fn out_buffer_size(&mut self, size: u32) -> &mut Self {
+        self.out_buffer_size = Some(size);
+        self
+    }
+
+    /// Maximum number of instances sharing the pipe name.
+    ///
+    /// See [`AnonPipeBuilder::max_instances`](crate::AnonPipeBuilder::max_instances).
+    pub fn max_instances(&mut self, instances: usize) -> &mut Self {
+        self.max_instances = Some(instances);
+        self
+    }
+
+    /// Maximum number of server ends created at the same time by the
+    /// process. Default is [`DEFAULT_MAX_CONCURRENT_CREATIONS`].
+    ///
+    /// Bursts of simultaneous `CreateNamedPipe` calls may fail transiently
+    /// with `ERROR_ACCESS_DENIED`, each costing a retry. Creations beyond
+    /// the limit wait for a running one to complete: the async
+    /// constructors yield to the runtime meanwhile, the others block their
+    /// thread for as long as a creation takes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is `0`.
+    pub fn max_concurrent_creations(&mut self, limit: usize) -> &mut Self {
+        assert!(limit > 0, "limit must be positive");
+        self.max_concurrent_creations = Some(limit);
+        self
+    }
+
+    pub(crate) fn apply(&self, opts: &mut ServerOptions) {
+        if let Some(size) = self.in_buffer_size {
+            opts.in_buffer_size(size);
+        }
+        if let Some(size) = self.out_buffer_size {
+            opts.out_buffer_size(size);
+        }
+    }
+}
+
+/// Install process-wide defaults.
+///
+/// Call once at startup, before creating any pipe. Thread safe, but the
+/// defaults can be set only once: later calls fail and return the given
+/// defaults back. Pipes created before the call do not change.
+pub fn set_global_defaults(defaults: AnonPipeDefaults) -> Result<(), AnonPipeDefaults> {
+    GLOBAL_DEFAULTS.set(defaults)
+}
+
+pub(crate) fn global_defaults() -> Option<&'static AnonPipeDefaults> {
+    GLOBAL_DEFAULTS.get()
+}
+
+/// `max_instances` of `explicit`, or of the global defaults.
+pub(crate) fn max_instances(explicit: Option<usize>) -> Option<usize> {
+    explicit.or_else(|| global_defaults()?.max_instances)
+}
+
+/// `max_concurrent_creations` of the global defaults, or the default.
+pub(crate) fn max_concurrent_creations() -> usize {
+    global_defaults()
+        .and_then(|defaults| defaults.max_concurrent_creations)
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_CREATIONS)
+}
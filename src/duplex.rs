@@ -0,0 +1,713 @@
+//! Duplex pipe.
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    audit, errors, peek_connected, AnonPipeRead, AnonPipeWrite, AsRawHandle, IntoRawHandle,
+    NamedPipeClient, NamedPipeServer, PipeInfo, RawHandle,
+};
+
+/// Asyncronous Duplex Pipe.
+///
+/// Both directions share one handle.
+/// `shutdown` does not close the write direction, the peer observes EOF
+/// once this end is dropped.
+///
+/// Reads and writes keep separate wakers, so a read and a write pending
+/// at the same time are both woken. Use [`into_split`](Self::into_split)
+/// to drive the directions from different tasks.
+///
+/// Created in pairs by [`anon_pipe_duplex_pair`](crate::anon_pipe_duplex_pair),
+/// the same shape as `tokio::io::duplex`, so code generic over
+/// `AsyncRead + AsyncWrite` runs over either transport.
+#[derive(Debug)]
+pub enum AnonPipeDuplex {
+    Server(NamedPipeServer),
+    Client(NamedPipeClient),
+}
+
+impl AnonPipeDuplex {
+    /// Buffer sizes and mode of the pipe.
+    pub fn pipe_info(&self) -> io::Result<PipeInfo> {
+        PipeInfo::query(self.as_raw_handle())
+    }
+
+    /// Maximum number of instances of the pipe name.
+    ///
+    /// See [`AnonPipeRead::max_instances`](crate::AnonPipeRead::max_instances).
+    pub fn max_instances(&self) -> io::Result<u32> {
+        crate::max_instances(self.as_raw_handle(), matches!(self, Self::Server(..)))
+    }
+
+    /// Number of instances of the pipe name currently open.
+    ///
+    /// See [`AnonPipeRead::current_instances`](crate::AnonPipeRead::current_instances).
+    pub fn current_instances(&self) -> io::Result<u32> {
+        crate::current_instances(self.as_raw_handle(), matches!(self, Self::Server(..)))
+    }
+
+    /// `true` if the peer is attached, without IO.
+    ///
+    /// See [`AnonPipeRead::is_connected`](crate::AnonPipeRead::is_connected).
+    pub fn is_connected(&self) -> io::Result<bool> {
+        peek_connected(self.as_raw_handle())
+    }
+
+    /// Split into a read half and a write half, usable from different
+    /// tasks.
+    ///
+    /// Unlike `tokio::io::split`, the halves share no lock: each polls the
+    /// readiness of its own direction. The handle is closed once both
+    /// halves are dropped.
+    pub fn into_split(self) -> (DuplexReadHalf, DuplexWriteHalf) {
+        let inner = Arc::new(self);
+        (DuplexReadHalf(inner.clone()), DuplexWriteHalf(inner))
+    }
+
+    /// Keep the read direction only, e.g. once a handshake is done.
+    ///
+    /// Waits until the write in flight, if any, completed, and fails with
+    /// its error. Nothing can be written afterwards.
+    ///
+    /// A pipe has no half-close: both directions share one handle, so the
+    /// peer observes EOF on the direction given up only once the returned
+    /// end is dropped too. Until then, a peer reading keeps waiting.
+    pub async fn into_read_only(mut self) -> io::Result<AnonPipeRead> {
+        std::future::poll_fn(|cx| self.poll_write_ready(cx)).await?;
+        self.flush().await?;
+        let read = self.into_inner(AnonPipeRead::Server, AnonPipeRead::Client);
+        Ok(audit::track(read, None))
+    }
+
+    /// Keep the write direction only, e.g. once a handshake is done.
+    ///
+    /// Nothing can be read afterwards: bytes received and not read yet are
+    /// lost, and a peer writing more fills the pipe buffer and then waits,
+    /// as nothing drains it.
+    ///
+    /// A pipe has no half-close: both directions share one handle, so the
+    /// peer observes `ErrorKind::BrokenPipe` on the direction given up only
+    /// once the returned end is dropped too.
+    pub fn into_write_only(self) -> AnonPipeWrite {
+        let write = self.into_inner(AnonPipeWrite::Server, AnonPipeWrite::Client);
+        audit::track(write, None)
+    }
+
+    /// Move the server or the client out, without running `Drop`.
+    fn into_inner<T>(
+        self,
+        server: impl FnOnce(NamedPipeServer) -> T,
+        client: impl FnOnce(NamedPipeClient) -> T,
+    ) -> T {
+        audit::untrack(&self);
+        let this = mem::ManuallyDrop::new(self);
+        // SAFETY: `this` is never used nor dropped again.
+        unsafe {
+            match &*this {
+                Self::Server(inner) => server(std::ptr::read(inner)),
+                Self::Client(inner) => client(std::ptr::read(inner)),
+            }
+        }
+    }
+
+    /// Poll until a read would not block, see
+    /// [`AnonPipeRead::poll_read_ready`]. Cancel safe.
+    pub fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self {
+            Self::Server(inner) => inner.poll_read_ready(cx),
+            Self::Client(inner) => inner.poll_read_ready(cx),
+        }
+    }
+
+    /// Read what arrived into `buf` without waiting, see
+    /// [`AnonPipeRead::try_read`].
+    pub fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Server(inner) => inner.try_read(buf),
+            Self::Client(inner) => inner.try_read(buf),
+        }
+    }
+
+    /// Poll until the write in flight, if any, completed, see
+    /// [`AnonPipeWrite::poll_write_ready`]. Cancel safe.
+    pub fn poll_write_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self {
+            Self::Server(inner) => inner.poll_write_ready(cx),
+            Self::Client(inner) => inner.poll_write_ready(cx),
+        }
+    }
+
+    /// Write from `buf` without waiting, see [`AnonPipeWrite::try_write`].
+    pub fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Server(inner) => inner.try_write(buf),
+            Self::Client(inner) => inner.try_write(buf),
+        }
+    }
+
+    fn poll_read_shared(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            match self.poll_read_ready(cx) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => return Poll::Pending,
+            }
+            match self.try_read(buf.initialize_unfilled()) {
+                Ok(n) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                // readiness was stale.
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+    }
+
+    fn poll_write_shared(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        loop {
+            match self.poll_write_ready(cx) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => return Poll::Pending,
+            }
+            match self.try_write(buf) {
+                // readiness was stale.
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    /// Clear buffered bytes in both directions, e.g. between requests.
+    ///
+    /// Waits until in-flight writes completed and flushes, then discards
+    /// the inbound bytes which arrived already, without waiting for more.
+    /// Bytes the peer sends later are not affected, so this cannot recover
+    /// from a protocol desync: the peer must not have a reply in transit.
+    /// Stops discarding at EOF.
+    pub async fn reset(&mut self) -> io::Result<()> {
+        std::future::poll_fn(|cx| self.poll_write_ready(cx)).await?;
+        self.flush().await?;
+
+        let mut scratch = [0; 4096];
+        std::future::poll_fn(|cx| loop {
+            let mut buf = io::ReadBuf::new(&mut scratch);
+            match Pin::new(&mut *self).poll_read(cx, &mut buf) {
+                Poll::Ready(Ok(())) if !buf.filled().is_empty() => continue,
+                Poll::Ready(Ok(())) | Poll::Pending => return Poll::Ready(Ok(())),
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            }
+        })
+        .await
+    }
+}
+
+impl AsyncRead for AnonPipeDuplex {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Server(ref mut inner) => Pin::new(inner).poll_read(cx, buf),
+            Self::Client(ref mut inner) => Pin::new(inner).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for AnonPipeDuplex {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        match self.get_mut() {
+            Self::Server(ref mut inner) => Pin::new(inner).poll_write(cx, buf),
+            Self::Client(ref mut inner) => Pin::new(inner).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        match self.get_mut() {
+            Self::Server(ref mut inner) => Pin::new(inner).poll_write_vectored(cx, bufs),
+            Self::Client(ref mut inner) => Pin::new(inner).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Self::Server(inner) => inner.is_write_vectored(),
+            Self::Client(inner) => inner.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        match self.get_mut() {
+            Self::Server(ref mut inner) => Pin::new(inner).poll_flush(cx),
+            Self::Client(ref mut inner) => Pin::new(inner).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        match self.get_mut() {
+            Self::Server(ref mut inner) => Pin::new(inner).poll_shutdown(cx),
+            Self::Client(ref mut inner) => Pin::new(inner).poll_shutdown(cx),
+        }
+    }
+}
+
+impl IntoRawHandle for AnonPipeDuplex {
+    fn into_raw_handle(self) -> RawHandle {
+        let h = self.as_raw_handle();
+        audit::untrack(&self);
+        mem::forget(self);
+        h
+    }
+}
+
+impl AsRawHandle for AnonPipeDuplex {
+    fn as_raw_handle(&self) -> RawHandle {
+        match self {
+            Self::Server(inner) => inner.as_raw_handle(),
+            Self::Client(inner) => inner.as_raw_handle(),
+        }
+    }
+}
+
+/// Read half of an [`AnonPipeDuplex`], created by
+/// [`AnonPipeDuplex::into_split`].
+#[derive(Debug)]
+pub struct DuplexReadHalf(Arc<AnonPipeDuplex>);
+
+/// Write half of an [`AnonPipeDuplex`], created by
+/// [`AnonPipeDuplex::into_split`].
+///
+/// `flush` and `shutdown` complete immediately, as on the duplex end.
+#[derive(Debug)]
+pub struct DuplexWriteHalf(Arc<AnonPipeDuplex>);
+
+impl AsyncRead for DuplexReadHalf {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.0.poll_read_shared(cx, buf)
+    }
+}
+
+impl AsyncWrite for DuplexWriteHalf {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        self.0.poll_write_shared(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsRawHandle for DuplexReadHalf {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.0.as_raw_handle()
+    }
+}
+
+impl AsRawHandle for DuplexWriteHalf {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.0.as_raw_handle()
+    }
+}
+
+/// Copy data in both directions between `a` and `b` until both reach EOF.
+///
+/// When one direction reaches EOF, the write side of the other stream is
+/// shut down while the opposite direction keeps flowing. Returns the number
+/// of bytes copied from `a` to `b` and from `b` to `a`.
+///
+/// Note that shutting down an [`AnonPipeDuplex`] does not close it, its
+/// peer observes EOF once `a` is dropped.
+pub async fn copy_bidirectional<B>(a: &mut AnonPipeDuplex, b: &mut B) -> io::Result<(u64, u64)>
+where
+    B: AsyncRead + AsyncWrite + Unpin + ?Sized,
+{
+    let (mut ar, mut aw) = io::split(a);
+    let (mut br, mut bw) = io::split(b);
+    let a_to_b = async {
+        let n = io::copy(&mut ar, &mut bw).await?;
+        bw.shutdown().await?;
+        Ok(n)
+    };
+    let b_to_a = async {
+        let n = io::copy(&mut br, &mut aw).await?;
+        aw.shutdown().await?;
+        Ok(n)
+    };
+    try_join(a_to_b, b_to_a).await
+}
+
+/// Tunnel between `a` and `b`: copy data in both directions until either
+/// side reaches EOF, then close both.
+///
+/// A pipe has no half-close, EOF means the peer closed its end in both
+/// directions and nothing more can be delivered to it. The tunnel then
+/// waits until the bytes already forwarded to the other side are written
+/// and drops both ends, so the other peer reads them followed by EOF.
+///
+/// Returns the bytes copied from `a` to `b` and from `b` to `a`.
+pub async fn tunnel(a: AnonPipeDuplex, b: AnonPipeDuplex) -> io::Result<(u64, u64)> {
+    let (mut ar, mut aw) = a.into_split();
+    let (mut br, mut bw) = b.into_split();
+    let mut a_to_b = 0;
+    let mut b_to_a = 0;
+    {
+        let a_copy = forward(&mut ar, &mut bw, &mut a_to_b);
+        let b_copy = forward(&mut br, &mut aw, &mut b_to_a);
+        tokio::pin!(a_copy);
+        tokio::pin!(b_copy);
+        // the first direction to finish ends the other.
+        std::future::poll_fn(|cx| {
+            if let Poll::Ready(result) = a_copy.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+            b_copy.as_mut().poll(cx)
+        })
+        .await?;
+    }
+    for w in [&aw, &bw] {
+        match std::future::poll_fn(|cx| w.0.poll_write_ready(cx)).await {
+            // the peer of a side that reached EOF may be gone already.
+            Err(err) if errors::is_broken_pipe(&err) => {}
+            result => result?,
+        }
+    }
+    Ok((a_to_b, b_to_a))
+}
+
+/// Copy until EOF, counting the bytes written to `w`.
+async fn forward(r: &mut DuplexReadHalf, w: &mut DuplexWriteHalf, n: &mut u64) -> io::Result<()> {
+    let mut buf = vec![0; 8192];
+    loop {
+        let len = r.read(&mut buf).await?;
+        if len == 0 {
+            return Ok(());
+        }
+        w.write_all(&buf[..len]).await?;
+        *n += len as u64;
+    }
+}
+
+/// Wait for both futures, or the first error.
+pub(crate) async fn try_join<A, B, T, U>(a: A, b: B) -> io::Result<(T, U)>
+where
+    A: Future<Output = io::Result<T>>,
+    B: Future<Output = io::Result<U>>,
+{
+    tokio::pin!(a);
+    tokio::pin!(b);
+    let mut a_out = None;
+    let mut b_out = None;
+    std::future::poll_fn(|cx| -> Poll<io::Result<()>> {
+        if a_out.is_none() {
+            if let Poll::Ready(out) = a.as_mut().poll(cx) {
+                a_out = Some(out?);
+            }
+        }
+        if b_out.is_none() {
+            if let Poll::Ready(out) = b.as_mut().poll(cx) {
+                b_out = Some(out?);
+            }
+        }
+        if a_out.is_some() && b_out.is_some() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    })
+    .await?;
+    Ok((a_out.unwrap(), b_out.unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe_duplex_pair;
+
+    #[tokio::test]
+    async fn test_duplex() -> io::Result<()> {
+        let (mut a, mut b) = anon_pipe_duplex_pair().await?;
+
+        a.write_all(b"ping").await?;
+        let mut buf = [0; 4];
+        b.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"ping");
+
+        b.write_all(b"pong").await?;
+        a.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"pong");
+        Ok(())
+    }
+
+    async fn ping_pong<S>(a: &mut S, b: &mut S) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        a.write_all(b"ping").await?;
+        let mut buf = [0; 4];
+        b.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"ping");
+        b.write_all(b"pong").await?;
+        a.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"pong");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_into_split() -> io::Result<()> {
+        let (a, mut b) = anon_pipe_duplex_pair().await?;
+        let (mut ar, mut aw) = a.into_split();
+        ping_pong_halves(&mut ar, &mut aw, &mut b).await?;
+        Ok(())
+    }
+
+    async fn ping_pong_halves(
+        ar: &mut DuplexReadHalf,
+        aw: &mut DuplexWriteHalf,
+        b: &mut AnonPipeDuplex,
+    ) -> io::Result<()> {
+        aw.write_all(b"ping").await?;
+        let mut buf = [0; 4];
+        b.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"ping");
+        b.write_all(b"pong").await?;
+        ar.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"pong");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_and_write_pending_concurrently() -> io::Result<()> {
+        let (a, b) = anon_pipe_duplex_pair().await?;
+        let (mut ar, mut aw) = a.into_split();
+        let len = b.pipe_info()?.in_buffer_size().max(4096) as usize * 4;
+        let (mut br, mut bw) = b.into_split();
+
+        let reader = tokio::spawn(async move {
+            let mut buf = [0; 4];
+            ar.read_exact(&mut buf).await?;
+            Ok::<_, io::Error>(buf)
+        });
+        let writer = tokio::spawn(async move {
+            // the second write stays pending until the peer reads.
+            aw.write_all(&vec![1; len]).await?;
+            aw.write_all(&vec![2; len]).await?;
+            Ok::<_, io::Error>(())
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!reader.is_finished());
+        assert!(!writer.is_finished());
+
+        // unblock both directions at once.
+        let mut received = vec![0; len * 2];
+        let (written, read) = tokio::join!(bw.write_all(b"pong"), br.read_exact(&mut received));
+        written?;
+        read?;
+
+        let both = async { (reader.await.unwrap(), writer.await.unwrap()) };
+        let (reader, writer) = tokio::time::timeout(std::time::Duration::from_secs(5), both)
+            .await
+            .expect("both directions woken");
+        assert_eq!(&reader?, b"pong");
+        writer?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tunnel() -> io::Result<()> {
+        let (mut a1, a2) = anon_pipe_duplex_pair().await?;
+        let (b1, mut b2) = anon_pipe_duplex_pair().await?;
+        let request = (0..100_000).map(|n| n as u8).collect::<Vec<_>>();
+
+        let proxy = tokio::spawn(tunnel(a2, b1));
+
+        a1.write_all(&request).await?;
+        let mut buf = vec![0; 100_000];
+        b2.read_exact(&mut buf).await?;
+        assert_eq!(buf, request);
+        b2.write_all(b"0123456789").await?;
+        drop(b2);
+
+        // the response is delivered, then EOF.
+        let mut response = vec![];
+        a1.read_to_end(&mut response).await?;
+        assert_eq!(response, b"0123456789");
+        assert_eq!(proxy.await.unwrap()?, (100_000, 10));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reset() -> io::Result<()> {
+        let (mut a, mut b) = anon_pipe_duplex_pair().await?;
+
+        b.write_all(b"stale response").await?;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        a.reset().await?;
+
+        b.write_all(b"fresh").await?;
+        let mut buf = [0; 5];
+        a.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"fresh");
+
+        // nothing to discard.
+        a.reset().await?;
+        a.write_all(b"ping").await?;
+        let mut buf = [0; 4];
+        b.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"ping");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplex_like_duplex_stream() -> io::Result<()> {
+        let (mut a, mut b) = io::duplex(64);
+        ping_pong(&mut a, &mut b).await?;
+
+        let (mut a, mut b) = anon_pipe_duplex_pair().await?;
+        ping_pong(&mut a, &mut b).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_copy_bidirectional() -> io::Result<()> {
+        let (mut a1, mut a2) = anon_pipe_duplex_pair().await?;
+        let (mut b1, mut b2) = anon_pipe_duplex_pair().await?;
+        let request = (0..100_000).map(|n| n as u8).collect::<Vec<_>>();
+
+        let proxy = tokio::spawn(async move { copy_bidirectional(&mut a2, &mut b1).await });
+
+        let a_side = {
+            let request = request.clone();
+            tokio::spawn(async move {
+                a1.write_all(&request).await?;
+                let mut response = [0; 10];
+                a1.read_exact(&mut response).await?;
+                assert_eq!(&response, b"0123456789");
+                Ok::<_, io::Error>(())
+            })
+        };
+        let b_side = tokio::spawn(async move {
+            let mut buf = vec![0; 100_000];
+            b2.read_exact(&mut buf).await?;
+            assert_eq!(buf, request);
+            b2.write_all(b"0123456789").await?;
+            Ok::<_, io::Error>(())
+        });
+
+        b_side.await.unwrap()?; // b2 is dropped first
+        a_side.await.unwrap()?; // then a1
+        assert_eq!(proxy.await.unwrap()?, (100_000, 10));
+        Ok(())
+    }
+
+    /// Echo `n` bytes back, as a future written by hand over the low-level
+    /// API only.
+    fn poll_echo(
+        end: &AnonPipeDuplex,
+        cx: &mut Context<'_>,
+        buf: &mut Vec<u8>,
+        n: usize,
+    ) -> Poll<io::Result<()>> {
+        let mut chunk = [0; 64];
+        while buf.len() < n {
+            match end.poll_read_ready(cx) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => return Poll::Pending,
+            }
+            let len = chunk.len().min(n - buf.len());
+            match end.try_read(&mut chunk[..len]) {
+                Ok(0) => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                Ok(read) => buf.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+        while !buf.is_empty() {
+            match end.poll_write_ready(cx) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => return Poll::Pending,
+            }
+            match end.try_write(buf) {
+                Ok(written) => drop(buf.drain(..written)),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    #[tokio::test]
+    async fn test_low_level_echo() -> io::Result<()> {
+        let (mut a, b) = anon_pipe_duplex_pair().await?;
+        let echo = tokio::spawn(async move {
+            let mut buf = vec![];
+            std::future::poll_fn(|cx| poll_echo(&b, cx, &mut buf, 13)).await
+        });
+
+        a.write_all(b"Hello, World!").await?;
+        let mut buf = [0; 13];
+        a.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"Hello, World!");
+        echo.await.unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_into_read_only() -> io::Result<()> {
+        let (mut a, mut b) = anon_pipe_duplex_pair().await?;
+        a.write_all(b"hello").await?;
+        let mut r = a.into_read_only().await?;
+
+        let mut buf = [0; 5];
+        b.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"hello");
+        b.write_all(b"world").await?;
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"world");
+
+        drop(r);
+        assert_eq!(b.read(&mut buf).await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_into_write_only() -> io::Result<()> {
+        let (a, mut b) = anon_pipe_duplex_pair().await?;
+        let mut w = a.into_write_only();
+        w.write_all(b"hello").await?;
+        drop(w);
+
+        let mut buf = vec![];
+        b.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"hello");
+        Ok(())
+    }
+}
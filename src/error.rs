@@ -0,0 +1,245 @@
+//! Classification of failures.
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Classification of failures while creating or connecting a pair.
+///
+/// Carried as the inner error of the returned `io::Error`, which keeps the
+/// `ErrorKind` of the underlying OS error. Its code is reachable with
+/// [`Error::raw_os_error`] or through `source()`, not with
+/// `io::Error::raw_os_error` of the wrapper; the predicates in
+/// [`errors`](crate::errors) look through it.
+///
+/// ```no_run
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// if let Err(err) = tokio_anon_pipe::anon_pipe().await {
+///     match err.get_ref().and_then(|e| e.downcast_ref::<tokio_anon_pipe::Error>()) {
+///         Some(tokio_anon_pipe::Error::NameCollision { .. }) => { /* try again later */ }
+///         _ => eprintln!("{}", err),
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Every generated name collided with an existing pipe.
+    NameCollision {
+        /// Number of names tried.
+        attempts: usize,
+        /// Error of the last attempt.
+        source: io::Error,
+    },
+    /// Creating the server end failed.
+    CreateServer(io::Error),
+    /// Opening the client end failed.
+    OpenClient(io::Error),
+    /// Connecting to the peer failed.
+    Connect(io::Error),
+    /// The creation did not complete within
+    /// [`creation_timeout`](crate::AnonPipeBuilder::creation_timeout).
+    ///
+    /// Converts into an `io::Error` of `ErrorKind::TimedOut`.
+    TimedOut {
+        /// Stage in progress when the deadline passed.
+        stage: CreationStage,
+        /// Error of the last attempt retried, or `ErrorKind::TimedOut` if
+        /// none.
+        source: io::Error,
+    },
+}
+
+/// Stage of creating a pair, reported by [`Error::TimedOut`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CreationStage {
+    /// Creating the server end, including the retries with fresh names.
+    CreateServer,
+    /// Opening the client end, including the retries while busy.
+    OpenClient,
+    /// Connecting the server end to the client.
+    Connect,
+}
+
+impl fmt::Display for CreationStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CreateServer => "creating the server",
+            Self::OpenClient => "opening the client",
+            Self::Connect => "connecting",
+        })
+    }
+}
+
+impl CreationStage {
+    /// `Error::TimedOut` at this stage, after `last` was retried.
+    pub(crate) fn timed_out(self, last: Option<io::Error>) -> io::Error {
+        let source =
+            last.unwrap_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "deadline elapsed"));
+        Error::TimedOut {
+            stage: self,
+            source,
+        }
+        .into()
+    }
+}
+
+/// Classify `err` with `f`, unless classified already.
+pub(crate) fn classify(err: io::Error, f: impl FnOnce(io::Error) -> Error) -> io::Error {
+    if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+        return err;
+    }
+    f(err).into()
+}
+
+impl Error {
+    /// Underlying OS error code, if any.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.source_ref().raw_os_error()
+    }
+
+    fn source_ref(&self) -> &io::Error {
+        match self {
+            Self::NameCollision { source, .. } | Self::TimedOut { source, .. } => source,
+            Self::CreateServer(source) | Self::OpenClient(source) | Self::Connect(source) => source,
+        }
+    }
+}
+
+/// `broken pipe (os error 109)`
+struct Describe<'a>(&'a io::Error);
+
+impl fmt::Display for Describe<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.raw_os_error() {
+            Some(code) => write!(f, "{} (os error {})", self.0.kind(), code),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NameCollision { attempts, source } => write!(
+                f,
+                "pipe name collided after {} attempts: {}",
+                attempts,
+                Describe(source)
+            ),
+            Self::CreateServer(source) => {
+                write!(f, "failed to create pipe server: {}", Describe(source))
+            }
+            Self::OpenClient(source) => {
+                write!(f, "failed to open pipe client: {}", Describe(source))
+            }
+            Self::Connect(source) => {
+                write!(f, "connection to peer failed: {}", Describe(source))
+            }
+            Self::TimedOut { stage, source } => write!(
+                f,
+                "pipe creation timed out while {}: {}",
+                stage,
+                Describe(source)
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(self.source_ref())
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        let kind = match &err {
+            Error::TimedOut { .. } => io::ErrorKind::TimedOut,
+            _ => err.source_ref().kind(),
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ERROR_ACCESS_DENIED: i32 = 5;
+    const ERROR_BROKEN_PIPE: i32 = 109;
+
+    #[test]
+    fn test_display() {
+        let err = Error::NameCollision {
+            attempts: 10,
+            source: io::Error::from_raw_os_error(ERROR_ACCESS_DENIED),
+        };
+        assert_eq!(
+            err.to_string(),
+            "pipe name collided after 10 attempts: permission denied (os error 5)"
+        );
+
+        let err = Error::Connect(io::Error::from_raw_os_error(ERROR_BROKEN_PIPE));
+        assert_eq!(
+            err.to_string(),
+            "connection to peer failed: broken pipe (os error 109)"
+        );
+
+        let err = Error::CreateServer(io::Error::new(io::ErrorKind::InvalidInput, "invalid sid"));
+        assert_eq!(err.to_string(), "failed to create pipe server: invalid sid");
+    }
+
+    #[test]
+    fn test_into_io_error() {
+        let err = io::Error::from(Error::OpenClient(io::Error::from_raw_os_error(
+            ERROR_ACCESS_DENIED,
+        )));
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            err.to_string(),
+            "failed to open pipe client: permission denied (os error 5)"
+        );
+        let inner = err.get_ref().unwrap().downcast_ref::<Error>().unwrap();
+        assert_eq!(inner.raw_os_error(), Some(ERROR_ACCESS_DENIED));
+        let source = error::Error::source(inner).unwrap();
+        let source = source.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.raw_os_error(), Some(ERROR_ACCESS_DENIED));
+    }
+
+    #[test]
+    fn test_timed_out() {
+        let err = CreationStage::CreateServer
+            .timed_out(Some(io::Error::from_raw_os_error(ERROR_ACCESS_DENIED)));
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(
+            err.to_string(),
+            "pipe creation timed out while creating the server: permission denied (os error 5)"
+        );
+        // classified already, kept as is.
+        let err = classify(err, Error::CreateServer);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<Error>(),
+            Some(Error::TimedOut {
+                stage: CreationStage::CreateServer,
+                ..
+            })
+        ));
+
+        let err = CreationStage::Connect.timed_out(None);
+        assert_eq!(
+            err.to_string(),
+            "pipe creation timed out while connecting: deadline elapsed"
+        );
+        assert_eq!(
+            err.get_ref()
+                .unwrap()
+                .downcast_ref::<Error>()
+                .unwrap()
+                .raw_os_error(),
+            None
+        );
+    }
+}
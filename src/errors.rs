@@ -0,0 +1,134 @@
+//! Predicates on the errors of pipe operations.
+//!
+//! These are the judgments the crate makes itself, e.g. when retrying the
+//! creation of a pair or when telling a closed peer apart from a failure.
+//! They look at both the `ErrorKind` and the raw Windows error code, and
+//! through the [`Error`](crate::Error) the ends are created with.
+use std::io;
+
+use crate::Error;
+
+// https://www.rpi.edu/dept/cis/software/g77-mingw32/include/winerror.h
+pub(crate) const ERROR_ACCESS_DENIED: i32 = 5;
+pub(crate) const ERROR_BROKEN_PIPE: i32 = 109;
+pub(crate) const ERROR_SEM_TIMEOUT: i32 = 121;
+pub(crate) const ERROR_PIPE_BUSY: i32 = 231;
+pub(crate) const ERROR_NO_DATA: i32 = 232;
+pub(crate) const ERROR_PIPE_NOT_CONNECTED: i32 = 233;
+pub(crate) const ERROR_PIPE_LISTENING: i32 = 536;
+
+/// Raw code of `err`, or of the OS error an [`Error`] carries.
+fn raw_code(err: &io::Error) -> Option<i32> {
+    match err
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<Error>())
+    {
+        Some(err) => err.raw_os_error(),
+        None => err.raw_os_error(),
+    }
+}
+
+/// `true` if `err` tells that the other end closed while writing to it:
+/// `ErrorKind::BrokenPipe`, `ERROR_BROKEN_PIPE` or `ERROR_NO_DATA`.
+pub fn is_broken_pipe(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::BrokenPipe
+        || matches!(raw_code(err), Some(ERROR_BROKEN_PIPE) | Some(ERROR_NO_DATA))
+}
+
+/// `true` if `err` tells that the peer is gone, in either direction.
+///
+/// Broken pipes as of [`is_broken_pipe`], plus `ErrorKind::NotConnected`
+/// and `ERROR_PIPE_NOT_CONNECTED`, which a server end fails with once its
+/// client disconnected.
+pub fn is_peer_gone(err: &io::Error) -> bool {
+    is_broken_pipe(err)
+        || err.kind() == io::ErrorKind::NotConnected
+        || raw_code(err) == Some(ERROR_PIPE_NOT_CONNECTED)
+}
+
+/// `true` if `err` is a transient failure of creating a pair, which a retry
+/// with a fresh name may not hit again.
+///
+/// This is the judgment of the creation of this crate: the failures of
+/// [`is_retryable_open`], and `ERROR_ACCESS_DENIED`, which creating the
+/// first instance of a name that collides fails with.
+pub fn is_transient(err: &io::Error) -> bool {
+    is_retryable_open(err) || raw_code(err) == Some(ERROR_ACCESS_DENIED)
+}
+
+/// `true` if `err` is a transient failure of opening or connecting a pipe,
+/// which a retry may not hit again.
+///
+/// This is the judgment of the retry loops of this crate. Transient are
+/// `ERROR_PIPE_BUSY` and `ERROR_SEM_TIMEOUT` (all instances busy) and
+/// `ERROR_PIPE_LISTENING` (connect racing the client, seen on Wine).
+/// `ERROR_ACCESS_DENIED` is not: opening a pipe the caller has no access
+/// to fails with it for good, see [`is_transient`] for creating.
+pub fn is_retryable_open(err: &io::Error) -> bool {
+    matches!(
+        raw_code(err),
+        Some(ERROR_SEM_TIMEOUT) | Some(ERROR_PIPE_BUSY) | Some(ERROR_PIPE_LISTENING)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(code: i32) -> io::Error {
+        io::Error::from_raw_os_error(code)
+    }
+
+    #[test]
+    fn test_is_broken_pipe() {
+        assert!(is_broken_pipe(&code(ERROR_BROKEN_PIPE)));
+        assert!(is_broken_pipe(&code(ERROR_NO_DATA)));
+        assert!(is_broken_pipe(&io::ErrorKind::BrokenPipe.into()));
+        assert!(!is_broken_pipe(&code(ERROR_PIPE_NOT_CONNECTED)));
+        assert!(!is_broken_pipe(&code(ERROR_PIPE_BUSY)));
+        assert!(!is_broken_pipe(&io::ErrorKind::NotConnected.into()));
+    }
+
+    #[test]
+    fn test_is_peer_gone() {
+        assert!(is_peer_gone(&code(ERROR_BROKEN_PIPE)));
+        assert!(is_peer_gone(&code(ERROR_NO_DATA)));
+        assert!(is_peer_gone(&code(ERROR_PIPE_NOT_CONNECTED)));
+        assert!(is_peer_gone(&io::ErrorKind::BrokenPipe.into()));
+        assert!(is_peer_gone(&io::ErrorKind::NotConnected.into()));
+        assert!(!is_peer_gone(&code(ERROR_PIPE_BUSY)));
+        assert!(!is_peer_gone(&code(ERROR_SEM_TIMEOUT)));
+        assert!(!is_peer_gone(&io::ErrorKind::TimedOut.into()));
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&code(ERROR_ACCESS_DENIED)));
+        assert!(is_transient(&code(ERROR_PIPE_BUSY)));
+        assert!(is_transient(&code(ERROR_SEM_TIMEOUT)));
+        assert!(is_transient(&code(ERROR_PIPE_LISTENING)));
+        assert!(!is_transient(&code(ERROR_BROKEN_PIPE)));
+        assert!(!is_transient(&io::ErrorKind::TimedOut.into()));
+        let err = Error::CreateServer(code(ERROR_ACCESS_DENIED));
+        assert!(is_transient(&err.into()));
+    }
+
+    #[test]
+    fn test_is_retryable_open() {
+        assert!(is_retryable_open(&code(ERROR_PIPE_BUSY)));
+        assert!(is_retryable_open(&code(ERROR_SEM_TIMEOUT)));
+        assert!(is_retryable_open(&code(ERROR_PIPE_LISTENING)));
+        assert!(!is_retryable_open(&code(ERROR_ACCESS_DENIED)));
+        assert!(!is_retryable_open(&code(ERROR_BROKEN_PIPE)));
+        assert!(!is_retryable_open(&code(ERROR_PIPE_NOT_CONNECTED)));
+        assert!(!is_retryable_open(&io::ErrorKind::TimedOut.into()));
+    }
+
+    #[test]
+    fn test_through_error() {
+        let err = Error::Connect(code(ERROR_BROKEN_PIPE));
+        assert!(is_peer_gone(&err.into()));
+        let err = Error::OpenClient(code(ERROR_PIPE_BUSY));
+        assert!(is_retryable_open(&err.into()));
+    }
+}
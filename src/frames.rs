@@ -0,0 +1,173 @@
+//! Stream of length-prefixed frames.
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use tokio::io::{self, AsyncRead};
+
+use crate::AnonPipeRead;
+
+/// Stream of frames sent by [`AnonPipeWrite::send_msg`](crate::AnonPipeWrite::send_msg).
+///
+/// Created by [`AnonPipeRead::frames`]. Ends on EOF at a frame boundary.
+/// Yields `ErrorKind::InvalidData` for a frame exceeding the maximum size,
+/// and `ErrorKind::UnexpectedEof` on EOF in the middle of a frame. The
+/// stream ends after an error.
+#[derive(Debug)]
+pub struct Frames<'a> {
+    inner: &'a mut AnonPipeRead,
+    max_frame: usize,
+    header: [u8; 4],
+    header_len: usize,
+    payload: Option<(Vec<u8>, usize)>,
+    done: bool,
+}
+
+impl<'a> Frames<'a> {
+    pub(crate) fn new(inner: &'a mut AnonPipeRead, max_frame: usize) -> Self {
+        Self {
+            inner,
+            max_frame,
+            header: [0; 4],
+            header_len: 0,
+            payload: None,
+            done: false,
+        }
+    }
+
+    fn poll_frame(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<Bytes>>> {
+        loop {
+            let (payload, filled) = match &mut self.payload {
+                Some((payload, filled)) => (payload, filled),
+                None => {
+                    let mut buf = io::ReadBuf::new(&mut self.header[self.header_len..]);
+                    match Pin::new(&mut *self.inner).poll_read(cx, &mut buf)? {
+                        Poll::Ready(()) => {}
+                        Poll::Pending => return Poll::Pending,
+                    }
+                    let n = buf.filled().len();
+                    match n {
+                        0 if self.header_len == 0 => return Poll::Ready(Ok(None)),
+                        0 => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                        n => self.header_len += n,
+                    }
+                    if self.header_len < self.header.len() {
+                        continue;
+                    }
+
+                    let len = u32::from_le_bytes(self.header) as usize;
+                    if len > self.max_frame {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("message length {} exceeds {}", len, self.max_frame),
+                        )));
+                    }
+                    self.header_len = 0;
+                    self.payload = Some((vec![0; len], 0));
+                    continue;
+                }
+            };
+
+            if *filled == payload.len() {
+                let (payload, _) = self.payload.take().unwrap();
+                return Poll::Ready(Ok(Some(payload.into())));
+            }
+            let mut buf = io::ReadBuf::new(&mut payload[*filled..]);
+            match Pin::new(&mut *self.inner).poll_read(cx, &mut buf)? {
+                Poll::Ready(()) => {}
+                Poll::Pending => return Poll::Pending,
+            }
+            match buf.filled().len() {
+                0 => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                n => *filled += n,
+            }
+        }
+    }
+}
+
+impl Stream for Frames<'_> {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let result = match this.poll_frame(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+        match result {
+            Ok(Some(frame)) => Poll::Ready(Some(Ok(frame))),
+            Ok(None) => {
+                this.done = true;
+                Poll::Ready(None)
+            }
+            Err(err) => {
+                this.done = true;
+                Poll::Ready(Some(Err(err)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::AsyncWriteExt;
+
+    async fn next(frames: &mut Frames<'_>) -> Option<io::Result<Bytes>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *frames).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn test_frames() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        let writer = tokio::spawn(async move {
+            w.send_msg(b"Hello").await?;
+            w.send_msg(b"").await?;
+            // split across the header and the payload.
+            for chunk in [&[5, 0][..], &[0, 0, b'W'], b"orld"] {
+                w.write_all(chunk).await?;
+                tokio::task::yield_now().await;
+            }
+            Ok::<_, io::Error>(())
+        });
+
+        let mut frames = r.frames(16);
+        let mut received = vec![];
+        while let Some(frame) = next(&mut frames).await {
+            received.push(frame?);
+        }
+        assert_eq!(received, vec![&b"Hello"[..], b"", b"World"]);
+        writer.await.unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frames_oversize() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.send_msg(b"Hello, World!").await?;
+        let mut frames = r.frames(4);
+        let err = next(&mut frames).await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(next(&mut frames).await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frames_eof_mid_frame() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(&[5, 0, 0, 0, b'H']).await?;
+        drop(w);
+        let mut frames = r.frames(16);
+        let err = next(&mut frames).await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+}
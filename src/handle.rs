@@ -0,0 +1,211 @@
+//! Serializable descriptor of a pipe handle.
+use std::process;
+
+use serde::{Deserialize, Serialize};
+use tokio::io;
+
+use crate::{
+    audit, info, AnonPipeRead, AnonPipeWrite, IntoRawHandle, NamedPipeClient, NamedPipeServer,
+    RawHandle,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Side {
+    Server,
+    Client,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum End {
+    Read,
+    Write,
+}
+
+/// Descriptor of a pipe end, to hand it over to another process in a
+/// serialized config, e.g. as JSON.
+///
+/// Carries the handle value and which end it is, plus the id of the
+/// process it was taken from. Serialization conveys only these values, not
+/// the handle itself: the receiving process must have inherited the handle,
+/// or been granted it with `DuplicateHandle` under the same value, before
+/// reconstructing the end.
+///
+/// Converting an end into a `PipeHandle` releases it without closing the
+/// handle, which leaks unless the end is reconstructed somewhere.
+///
+/// ```no_run
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// use tokio_anon_pipe::PipeHandle;
+///
+/// let (r, w) = tokio_anon_pipe::anon_pipe().await?;
+/// let handle = PipeHandle::from(w);
+/// // ... serialize `handle`, pass it to the process inheriting it ...
+/// let w = unsafe { handle.into_write() }?;
+/// # drop((r, w));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipeHandle {
+    handle: u64,
+    pid: u32,
+    side: Side,
+    end: End,
+}
+
+impl PipeHandle {
+    fn new(handle: RawHandle, side: Side, end: End) -> Self {
+        Self {
+            handle: handle as usize as u64,
+            pid: process::id(),
+            side,
+            end,
+        }
+    }
+
+    /// Handle value.
+    pub fn raw_handle(&self) -> RawHandle {
+        self.handle as usize as RawHandle
+    }
+
+    /// Id of the process the handle was taken from.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Whether it describes a read end.
+    pub fn is_read(&self) -> bool {
+        self.end == End::Read
+    }
+
+    /// Whether it describes a write end.
+    pub fn is_write(&self) -> bool {
+        self.end == End::Write
+    }
+
+    /// Reconstruct the read end.
+    ///
+    /// The handle is dissociated from the completion port it was registered
+    /// with before, then registered with the current runtime. Must be
+    /// called within a tokio runtime. Fails with `InvalidInput` if
+    /// it describes a write end.
+    ///
+    /// # Safety
+    ///
+    /// The handle must be open in this process, as the described end, and
+    /// owned by nothing else: it is closed when the end is dropped.
+    pub unsafe fn into_read(self) -> io::Result<AnonPipeRead> {
+        if self.end != End::Read {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "pipe handle is a write end",
+            ));
+        }
+        reregister(self.raw_handle());
+        let read = match self.side {
+            Side::Server => {
+                AnonPipeRead::Server(NamedPipeServer::from_raw_handle(self.raw_handle())?)
+            }
+            Side::Client => {
+                AnonPipeRead::Client(NamedPipeClient::from_raw_handle(self.raw_handle())?)
+            }
+        };
+        Ok(audit::track(read, None))
+    }
+
+    /// Reconstruct the write end.
+    ///
+    /// The handle is dissociated from the completion port it was registered
+    /// with before, then registered with the current runtime. Must be
+    /// called within a tokio runtime. Fails with `InvalidInput` if
+    /// it describes a read end.
+    ///
+    /// # Safety
+    ///
+    /// The handle must be open in this process, as the described end, and
+    /// owned by nothing else: it is closed when the end is dropped.
+    pub unsafe fn into_write(self) -> io::Result<AnonPipeWrite> {
+        if self.end != End::Write {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "pipe handle is a read end",
+            ));
+        }
+        reregister(self.raw_handle());
+        let write = match self.side {
+            Side::Server => {
+                AnonPipeWrite::Server(NamedPipeServer::from_raw_handle(self.raw_handle())?)
+            }
+            Side::Client => {
+                AnonPipeWrite::Client(NamedPipeClient::from_raw_handle(self.raw_handle())?)
+            }
+        };
+        Ok(audit::track(write, None))
+    }
+}
+
+/// Prepare `handle` to be registered again.
+fn reregister(handle: RawHandle) {
+    // fails before Windows 8.1, registering then fails if the handle is
+    // still associated.
+    let _ = info::clear_completion_port(handle);
+}
+
+impl From<AnonPipeRead> for PipeHandle {
+    fn from(read: AnonPipeRead) -> Self {
+        let side = match read {
+            AnonPipeRead::Server(_) => Side::Server,
+            AnonPipeRead::Client(_) => Side::Client,
+        };
+        Self::new(read.into_raw_handle(), side, End::Read)
+    }
+}
+
+impl From<AnonPipeWrite> for PipeHandle {
+    fn from(write: AnonPipeWrite) -> Self {
+        let side = match write {
+            AnonPipeWrite::Server(_) => Side::Server,
+            AnonPipeWrite::Client(_) => Side::Client,
+        };
+        Self::new(write.into_raw_handle(), side, End::Write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn roundtrip(handle: PipeHandle) -> PipeHandle {
+        let encoded = bincode::serialize(&handle).unwrap();
+        bincode::deserialize(&encoded).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pipe_handle() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        let (r, w) = (PipeHandle::from(r), PipeHandle::from(w));
+        assert!(r.is_read());
+        assert!(w.is_write());
+        assert_eq!(w.pid(), process::id());
+
+        let (r, w) = (roundtrip(r), roundtrip(w));
+        let err = unsafe { r.into_write() }.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = unsafe { w.into_read() }.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let mut r = unsafe { r.into_read() }?;
+        let mut w = unsafe { w.into_write() }?;
+        w.write_all(b"handed over").await?;
+        drop(w);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(buf, b"handed over");
+        Ok(())
+    }
+}
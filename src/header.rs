@@ -0,0 +1,181 @@
+//! Fixed-size headers with explicit byte order.
+use std::mem;
+
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+
+use crate::{AnonPipeRead, AnonPipeWrite};
+
+/// Byte order of a header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// Header decoded from its fixed-size byte representation.
+///
+/// Implemented for the primitive integers. Implement it for a header struct
+/// by decoding each field from its slice of `bytes`:
+///
+/// ```
+/// use tokio_anon_pipe::{Endian, FromBytes};
+///
+/// struct Header {
+///     magic: u32,
+///     len: u16,
+/// }
+///
+/// impl FromBytes for Header {
+///     // no padding on the wire.
+///     const SIZE: usize = 6;
+///
+///     fn from_bytes(bytes: &[u8], endian: Endian) -> Self {
+///         Self {
+///             magic: u32::from_bytes(&bytes[..4], endian),
+///             len: u16::from_bytes(&bytes[4..], endian),
+///         }
+///     }
+/// }
+/// ```
+pub trait FromBytes: Sized {
+    /// Size on the wire. Default is `size_of::<Self>()`.
+    const SIZE: usize = mem::size_of::<Self>();
+
+    /// Decode from exactly [`SIZE`](Self::SIZE) bytes.
+    fn from_bytes(bytes: &[u8], endian: Endian) -> Self;
+}
+
+/// Header encoded into its fixed-size byte representation.
+///
+/// Counterpart of [`FromBytes`].
+pub trait ToBytes: Sized {
+    /// Size on the wire. Default is `size_of::<Self>()`.
+    const SIZE: usize = mem::size_of::<Self>();
+
+    /// Encode into exactly [`SIZE`](Self::SIZE) bytes.
+    fn to_bytes(&self, bytes: &mut [u8], endian: Endian);
+}
+
+macro_rules! impl_int {
+    ($($ty:ty),*) => {
+        $(
+            impl FromBytes for $ty {
+                fn from_bytes(bytes: &[u8], endian: Endian) -> Self {
+                    let mut buf = [0; mem::size_of::<$ty>()];
+                    buf.copy_from_slice(bytes);
+                    match endian {
+                        Endian::Little => <$ty>::from_le_bytes(buf),
+                        Endian::Big => <$ty>::from_be_bytes(buf),
+                    }
+                }
+            }
+
+            impl ToBytes for $ty {
+                fn to_bytes(&self, bytes: &mut [u8], endian: Endian) {
+                    let buf = match endian {
+                        Endian::Little => self.to_le_bytes(),
+                        Endian::Big => self.to_be_bytes(),
+                    };
+                    bytes.copy_from_slice(&buf);
+                }
+            }
+        )*
+    };
+}
+
+impl_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl AnonPipeRead {
+    /// Read exactly `H::SIZE` bytes and decode them in `endian` byte order.
+    ///
+    /// Fails with `ErrorKind::UnexpectedEof` if the writer closes before the
+    /// whole header arrived.
+    pub async fn read_header<H: FromBytes>(&mut self, endian: Endian) -> io::Result<H> {
+        let mut buf = vec![0; H::SIZE];
+        self.read_exact(&mut buf).await?;
+        Ok(H::from_bytes(&buf, endian))
+    }
+}
+
+impl AnonPipeWrite {
+    /// Encode `header` in `endian` byte order and write all of it.
+    ///
+    /// Read it with [`AnonPipeRead::read_header`].
+    pub async fn write_header<H: ToBytes>(&mut self, header: &H, endian: Endian) -> io::Result<()> {
+        let mut buf = vec![0; H::SIZE];
+        header.to_bytes(&mut buf, endian);
+        self.write_all(&buf).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+
+    #[derive(Debug, PartialEq)]
+    struct Header {
+        magic: u32,
+        len: u16,
+        kind: u8,
+    }
+
+    impl FromBytes for Header {
+        const SIZE: usize = 7;
+
+        fn from_bytes(bytes: &[u8], endian: Endian) -> Self {
+            Self {
+                magic: u32::from_bytes(&bytes[..4], endian),
+                len: u16::from_bytes(&bytes[4..6], endian),
+                kind: u8::from_bytes(&bytes[6..], endian),
+            }
+        }
+    }
+
+    impl ToBytes for Header {
+        const SIZE: usize = 7;
+
+        fn to_bytes(&self, bytes: &mut [u8], endian: Endian) {
+            self.magic.to_bytes(&mut bytes[..4], endian);
+            self.len.to_bytes(&mut bytes[4..6], endian);
+            self.kind.to_bytes(&mut bytes[6..], endian);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_header() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        let header = Header {
+            magic: 0x0102_0304,
+            len: 0x0506,
+            kind: 7,
+        };
+
+        w.write_header(&header, Endian::Big).await?;
+        w.write_header(&header, Endian::Little).await?;
+        let mut raw = [0; 7];
+        r.read_exact(&mut raw).await?;
+        assert_eq!(raw, [1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(r.read_header::<Header>(Endian::Little).await?, header);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_header_eof() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(&[1, 2]).await?;
+        drop(w);
+        let err = r.read_header::<u32>(Endian::Big).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+
+    #[test]
+    fn test_int_bytes() {
+        let mut buf = [0; 2];
+        (-2i16).to_bytes(&mut buf, Endian::Little);
+        assert_eq!(buf, [0xfe, 0xff]);
+        assert_eq!(i16::from_bytes(&buf, Endian::Big), -257);
+    }
+}
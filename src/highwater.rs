@@ -0,0 +1,226 @@
+//! Peak buffered bytes, for sizing the pipe buffers.
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{self, AsyncRead, AsyncWrite};
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::{info, AsRawHandle, RawHandle};
+
+/// Peak bytes buffered in each direction, as recorded by [`HighWater`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HighWaterMark {
+    incoming: u32,
+    outgoing: u32,
+}
+
+impl HighWaterMark {
+    /// Most bytes seen buffered unread towards the end.
+    pub fn incoming(&self) -> u32 {
+        self.incoming
+    }
+
+    /// Most bytes seen buffered unread from the end, by the write quota in
+    /// use. A read pending on the peer counts as buffered.
+    pub fn outgoing(&self) -> u32 {
+        self.outgoing
+    }
+}
+
+#[derive(Debug)]
+struct Marks {
+    incoming: AtomicU32,
+    outgoing: AtomicU32,
+    /// Handle value while the end is open, the sampling task must not query
+    /// a closed or reused handle.
+    handle: Mutex<Option<usize>>,
+}
+
+impl Marks {
+    /// `false` once the end is gone.
+    fn sample(&self) -> io::Result<bool> {
+        let handle = self.handle.lock().unwrap_or_else(PoisonError::into_inner);
+        let handle = match *handle {
+            Some(handle) => handle,
+            None => return Ok(false),
+        };
+        let (incoming, outgoing) = info::buffered_bytes(handle as RawHandle)?;
+        self.incoming.fetch_max(incoming, Ordering::Relaxed);
+        self.outgoing.fetch_max(outgoing, Ordering::Relaxed);
+        Ok(true)
+    }
+}
+
+/// End recording the peak bytes buffered in the pipe.
+///
+/// Samples `FilePipeLocalInformation` on [`sample`](Self::sample), and at
+/// the interval set by [`poll_interval`](Self::poll_interval), off by
+/// default. Peaks between two samples are missed, so the mark is a lower
+/// bound.
+#[derive(Debug)]
+pub struct HighWater<T> {
+    inner: T,
+    marks: Arc<Marks>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl<T: AsRawHandle> HighWater<T> {
+    /// Record the peaks of `inner`, sampled on [`sample`](Self::sample)
+    /// only.
+    pub fn new(inner: T) -> Self {
+        let marks = Marks {
+            incoming: AtomicU32::new(0),
+            outgoing: AtomicU32::new(0),
+            handle: Mutex::new(Some(inner.as_raw_handle() as usize)),
+        };
+        Self {
+            inner,
+            marks: Arc::new(marks),
+            task: None,
+        }
+    }
+
+    /// Also sample every `interval` on a spawned task, until the end is
+    /// gone or sampling fails.
+    ///
+    /// Must be called within a tokio runtime with the time driver enabled.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.task = Some(tokio::spawn(sample_every(self.marks.clone(), interval)));
+        self
+    }
+
+    /// Sample the buffered bytes now.
+    pub fn sample(&self) -> io::Result<()> {
+        self.marks.sample().map(drop)
+    }
+
+    /// Peaks recorded so far.
+    pub fn high_water_mark(&self) -> HighWaterMark {
+        HighWaterMark {
+            incoming: self.marks.incoming.load(Ordering::Relaxed),
+            outgoing: self.marks.outgoing.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Gets a reference to the underlying end.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+}
+
+async fn sample_every(marks: Arc<Marks>, interval: Duration) {
+    let mut interval = time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match marks.sample() {
+            Ok(true) => {}
+            Ok(false) | Err(..) => return,
+        }
+    }
+}
+
+impl<T> Drop for HighWater<T> {
+    fn drop(&mut self) {
+        // before the handle closes with `inner`.
+        *self
+            .marks
+            .handle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for HighWater<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for HighWater<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+impl<T: AsRawHandle> AsRawHandle for HighWater<T> {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.inner.as_raw_handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_high_water_mark() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let mut r = HighWater::new(r).poll_interval(Duration::from_millis(5));
+        assert_eq!(r.high_water_mark(), HighWaterMark::default());
+
+        // nothing reads yet, the bytes stay buffered.
+        w.write_all(&[0; 1000]).await?;
+        time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(r.high_water_mark().incoming(), 1000);
+
+        let mut buf = [0; 1000];
+        r.read_exact(&mut buf).await?;
+        r.sample()?;
+        // the peak is kept.
+        assert_eq!(r.high_water_mark().incoming(), 1000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sample_without_interval() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let r = HighWater::new(r);
+        w.write_all(b"hello").await?;
+        time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(r.high_water_mark().incoming(), 0);
+        r.sample()?;
+        assert_eq!(r.high_water_mark().incoming(), 5);
+        Ok(())
+    }
+}
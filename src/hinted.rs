@@ -0,0 +1,104 @@
+//! Read to EOF, presized from the pipe buffer.
+use tokio::io::{self, AsyncReadExt};
+
+use crate::AnonPipeRead;
+
+/// Least capacity [`AnonPipeRead::read_to_end_hinted`] reserves at once.
+const MIN_RESERVE: usize = 8 * 1024;
+
+/// Stack buffer probing for EOF before growing a full `Vec`.
+const PROBE_LEN: usize = 32;
+
+impl AnonPipeRead {
+    /// Read until EOF, appending to `out`, like `read_to_end`, with fewer
+    /// reallocations.
+    ///
+    /// Whenever `out` is full, reserves at least what
+    /// [`bytes_available`](Self::bytes_available) reports, and at least
+    /// 8 KiB, instead of growing step by step. If nothing is buffered, a
+    /// read into a small stack buffer comes first, so `out` is not grown
+    /// only to find EOF. Appends the same bytes as `read_to_end`, and
+    /// returns how many.
+    pub async fn read_to_end_hinted(&mut self, out: &mut Vec<u8>) -> io::Result<usize> {
+        let start = out.len();
+        loop {
+            if out.len() == out.capacity() {
+                // only a hint: a failing peek leaves it to the read.
+                let available = self.bytes_available().unwrap_or(0) as usize;
+                if available == 0 {
+                    let mut probe = [0; PROBE_LEN];
+                    match self.read(&mut probe).await? {
+                        0 => break,
+                        n => out.extend_from_slice(&probe[..n]),
+                    }
+                    continue;
+                }
+                out.reserve(available.max(MIN_RESERVE));
+            }
+            // fills the spare capacity only.
+            if self.read_buf(out).await? == 0 {
+                break;
+            }
+        }
+        Ok(out.len() - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::AsyncWriteExt;
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|n| (n * 31 % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn test_read_to_end_hinted_streaming() -> io::Result<()> {
+        let data = data(1024 * 1024);
+        let (mut r, mut w) = anon_pipe().await?;
+        let writer = tokio::spawn({
+            let data = data.clone();
+            async move {
+                for chunk in data.chunks(10_000) {
+                    w.write_all(chunk).await?;
+                    tokio::task::yield_now().await;
+                }
+                w.close().await
+            }
+        });
+
+        let mut out = b"head".to_vec();
+        assert_eq!(r.read_to_end_hinted(&mut out).await?, data.len());
+        assert_eq!(&out[..4], b"head");
+        assert_eq!(&out[4..], &data[..]);
+        writer.await.unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_to_end_hinted_drained() -> io::Result<()> {
+        let data = data(30_000);
+        let (mut r, mut w) = anon_pipe().await?;
+        w.write_all(&data).await?;
+        w.close().await?;
+
+        let mut out = vec![];
+        assert_eq!(r.read_to_end_hinted(&mut out).await?, data.len());
+        assert_eq!(out, data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_to_end_hinted_eof() -> io::Result<()> {
+        let (mut r, w) = anon_pipe().await?;
+        drop(w);
+
+        let mut out = vec![];
+        assert_eq!(r.read_to_end_hinted(&mut out).await?, 0);
+        // nothing allocated.
+        assert_eq!(out.capacity(), 0);
+        Ok(())
+    }
+}
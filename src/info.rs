@@ -0,0 +1,189 @@
+//! Pipe information.
+use std::io;
+
+use crate::{sys, RawHandle};
+
+// https://docs.microsoft.com/en-us/windows/win32/api/namedpipeapi/nf-namedpipeapi-getnamedpipeinfo
+const PIPE_SERVER_END: u32 = 0x0000_0001;
+const PIPE_TYPE_MESSAGE: u32 = 0x0000_0004;
+
+// https://docs.microsoft.com/en-us/windows-hardware/drivers/ddi/ntifs/ns-ntifs-_file_mode_information
+const FILE_MODE_INFORMATION: u32 = 16;
+const FILE_WRITE_THROUGH: u32 = 0x0000_0002;
+const FILE_SYNCHRONOUS_IO_ALERT: u32 = 0x0000_0010;
+const FILE_SYNCHRONOUS_IO_NONALERT: u32 = 0x0000_0020;
+
+// https://docs.microsoft.com/en-us/windows-hardware/drivers/ddi/ntifs/ns-ntifs-_file_pipe_local_information
+const FILE_PIPE_LOCAL_INFORMATION: u32 = 24;
+const FILE_PIPE_SERVER_END: u32 = 0x0000_0001;
+
+// https://docs.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/ne-wdm-_file_information_class
+const FILE_REPLACE_COMPLETION_INFORMATION: u32 = 61;
+
+/// `FileInformationClass` of `handle` into `info`.
+fn query_file<T>(handle: RawHandle, info: &mut T, class: u32) -> io::Result<()> {
+    let mut iosb = sys::IO_STATUS_BLOCK {
+        Status: 0,
+        Information: 0,
+    };
+    let status = unsafe {
+        sys::NtQueryInformationFile(
+            handle as _,
+            &mut iosb,
+            info as *mut T as *mut _,
+            std::mem::size_of::<T>() as u32,
+            class,
+        )
+    };
+    if status < 0 {
+        let err = unsafe { sys::RtlNtStatusToDosError(status) };
+        return Err(io::Error::from_raw_os_error(err as i32));
+    }
+    Ok(())
+}
+
+/// Set `FileInformationClass` of `handle` from `info`.
+fn set_file<T>(handle: RawHandle, info: &mut T, class: u32) -> io::Result<()> {
+    let mut iosb = sys::IO_STATUS_BLOCK {
+        Status: 0,
+        Information: 0,
+    };
+    let status = unsafe {
+        sys::NtSetInformationFile(
+            handle as _,
+            &mut iosb,
+            info as *mut T as *mut _,
+            std::mem::size_of::<T>() as u32,
+            class,
+        )
+    };
+    if status < 0 {
+        let err = unsafe { sys::RtlNtStatusToDosError(status) };
+        return Err(io::Error::from_raw_os_error(err as i32));
+    }
+    Ok(())
+}
+
+/// Set `FILE_WRITE_THROUGH` on `handle`, as `FILE_FLAG_WRITE_THROUGH` does
+/// at creation.
+pub(crate) fn set_write_through(handle: RawHandle) -> io::Result<()> {
+    let mut mode = 0u32;
+    query_file(handle, &mut mode, FILE_MODE_INFORMATION)?;
+    mode |= FILE_WRITE_THROUGH;
+    set_file(handle, &mut mode, FILE_MODE_INFORMATION)
+}
+
+/// Dissociate `handle` from its I/O completion port, if any, so that it can
+/// be registered again.
+///
+/// The association belongs to the file object, it outlives a forgotten
+/// end and is shared by inherited handles.
+pub(crate) fn clear_completion_port(handle: RawHandle) -> io::Result<()> {
+    let mut info = sys::FILE_COMPLETION_INFORMATION {
+        Port: std::ptr::null_mut(),
+        Key: std::ptr::null_mut(),
+    };
+    set_file(handle, &mut info, FILE_REPLACE_COMPLETION_INFORMATION)
+}
+
+/// Bytes a write through `handle` can put into the pipe right now.
+pub(crate) fn write_quota_available(handle: RawHandle) -> io::Result<u32> {
+    let mut info = sys::FILE_PIPE_LOCAL_INFORMATION::default();
+    query_file(handle, &mut info, FILE_PIPE_LOCAL_INFORMATION)?;
+    Ok(info.WriteQuotaAvailable)
+}
+
+/// Bytes buffered unread towards `handle` and from it.
+///
+/// The outgoing bytes are the write quota in use, so a read pending on the
+/// other end counts as buffered.
+pub(crate) fn buffered_bytes(handle: RawHandle) -> io::Result<(u32, u32)> {
+    let mut info = sys::FILE_PIPE_LOCAL_INFORMATION::default();
+    query_file(handle, &mut info, FILE_PIPE_LOCAL_INFORMATION)?;
+    // the server writes into the outbound buffer, the client into the
+    // inbound one.
+    let quota = if info.NamedPipeEnd == FILE_PIPE_SERVER_END {
+        info.OutboundQuota
+    } else {
+        info.InboundQuota
+    };
+    let outgoing = quota.saturating_sub(info.WriteQuotaAvailable);
+    Ok((info.ReadDataAvailable, outgoing))
+}
+
+/// Information of the pipe, as returned by `GetNamedPipeInfo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipeInfo {
+    flags: u32,
+    out_buffer_size: u32,
+    in_buffer_size: u32,
+    max_instances: u32,
+    mode: u32,
+}
+
+impl PipeInfo {
+    pub(crate) fn query(handle: RawHandle) -> io::Result<Self> {
+        let mut info = Self {
+            flags: 0,
+            out_buffer_size: 0,
+            in_buffer_size: 0,
+            max_instances: 0,
+            mode: 0,
+        };
+        let ok = unsafe {
+            sys::GetNamedPipeInfo(
+                handle as _,
+                &mut info.flags,
+                &mut info.out_buffer_size,
+                &mut info.in_buffer_size,
+                &mut info.max_instances,
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        query_file(handle, &mut info.mode, FILE_MODE_INFORMATION)?;
+        Ok(info)
+    }
+
+    /// `true` for the server end, `false` for the client end.
+    pub fn is_server_end(&self) -> bool {
+        self.flags & PIPE_SERVER_END != 0
+    }
+
+    /// `true` if the pipe was created in message mode.
+    pub fn is_message_type(&self) -> bool {
+        self.flags & PIPE_TYPE_MESSAGE != 0
+    }
+
+    /// Size of the outgoing buffer in bytes. `0` means allocated as needed.
+    pub fn out_buffer_size(&self) -> u32 {
+        self.out_buffer_size
+    }
+
+    /// Size of the incoming buffer in bytes. `0` means allocated as needed.
+    pub fn in_buffer_size(&self) -> u32 {
+        self.in_buffer_size
+    }
+
+    /// `true` if the handle was opened with `FILE_FLAG_OVERLAPPED`.
+    ///
+    /// Always `true` for the ends created by this crate, tokio requires it.
+    pub fn is_overlapped(&self) -> bool {
+        self.mode & (FILE_SYNCHRONOUS_IO_ALERT | FILE_SYNCHRONOUS_IO_NONALERT) == 0
+    }
+
+    /// `true` if writes through the handle are written through
+    /// (`FILE_FLAG_WRITE_THROUGH`).
+    ///
+    /// See [`AnonPipeBuilder::write_through`](crate::AnonPipeBuilder::write_through).
+    pub fn is_write_through(&self) -> bool {
+        self.mode & FILE_WRITE_THROUGH != 0
+    }
+
+    /// Maximum number of instances. `255` means unlimited.
+    pub fn max_instances(&self) -> u32 {
+        self.max_instances
+    }
+}
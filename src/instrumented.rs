@@ -0,0 +1,348 @@
+//! Latency instrumentation.
+use std::convert::TryFrom;
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio::io::{self, AsyncRead, AsyncWrite};
+
+/// Linear sub-buckets per power of two. Relative error is below 1/16.
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+/// Log-linear histogram of nanoseconds, allocated once.
+struct Histogram {
+    counts: Box<[u64; BUCKETS]>,
+    immediate: u64,
+    pending: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            counts: Box::new([0; BUCKETS]),
+            immediate: 0,
+            pending: 0,
+            max: 0,
+        }
+    }
+
+    fn index(value: u64) -> usize {
+        if value < SUB_BUCKETS as u64 {
+            return value as usize;
+        }
+        let exp = 63 - value.leading_zeros();
+        let sub = (value >> (exp - SUB_BUCKET_BITS)) as usize & (SUB_BUCKETS - 1);
+        (exp - SUB_BUCKET_BITS + 1) as usize * SUB_BUCKETS + sub
+    }
+
+    /// Lowest value of the bucket.
+    fn value(index: usize) -> u64 {
+        if index < SUB_BUCKETS {
+            return index as u64;
+        }
+        let exp = (index / SUB_BUCKETS) as u32 + SUB_BUCKET_BITS - 1;
+        let sub = (index % SUB_BUCKETS) as u64;
+        (SUB_BUCKETS as u64 + sub) << (exp - SUB_BUCKET_BITS)
+    }
+
+    fn record(&mut self, elapsed: Duration, pending: bool) {
+        let value = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.counts[Self::index(value)] += 1;
+        self.max = self.max.max(value);
+        if pending {
+            self.pending += 1;
+        } else {
+            self.immediate += 1;
+        }
+    }
+
+    fn percentile(&self, q: f64) -> Duration {
+        let count = self.immediate + self.pending;
+        if count == 0 {
+            return Duration::from_nanos(0);
+        }
+        let rank = ((q * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, n) in self.counts.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Duration::from_nanos(Self::value(index).min(self.max));
+            }
+        }
+        Duration::from_nanos(self.max)
+    }
+
+    fn snapshot(&self) -> OpLatency {
+        OpLatency {
+            count: self.immediate + self.pending,
+            immediate: self.immediate,
+            pending: self.pending,
+            p50: self.percentile(0.50),
+            p95: self.percentile(0.95),
+            p99: self.percentile(0.99),
+            max: Duration::from_nanos(self.max),
+        }
+    }
+}
+
+/// Operation in flight.
+#[derive(Default)]
+struct InFlight {
+    started: Option<Instant>,
+    pending: bool,
+}
+
+impl InFlight {
+    fn poll<T>(&mut self, histogram: &mut Histogram, poll: impl FnOnce() -> Poll<T>) -> Poll<T> {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        match poll() {
+            Poll::Ready(result) => {
+                histogram.record(started.elapsed(), self.pending);
+                *self = Self::default();
+                Poll::Ready(result)
+            }
+            Poll::Pending => {
+                self.pending = true;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Read or write half recording the latency of each operation.
+///
+/// The latency of an operation is the time from its first poll to its
+/// completion. Recording does not allocate. Available with the `metrics`
+/// feature.
+pub struct Instrumented<T> {
+    inner: T,
+    reads: Histogram,
+    writes: Histogram,
+    read: InFlight,
+    write: InFlight,
+}
+
+impl<T> fmt::Debug for Instrumented<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instrumented")
+            .field("inner", &self.inner)
+            .field("snapshot", &self.snapshot())
+            .finish()
+    }
+}
+
+impl<T> Instrumented<T> {
+    /// Wrap `inner`.
+    pub fn wrap(inner: T) -> Self {
+        Self {
+            inner,
+            reads: Histogram::new(),
+            writes: Histogram::new(),
+            read: InFlight::default(),
+            write: InFlight::default(),
+        }
+    }
+
+    /// Latency distribution of the operations completed so far.
+    pub fn snapshot(&self) -> LatencySnapshot {
+        LatencySnapshot {
+            reads: self.reads.snapshot(),
+            writes: self.writes.snapshot(),
+        }
+    }
+
+    /// Gets a reference to the underlying half.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Unwrap the underlying half.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Latency distribution of reads and writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    reads: OpLatency,
+    writes: OpLatency,
+}
+
+impl LatencySnapshot {
+    /// Latency of `poll_read`.
+    pub fn reads(&self) -> &OpLatency {
+        &self.reads
+    }
+
+    /// Latency of `poll_write`.
+    pub fn writes(&self) -> &OpLatency {
+        &self.writes
+    }
+}
+
+/// Latency distribution of one kind of operation.
+///
+/// Percentiles are accurate to 1/16 of the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpLatency {
+    count: u64,
+    immediate: u64,
+    pending: u64,
+    p50: Duration,
+    p95: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+impl OpLatency {
+    /// Number of completed operations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Number of operations completed on the first poll.
+    pub fn immediate(&self) -> u64 {
+        self.immediate
+    }
+
+    /// Number of operations which returned `Pending` at least once.
+    pub fn pending(&self) -> u64 {
+        self.pending
+    }
+
+    /// Median.
+    pub fn p50(&self) -> Duration {
+        self.p50
+    }
+
+    /// 95th percentile.
+    pub fn p95(&self) -> Duration {
+        self.p95
+    }
+
+    /// 99th percentile.
+    pub fn p99(&self) -> Duration {
+        self.p99
+    }
+
+    /// Maximum.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+}
+
+impl<T> AsyncRead for Instrumented<T>
+where
+    T: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let inner = &mut this.inner;
+        this.read
+            .poll(&mut this.reads, || Pin::new(inner).poll_read(cx, buf))
+    }
+}
+
+impl<T> AsyncWrite for Instrumented<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let inner = &mut this.inner;
+        this.write
+            .poll(&mut this.writes, || Pin::new(inner).poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_histogram() {
+        for value in (0..100_000).chain([u64::MAX / 2, u64::MAX]) {
+            let index = Histogram::index(value);
+            assert!(index < BUCKETS);
+            let low = Histogram::value(index);
+            assert!(low <= value);
+            assert!(value - low <= value / SUB_BUCKETS as u64);
+        }
+
+        let mut histogram = Histogram::new();
+        for ms in 1..=100 {
+            histogram.record(Duration::from_millis(ms), false);
+        }
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count(), 100);
+        assert!(snapshot.p50() >= Duration::from_millis(47));
+        assert!(snapshot.p50() <= Duration::from_millis(50));
+        assert!(snapshot.p99() >= Duration::from_millis(93));
+        assert_eq!(snapshot.max(), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_instrumented() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        let mut r = Instrumented::wrap(r);
+        let mut w = Instrumented::wrap(w);
+
+        for _ in 0..10 {
+            w.write_all(b"0123456789").await?;
+            let mut buf = [0; 10];
+            r.read_exact(&mut buf).await?;
+        }
+        let writes = *w.snapshot().writes();
+        assert_eq!(writes.count(), 10);
+        assert_eq!(writes.immediate() + writes.pending(), 10);
+        assert_eq!(w.snapshot().reads().count(), 0);
+        let reads = *r.snapshot().reads();
+        assert!(reads.count() >= 10);
+        assert_eq!(reads.immediate() + reads.pending(), reads.count());
+        let fast = reads.p99();
+
+        let writer = tokio::spawn(async move {
+            for _ in 0..5 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                w.write_all(b"0123456789").await?;
+            }
+            Ok::<_, io::Error>(())
+        });
+        for _ in 0..5 {
+            let mut buf = [0; 10];
+            r.read_exact(&mut buf).await?;
+        }
+        writer.await.unwrap()?;
+        let reads = *r.snapshot().reads();
+        assert!(reads.pending() >= 5);
+        assert!(reads.p99() >= Duration::from_millis(40));
+        assert!(reads.p99() > fast);
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+//! Stream integrity checks with CRC32C.
+//!
+//! The stream is framed into blocks: a 4-byte little-endian payload length,
+//! the payload, and the 4-byte little-endian CRC32C (Castagnoli) of the
+//! payload.
+use std::convert::TryFrom;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{self, AsyncRead, AsyncWrite};
+
+/// Default block size of [`ChecksummedWrite`] and [`ChecksummedRead`].
+pub const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+const HEADER_LEN: usize = 4;
+const TRAILER_LEN: usize = 4;
+
+/// CRC32C (Castagnoli, reflected) of `data`.
+fn crc32c(data: &[u8]) -> u32 {
+    const POLY: u32 = 0x82f6_3b78;
+
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Write half framing the stream into checksummed blocks.
+///
+/// Read the other end with [`ChecksummedRead`]. Each block costs 8 bytes,
+/// so larger blocks mean less overhead but later detection.
+///
+/// Written bytes are buffered until a block is full, like
+/// `tokio::io::BufWriter`: `flush` sends the partial block, and dropping
+/// the write half without flushing loses it.
+#[derive(Debug)]
+pub struct ChecksummedWrite<T> {
+    inner: T,
+    block_size: usize,
+    buf: Vec<u8>,
+    sealed: bool,
+    written: usize,
+}
+
+impl<T> ChecksummedWrite<T> {
+    /// Frame what is written into `inner`, in blocks of
+    /// [`DEFAULT_BLOCK_SIZE`].
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            block_size: DEFAULT_BLOCK_SIZE,
+            buf: vec![0; HEADER_LEN],
+            sealed: false,
+            written: 0,
+        }
+    }
+
+    /// Maximum payload bytes per block. Default is [`DEFAULT_BLOCK_SIZE`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is `0` or doesn't fit in a `u32`.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        assert!(block_size > 0, "block_size must be positive");
+        assert!(u32::try_from(block_size).is_ok(), "block_size too large");
+        self.block_size = block_size;
+        self
+    }
+
+    /// Gets a reference to the underlying write half.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    fn payload_len(&self) -> usize {
+        self.buf.len() - HEADER_LEN
+    }
+
+    /// Close the buffered block with its length and checksum.
+    fn seal(&mut self) {
+        let crc = crc32c(&self.buf[HEADER_LEN..]);
+        let len = self.payload_len() as u32;
+        self.buf[..HEADER_LEN].copy_from_slice(&len.to_le_bytes());
+        self.buf.extend_from_slice(&crc.to_le_bytes());
+        self.sealed = true;
+    }
+}
+
+impl<T: AsyncWrite + Unpin> ChecksummedWrite<T> {
+    /// Write the sealed block, if any.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.sealed {
+            return Poll::Ready(Ok(()));
+        }
+        while self.written < self.buf.len() {
+            let n = match Pin::new(&mut self.inner).poll_write(cx, &self.buf[self.written..]) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => return Poll::Pending,
+            };
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        self.buf.truncate(HEADER_LEN);
+        self.sealed = false;
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for ChecksummedWrite<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        }
+        let n = buf.len().min(this.block_size - this.payload_len());
+        this.buf.extend_from_slice(&buf[..n]);
+        if this.payload_len() == this.block_size {
+            this.seal();
+            if let Poll::Ready(Err(err)) = this.poll_drain(cx) {
+                return Poll::Ready(Err(err));
+            }
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        if !this.sealed && this.payload_len() > 0 {
+            this.seal();
+        }
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        match Pin::new(&mut *this).poll_flush(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_shutdown(cx),
+            other => other,
+        }
+    }
+}
+
+/// Read half verifying the blocks written by [`ChecksummedWrite`].
+///
+/// Fails with `ErrorKind::InvalidData` naming the stream offset of the
+/// first block whose checksum doesn't match, or which is larger than the
+/// block size, and with `ErrorKind::UnexpectedEof` if the stream ends
+/// within a block. Nothing of a bad block is returned.
+#[derive(Debug)]
+pub struct ChecksummedRead<T> {
+    inner: T,
+    block_size: usize,
+    raw: Vec<u8>,
+    block: Vec<u8>,
+    pos: usize,
+    offset: u64,
+}
+
+impl<T> ChecksummedRead<T> {
+    /// Verify what is read from `inner`, accepting blocks up to
+    /// [`DEFAULT_BLOCK_SIZE`].
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            block_size: DEFAULT_BLOCK_SIZE,
+            raw: vec![],
+            block: vec![],
+            pos: 0,
+            offset: 0,
+        }
+    }
+
+    /// Largest block accepted. Default is [`DEFAULT_BLOCK_SIZE`].
+    ///
+    /// Must be at least the block size of the writer.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Gets a reference to the underlying read half.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Verify the first block in `raw`, if it arrived completely.
+    fn next_block(&mut self) -> io::Result<bool> {
+        if self.raw.len() < HEADER_LEN {
+            return Ok(false);
+        }
+        let mut header = [0; HEADER_LEN];
+        header.copy_from_slice(&self.raw[..HEADER_LEN]);
+        let len = u32::from_le_bytes(header) as usize;
+        if len > self.block_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "block at offset {} of {} bytes exceeds {}",
+                    self.offset, len, self.block_size
+                ),
+            ));
+        }
+        if self.raw.len() < HEADER_LEN + len + TRAILER_LEN {
+            return Ok(false);
+        }
+        let payload = &self.raw[HEADER_LEN..HEADER_LEN + len];
+        let mut trailer = [0; TRAILER_LEN];
+        trailer.copy_from_slice(&self.raw[HEADER_LEN + len..HEADER_LEN + len + TRAILER_LEN]);
+        let expected = u32::from_le_bytes(trailer);
+        let actual = crc32c(payload);
+        if expected != actual {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "checksum mismatch {:08x} != {:08x} in block at offset {}",
+                    actual, expected, self.offset
+                ),
+            ));
+        }
+        self.block.clear();
+        self.block.extend_from_slice(payload);
+        self.pos = 0;
+        self.raw.drain(..HEADER_LEN + len + TRAILER_LEN);
+        self.offset += len as u64;
+        Ok(true)
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ChecksummedRead<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            if this.pos < this.block.len() {
+                let n = buf.remaining().min(this.block.len() - this.pos);
+                buf.put_slice(&this.block[this.pos..this.pos + n]);
+                this.pos += n;
+                return Poll::Ready(Ok(()));
+            }
+            if this.next_block()? {
+                continue;
+            }
+
+            let mut chunk = [0; 8 * 1024];
+            let mut chunk = io::ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+            if chunk.filled().is_empty() {
+                if this.raw.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            this.raw.extend_from_slice(chunk.filled());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{anon_pipe, AnonPipeRead};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Read half flipping the bits of the byte at a stream offset.
+    struct Corrupt {
+        inner: AnonPipeRead,
+        at: usize,
+        pos: usize,
+    }
+
+    impl AsyncRead for Corrupt {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut io::ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            let filled = buf.filled().len();
+            let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+            let n = buf.filled().len() - filled;
+            if (this.pos..this.pos + n).contains(&this.at) {
+                buf.filled_mut()[filled + this.at - this.pos] ^= 0xff;
+            }
+            this.pos += n;
+            result
+        }
+    }
+
+    #[test]
+    fn test_crc32c() {
+        // check value of the CRC-32C catalogue entry.
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+    }
+
+    #[tokio::test]
+    async fn test_checksummed() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        let mut r = ChecksummedRead::new(r).block_size(100);
+        let mut w = ChecksummedWrite::new(w).block_size(100);
+
+        let data = (0..1000).map(|i| i as u8).collect::<Vec<_>>();
+        let expected = data.clone();
+        let writer = tokio::spawn(async move {
+            w.write_all(&data[..10]).await?;
+            w.flush().await?;
+            w.write_all(&data[10..]).await?;
+            w.shutdown().await
+        });
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        writer.await.unwrap()?;
+        assert_eq!(buf, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksummed_corrupted() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        // 3rd block, payload offset 200: 2 blocks of 108 bytes, 4 header
+        // bytes, then 50 bytes into the payload.
+        let r = Corrupt {
+            inner: r,
+            at: 2 * 108 + 4 + 50,
+            pos: 0,
+        };
+        let mut r = ChecksummedRead::new(r).block_size(100);
+        let mut w = ChecksummedWrite::new(w).block_size(100);
+
+        let writer = tokio::spawn(async move {
+            w.write_all(&[0x55; 1000]).await?;
+            w.shutdown().await
+        });
+        let mut buf = vec![];
+        let err = r.read_to_end(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("offset 200"), "{}", err);
+        assert_eq!(buf.len(), 200);
+        drop(r);
+        let _ = writer.await.unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksummed_truncated() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let mut r = ChecksummedRead::new(r);
+
+        w.write_all(&[10, 0, 0, 0, b'H', b'i']).await?;
+        drop(w);
+        let err = r.read_to_end(&mut vec![]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Keepalive probe for the write half.
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io;
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::AnonPipeWrite;
+
+/// Write half probing the peer at an interval.
+///
+/// Created by [`AnonPipeWrite::with_keepalive`].
+/// Once a probe fails, every subsequent write fails with
+/// `ErrorKind::BrokenPipe`.
+#[derive(Debug)]
+pub struct KeepAlive {
+    inner: Arc<AnonPipeWrite>,
+    alive: Arc<AtomicBool>,
+    task: JoinHandle<()>,
+}
+
+impl KeepAlive {
+    pub(crate) fn new(inner: AnonPipeWrite, interval: Duration) -> Self {
+        let inner = Arc::new(inner);
+        let alive = Arc::new(AtomicBool::new(true));
+        let task = tokio::spawn(probe(Arc::downgrade(&inner), alive.clone(), interval));
+        Self { inner, alive, task }
+    }
+
+    /// `false` once a probe detected the peer is gone.
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    /// Gets a reference to the underlying write half.
+    pub fn get_ref(&self) -> &AnonPipeWrite {
+        &self.inner
+    }
+
+    /// Exclusive access to the write half, or `None` while a probe holds it.
+    ///
+    /// The probe holds it only for one non-blocking write, so the task is
+    /// woken to try again right away.
+    fn inner_mut(&mut self, cx: &mut Context<'_>) -> Option<&mut AnonPipeWrite> {
+        let inner = Arc::get_mut(&mut self.inner);
+        if inner.is_none() {
+            cx.waker().wake_by_ref();
+        }
+        inner
+    }
+}
+
+async fn probe(pipe: Weak<AnonPipeWrite>, alive: Arc<AtomicBool>, interval: Duration) {
+    let mut interval = time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval.tick().await; // first tick completes immediately.
+    loop {
+        interval.tick().await;
+        let pipe = match pipe.upgrade() {
+            Some(pipe) => pipe,
+            None => return,
+        };
+        match pipe.try_write(&[]) {
+            // a write is in flight. the peer was alive a moment ago.
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(..) => {
+                alive.store(false, Ordering::SeqCst);
+                return;
+            }
+            Ok(..) => {}
+        }
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl io::AsyncWrite for KeepAlive {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        loop {
+            if !self.is_alive() {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+            match self.inner.poll_write_ready(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+            match self.inner.try_write(buf) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        match self.get_mut().inner_mut(cx) {
+            Some(inner) => Pin::new(inner).poll_flush(cx),
+            None => Poll::Pending,
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        // nothing is written after shutdown, stop probing.
+        this.task.abort();
+        match this.inner_mut(cx) {
+            Some(inner) => Pin::new(inner).poll_shutdown(cx),
+            None => Poll::Pending,
+        }
+    }
+}
@@ -13,6 +13,29 @@
 //!
 //! `x86_64-pc-windows-msvc` only
 //!
+//! # Features
+//!
+//! - `bytes`: `AnonPipeRead::read_frame_checked`, frames with a CRC32
+//!   trailer, and `AnonPipeWrite::write_owned`.
+//! - `compression`: `CompressedWrite` and `CompressedRead`, zlib compressed
+//!   traffic with `flate2`.
+//! - `handle-audit`: `live_pipes`, registry of the live ends to detect
+//!   leaked handles in tests. Adds no overhead when disabled.
+//! - `integrity`: `ChecksummedWrite` and `ChecksummedRead`, CRC32C checked
+//!   blocks to detect corruption in transit.
+//! - `metrics`: `Instrumented`, latency histograms of reads and writes.
+//! - `rand`: randomize pipe names with `rand`, for the users already
+//!   configuring it. Otherwise the names are randomized with `getrandom`.
+//! - `serde`: `typed_channel`, and `PipeHandle`, a serializable
+//!   descriptor to hand an end over to another process.
+//! - `spillover`: `AnonPipeRead::spillover_reader`, capture to memory and a
+//!   temporary file.
+//! - `stats`: `creation_stats`, counters of created pipes and retries.
+//! - `stream`: `AnonPipeRead::frames`.
+//! - `test-util`: `Loopback`, echo target for protocol tests, and
+//!   `anon_pipe_tiny`, fragmenting every transfer.
+//! - `tokio-util`: `Cancellable`.
+//!
 //! # Example
 //!
 //! ```
@@ -28,16 +51,34 @@
 //!     let len = r.read(&mut buf[..]).await?;
 //!
 //!     assert_eq!(&buf[..len], &b"HELLO, WORLD!"[..]);
+//!
+//!     w.close().await?;
+//!     r.close().await?;
 //!     Ok(())
 //! }
 //! ```
+//!
+//! # Closing
+//!
+//! Rust has no async drop: dropping an end does **not** flush. In-flight
+//! writes may still complete after the drop, but their errors are lost, and
+//! so may be the data if the reader is gone. Close the write end with
+//! [`AnonPipeWrite::close`] (or [`AnonPipeWrite::close_graceful`] to also
+//! wait until the reader drained the pipe) to learn whether everything
+//! written was accepted.
+use std::convert::TryFrom;
+use std::ffi::c_void;
+use std::fmt;
 use std::mem;
 #[cfg(windows)]
-use std::os::windows::io::{AsRawHandle, IntoRawHandle, RawHandle};
+use std::os::windows::io::{AsRawHandle, IntoRawHandle, OwnedHandle, RawHandle};
 use std::pin::Pin;
 use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
+use security::{RawSecurityAttributes, Security, SecurityDescriptor};
 #[cfg(not(windows))]
 use stub::*;
 use tokio::io;
@@ -46,9 +87,122 @@ use tokio::net::windows::named_pipe::{
     ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions,
 };
 
+mod audit;
+mod barrier;
+mod bitness;
+mod blocking;
+#[cfg(feature = "tokio-util")]
+mod cancel;
+mod capped;
+mod chain;
+#[cfg(feature = "bytes")]
+mod checked;
+mod child;
+mod chunked;
+#[cfg(feature = "compression")]
+mod compress;
+mod counted;
+mod deadline;
+mod defaults;
+mod duplex;
+mod error;
+pub mod errors;
+#[cfg(feature = "stream")]
+mod frames;
+#[cfg(feature = "serde")]
+mod handle;
+mod header;
+mod highwater;
+mod hinted;
+mod info;
+#[cfg(feature = "metrics")]
+mod instrumented;
+#[cfg(feature = "integrity")]
+mod integrity;
+mod keepalive;
+mod limit;
+mod listener;
+#[cfg(feature = "test-util")]
+mod loopback;
+mod msg;
+mod name;
+mod owned;
+mod parts;
+mod peek;
+mod prefix;
+mod pump;
+mod security;
+mod select;
+mod server;
+#[cfg(feature = "spillover")]
+mod spillover;
+#[cfg(feature = "stats")]
+mod stats;
+mod swap;
+mod sys;
+#[cfg(feature = "test-util")]
+mod tiny;
+#[cfg(feature = "serde")]
+mod typed;
+mod vectored;
+
+#[cfg(feature = "handle-audit")]
+pub use audit::{live_pipes, LivePipeInfo};
+pub use barrier::{BARRIER_ACK, BARRIER_REQUEST};
+pub use bitness::Bitness;
+#[cfg(feature = "tokio-util")]
+pub use cancel::Cancellable;
+pub use capped::CapExceeded;
+pub use chain::ChainedRead;
+pub use child::{spawn_with_child_reading, spawn_with_child_writing};
+pub use chunked::ChunkedWrite;
+#[cfg(feature = "compression")]
+pub use compress::{CompressedRead, CompressedWrite};
+pub use counted::{Counted, PipeStats};
+pub use deadline::{ReadExactTimeout, TimeoutPartial, WriteAllTimeout};
+pub use defaults::{set_global_defaults, AnonPipeDefaults};
+pub use duplex::{copy_bidirectional, tunnel, AnonPipeDuplex, DuplexReadHalf, DuplexWriteHalf};
+pub use error::{CreationStage, Error};
+pub use errors::is_transient;
+#[cfg(feature = "stream")]
+pub use frames::Frames;
+#[cfg(feature = "serde")]
+pub use handle::PipeHandle;
+pub use header::{Endian, FromBytes, ToBytes};
+pub use highwater::{HighWater, HighWaterMark};
+pub use info::PipeInfo;
+#[cfg(feature = "metrics")]
+pub use instrumented::{Instrumented, LatencySnapshot, OpLatency};
+#[cfg(feature = "integrity")]
+pub use integrity::{ChecksummedRead, ChecksummedWrite, DEFAULT_BLOCK_SIZE};
+pub use keepalive::KeepAlive;
+pub use limit::DEFAULT_MAX_CONCURRENT_CREATIONS;
+pub use listener::AnonPipeListener;
+#[cfg(feature = "test-util")]
+pub use loopback::{Loopback, LoopbackHandle};
+pub use name::{parse_pipe_name, validate_pipe_name, NameError, PipeNameParts, MAX_PIPE_NAME_LEN};
+pub use owned::ReadOwned;
+#[cfg(feature = "bytes")]
+pub use owned::WriteOwned;
+pub use parts::{from_parts, Direction};
+pub use peek::{PeekableRead, DEFAULT_MAX_PEEK};
+pub use prefix::PrefixedRead;
+pub use pump::{pump, pump_to_eof, PumpOptions};
+pub use select::select_readable;
+pub use server::AnonPipeServer;
+#[cfg(feature = "spillover")]
+pub use spillover::SpilloverRead;
+#[cfg(feature = "stats")]
+pub use stats::{creation_stats, reset_creation_stats, CreationStats};
+pub use swap::SwappableWrite;
+#[cfg(feature = "test-util")]
+pub use tiny::{anon_pipe_tiny, Tiny};
+#[cfg(feature = "serde")]
+pub use typed::{typed_channel, TypedReceiver, TypedSender, DEFAULT_MAX_FRAME_SIZE};
+
 #[cfg(not(windows))]
 mod stub {
-    #![allow(unused_variables)]
+    #![allow(unused_variables, dead_code)]
     //! stub for non windows.
     //! developing reason.
     use super::*;
@@ -63,10 +217,25 @@ mod stub {
         fn into_raw_handle(self) -> RawHandle;
     }
 
-    pub(super) trait AsRawHandle {
+    pub trait AsRawHandle {
         fn as_raw_handle(&self) -> RawHandle;
     }
 
+    #[derive(Debug)]
+    pub struct OwnedHandle;
+
+    impl IntoRawHandle for OwnedHandle {
+        fn into_raw_handle(self) -> RawHandle {
+            panic!("stub")
+        }
+    }
+
+    impl AsRawHandle for OwnedHandle {
+        fn as_raw_handle(&self) -> RawHandle {
+            panic!("stub")
+        }
+    }
+
     impl NamedPipeServer {
         pub(super) async fn connect(&self) -> io::Result<()> {
             panic!("stub")
@@ -102,6 +271,28 @@ mod stub {
         }
     }
 
+    impl NamedPipeServer {
+        pub(super) unsafe fn from_raw_handle(handle: RawHandle) -> io::Result<Self> {
+            panic!("stub")
+        }
+
+        pub(super) fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            panic!("stub")
+        }
+
+        pub(super) fn poll_write_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            panic!("stub")
+        }
+
+        pub(super) fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+            panic!("stub")
+        }
+
+        pub(super) fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+            panic!("stub")
+        }
+    }
+
     impl AsRawHandle for NamedPipeServer {
         fn as_raw_handle(&self) -> RawHandle {
             panic!("stub")
@@ -140,33 +331,323 @@ mod stub {
         }
     }
 
+    impl NamedPipeClient {
+        pub(super) unsafe fn from_raw_handle(handle: RawHandle) -> io::Result<Self> {
+            panic!("stub")
+        }
+
+        pub(super) fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            panic!("stub")
+        }
+
+        pub(super) fn poll_write_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            panic!("stub")
+        }
+
+        pub(super) fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+            panic!("stub")
+        }
+
+        pub(super) fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+            panic!("stub")
+        }
+    }
+
     impl AsRawHandle for NamedPipeClient {
         fn as_raw_handle(&self) -> RawHandle {
             panic!("stub")
         }
     }
 
-    pub(super) fn new_server(
-        name: &str,
-        reject_remote_clients: bool,
-        write: bool,
-    ) -> io::Result<NamedPipeServer> {
-        panic!("stub")
+    #[derive(Debug, Clone)]
+    pub struct ServerOptions;
+
+    impl ServerOptions {
+        pub(super) fn new() -> Self {
+            Self
+        }
+
+        pub(super) fn access_inbound(&mut self, allowed: bool) -> &mut Self {
+            self
+        }
+
+        pub(super) fn access_outbound(&mut self, allowed: bool) -> &mut Self {
+            self
+        }
+
+        pub(super) fn first_pipe_instance(&mut self, first: bool) -> &mut Self {
+            self
+        }
+
+        pub(super) fn reject_remote_clients(&mut self, reject: bool) -> &mut Self {
+            self
+        }
+
+        pub(super) fn max_instances(&mut self, instances: usize) -> &mut Self {
+            self
+        }
+
+        pub(super) fn in_buffer_size(&mut self, buffer: u32) -> &mut Self {
+            self
+        }
+
+        pub(super) fn out_buffer_size(&mut self, buffer: u32) -> &mut Self {
+            self
+        }
+
+        pub(super) fn pipe_mode(&mut self, pipe_mode: PipeMode) -> &mut Self {
+            self
+        }
+
+        pub(super) fn create(
+            &self,
+            addr: impl AsRef<std::ffi::OsStr>,
+        ) -> io::Result<NamedPipeServer> {
+            panic!("stub")
+        }
+
+        pub(super) unsafe fn create_with_security_attributes_raw(
+            &self,
+            addr: impl AsRef<std::ffi::OsStr>,
+            attrs: *mut std::ffi::c_void,
+        ) -> io::Result<NamedPipeServer> {
+            panic!("stub")
+        }
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub enum PipeMode {
+        Byte,
+        Message,
     }
 
-    pub(super) fn new_client(name: &str, write: bool) -> io::Result<NamedPipeClient> {
-        panic!("stub")
+    #[derive(Debug, Clone)]
+    pub struct ClientOptions;
+
+    impl ClientOptions {
+        pub(super) fn new() -> Self {
+            Self
+        }
+
+        pub(super) fn read(&mut self, allowed: bool) -> &mut Self {
+            self
+        }
+
+        pub(super) fn write(&mut self, allowed: bool) -> &mut Self {
+            self
+        }
+
+        pub(super) fn open(
+            &self,
+            path: impl AsRef<std::ffi::OsStr>,
+        ) -> io::Result<NamedPipeClient> {
+            panic!("stub")
+        }
     }
 }
 
+/// Unpredictable part of the name.
+#[cfg(feature = "rand")]
+fn entropy() -> [u8; 16] {
+    rand::random()
+}
+
+/// Unpredictable part of the name, from the OS (`ProcessPrng` or
+/// `BCryptGenRandom`).
+#[cfg(not(feature = "rand"))]
+fn entropy() -> [u8; 16] {
+    secure_entropy().unwrap_or_else(|_| fallback_entropy())
+}
+
+/// Output of a CSPRNG, without fallback.
+#[cfg(feature = "rand")]
+fn secure_entropy() -> io::Result<[u8; 16]> {
+    Ok(rand::random())
+}
+
+/// Output of a CSPRNG, without fallback.
+#[cfg(not(feature = "rand"))]
+fn secure_entropy() -> io::Result<[u8; 16]> {
+    let mut buf = [0; 16];
+    getrandom::getrandom(&mut buf).map_err(|err| io::Error::other(err.to_string()))?;
+    Ok(buf)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The performance counter, if the OS failed to provide randomness.
+/// Together with pid, thread id and the counter, the name is still unique,
+/// but it is predictable.
+#[cfg(not(feature = "rand"))]
+fn fallback_entropy() -> [u8; 16] {
+    let mut count = 0;
+    unsafe { sys::QueryPerformanceCounter(&mut count) };
+    (count as u128).to_be_bytes()
+}
+
 fn genname() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
     let procid = process::id();
-    let random = rand::random::<usize>();
+    let threadid = unsafe { sys::GetCurrentThreadId() };
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let random = hex(&entropy());
+
+    let name = format!(
+        "{}{}{}.{}.{}.{}",
+        name::NAMESPACE,
+        name::MARKER,
+        procid,
+        threadid,
+        count,
+        random
+    );
+    debug_assert_eq!(validate_pipe_name(&name), Ok(()));
+    name
+}
+
+/// Name telling nothing about the process, see
+/// [`AnonPipeBuilder::unpredictable_names`].
+fn unpredictable_name() -> io::Result<String> {
+    let name = format!(
+        "{}{}{}",
+        name::NAMESPACE,
+        name::MARKER,
+        hex(&secure_entropy()?)
+    );
+    debug_assert_eq!(validate_pipe_name(&name), Ok(()));
+    Ok(name)
+}
+
+fn peer_session_id(handle: RawHandle, server: bool) -> io::Result<u32> {
+    let mut id = 0;
+    let ok = unsafe {
+        if server {
+            sys::GetNamedPipeClientSessionId(handle as _, &mut id)
+        } else {
+            sys::GetNamedPipeServerSessionId(handle as _, &mut id)
+        }
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(id)
+}
+
+/// The instance counts are reported to the server role only.
+fn server_role(server: bool) -> io::Result<()> {
+    if !server {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "instance counts are available on a server end only",
+        ));
+    }
+    Ok(())
+}
+
+fn max_instances(handle: RawHandle, server: bool) -> io::Result<u32> {
+    server_role(server)?;
+    Ok(PipeInfo::query(handle)?.max_instances())
+}
 
-    format!(r"\\.\pipe\__tokio_anonymous_pipe0__.{}.{}", procid, random)
+fn current_instances(handle: RawHandle, server: bool) -> io::Result<u32> {
+    server_role(server)?;
+    let mut instances = 0;
+    let ok = unsafe {
+        sys::GetNamedPipeHandleStateW(
+            handle as _,
+            std::ptr::null_mut(),
+            &mut instances,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(instances)
+}
+
+/// `GetHandleInformation` succeeds only on an open handle.
+fn is_valid(handle: RawHandle) -> bool {
+    let mut flags = 0;
+    unsafe { sys::GetHandleInformation(handle as _, &mut flags) != 0 }
+}
+
+/// Interval of checking the peer in `closed`.
+pub const CLOSED_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// `true` if the pipe is disconnected, checked without consuming data.
+fn peer_closed(handle: RawHandle) -> bool {
+    let ok = unsafe {
+        sys::PeekNamedPipe(
+            handle as _,
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    ok == 0
+}
+
+/// Error of IO on a pipe without a peer: not connected yet, or the peer
+/// is gone.
+fn is_disconnected(err: &io::Error) -> bool {
+    errors::is_peer_gone(err) || err.raw_os_error() == Some(ERROR_PIPE_LISTENING)
+}
+
+/// `true` if a peer is attached, checked with a zero-byte `PeekNamedPipe`.
+/// The handle needs read access.
+fn peek_connected(handle: RawHandle) -> io::Result<bool> {
+    if !is_valid(handle) {
+        return Ok(false);
+    }
+    if !peer_closed(handle) {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if is_disconnected(&err) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
 }
 
 /// Asyncronous Pipe Read.
+///
+/// # End of stream
+///
+/// Windows completes a pending `ReadFile` with `ERROR_BROKEN_PIPE` once the
+/// last handle of the write end is closed, no matter whether the writer
+/// shut down and dropped it or its process crashed and the kernel closed it.
+/// Both cases are reported as `Ok(0)` (EOF) by `poll_read`.
+/// `shutdown` on [`AnonPipeWrite`] does not close the handle, so EOF is
+/// observed only after the write end is dropped.
+///
+/// The pipe itself cannot tell a clean exit from a crash. If the
+/// distinction matters, end the stream with an application level trailer
+/// (or check the peer process exit status).
+///
+/// # Runtime
+///
+/// Each end is registered with the reactor of the runtime it was created
+/// in (see [`AnonPipeBuilder::runtime`]), and stays bound to it when moved
+/// into a task of another runtime. Its readiness is then only delivered
+/// while the creating runtime is driven: an idle current-thread runtime
+/// makes reads and writes hang, a shut down one makes them fail. Create the
+/// ends in (or for) the runtime that uses them.
+///
+/// Reads and writes spend the cooperative budget of the task (see
+/// `tokio::task::coop`), as on tokio's TCP streams: once it is spent, even
+/// a ready end returns `Poll::Pending`, so a loop on a fast peer yields to
+/// the other tasks of its thread. The non-async [`try_read`](Self::try_read)
+/// and [`AnonPipeWrite::try_write`] spend none.
 #[derive(Debug)]
 pub enum AnonPipeRead {
     Server(NamedPipeServer),
@@ -176,11 +657,302 @@ pub enum AnonPipeRead {
 impl AnonPipeRead {
     async fn connect(&self) -> io::Result<()> {
         match self {
-            Self::Server(inner) => inner.connect().await?,
+            Self::Server(inner) => connect_server(inner, Deadline::default()).await?,
             _ => panic!("not a server"),
         }
         Ok(())
     }
+
+    /// Terminal Services session id of the peer process.
+    ///
+    /// See [`AnonPipeBuilder::cross_session`].
+    pub fn peer_session_id(&self) -> io::Result<u32> {
+        peer_session_id(self.as_raw_handle(), matches!(self, Self::Server(..)))
+    }
+
+    /// Buffer sizes and mode of the pipe.
+    pub fn pipe_info(&self) -> io::Result<PipeInfo> {
+        PipeInfo::query(self.as_raw_handle())
+    }
+
+    /// Maximum number of instances of the pipe name, `255` meaning
+    /// unlimited.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` for a client end.
+    pub fn max_instances(&self) -> io::Result<u32> {
+        max_instances(self.as_raw_handle(), matches!(self, Self::Server(..)))
+    }
+
+    /// Number of instances of the pipe name currently open, including the
+    /// ones still waiting for a client.
+    ///
+    /// Instances come and go concurrently, the count may be stale as soon
+    /// as it is returned. Fails with `ErrorKind::InvalidInput` for a client
+    /// end.
+    pub fn current_instances(&self) -> io::Result<u32> {
+        current_instances(self.as_raw_handle(), matches!(self, Self::Server(..)))
+    }
+
+    /// `false` if the handle was closed behind this end, e.g. through a
+    /// copy of the raw handle.
+    ///
+    /// Only tells the handle value is open, it may have been reused by an
+    /// unrelated object in the meantime.
+    pub fn is_valid(&self) -> bool {
+        is_valid(self.as_raw_handle())
+    }
+
+    /// `true` if the writer is attached, without reading.
+    ///
+    /// `false` before a client connected to a server end, after the writer
+    /// closed, or if the handle is no longer valid. The answer may be stale
+    /// as soon as it is returned: the writer can close right after.
+    ///
+    /// Data not read yet stays readable after the writer closed.
+    pub fn is_connected(&self) -> io::Result<bool> {
+        peek_connected(self.as_raw_handle())
+    }
+
+    /// Copy the bytes at the head of the pipe buffer into `buf` without
+    /// consuming them. Returns `0` if nothing is buffered.
+    ///
+    /// Calls `PeekNamedPipe` directly: never waits and needs no runtime, so
+    /// a watchdog thread can sample the stream, e.g. for health markers.
+    /// Only the pipe buffer is seen: once this end was read from, the
+    /// completion port driver keeps a read in flight which moves arriving
+    /// data out of it (see [`PeekableRead`]). Fails with
+    /// `ErrorKind::BrokenPipe` once the writer is gone.
+    pub fn peek_nonblocking(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = u32::try_from(buf.len()).unwrap_or(u32::MAX);
+        let mut read = 0;
+        let ok = unsafe {
+            sys::PeekNamedPipe(
+                self.as_raw_handle() as _,
+                buf.as_mut_ptr().cast(),
+                len,
+                &mut read,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(read as usize)
+    }
+
+    /// Number of bytes in the pipe buffer, as reported by `PeekNamedPipe`.
+    ///
+    /// Bytes already taken by the read kept in flight by the completion port
+    /// driver are not counted, so `0` does not mean a read would block.
+    /// Returns `0` once the writer is gone and the pipe buffer is empty.
+    pub fn bytes_available(&self) -> io::Result<u32> {
+        let mut available = 0;
+        let ok = unsafe {
+            sys::PeekNamedPipe(
+                self.as_raw_handle() as _,
+                std::ptr::null_mut(),
+                0,
+                std::ptr::null_mut(),
+                &mut available,
+                std::ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            let err = io::Error::last_os_error();
+            if errors::is_broken_pipe(&err) {
+                return Ok(0);
+            }
+            return Err(err);
+        }
+        Ok(available)
+    }
+
+    /// Poll until a read would not block, i.e. data arrived or the writer
+    /// is gone.
+    ///
+    /// Does not consume any data. See [`select_readable`] for waiting on
+    /// several read halves.
+    ///
+    /// Cancel safe: a future giving up after `Poll::Pending` loses nothing.
+    /// Readiness may be stale, so pair it with [`try_read`](Self::try_read)
+    /// and poll again on `ErrorKind::WouldBlock`.
+    pub fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self {
+            Self::Server(inner) => inner.poll_read_ready(cx),
+            Self::Client(inner) => inner.poll_read_ready(cx),
+        }
+    }
+
+    /// Read what arrived into `buf` without waiting, for futures written by
+    /// hand.
+    ///
+    /// Fails with `ErrorKind::WouldBlock` if nothing arrived, which clears
+    /// the readiness: poll [`poll_read_ready`](Self::poll_read_ready) again
+    /// to be woken. Returns `Ok(0)` at EOF.
+    pub fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Server(inner) => inner.try_read(buf),
+            Self::Client(inner) => inner.try_read(buf),
+        }
+    }
+
+    /// Wait until the peer closed the pipe.
+    ///
+    /// Named pipes never report `READ_CLOSED` readiness, so the pipe is
+    /// checked with `PeekNamedPipe` whenever it turns readable.
+    /// Does not consume any data. Resolves right away once the peer is gone
+    /// and everything was read. While data is buffered and not read yet,
+    /// the peer is checked every [`CLOSED_POLL_INTERVAL`] instead.
+    ///
+    /// Must be called within a tokio runtime with the time driver enabled.
+    pub async fn closed(&self) {
+        loop {
+            let ready = std::future::poll_fn(|cx| self.poll_read_ready(cx)).await;
+            if ready.is_err() || peer_closed(self.as_raw_handle()) {
+                return;
+            }
+            tokio::time::sleep(CLOSED_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Cancel pending reads and close.
+    ///
+    /// Data not read yet is discarded, the writer then fails with
+    /// `ErrorKind::BrokenPipe`. The handle is closed in any case.
+    pub async fn close(self) -> io::Result<()> {
+        // https://www.rpi.edu/dept/cis/software/g77-mingw32/include/winerror.h
+        const ERROR_NOT_FOUND: i32 = 1168;
+
+        let ok = unsafe { sys::CancelIoEx(self.as_raw_handle() as _, std::ptr::null_mut()) };
+        drop(self);
+        if ok == 0 {
+            let err = io::Error::last_os_error();
+            // nothing was in flight.
+            if err.raw_os_error() != Some(ERROR_NOT_FOUND) {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stream of messages sent by [`AnonPipeWrite::send_msg`], until EOF.
+    ///
+    /// Messages longer than `max_frame` yield `ErrorKind::InvalidData`.
+    /// See [`Frames`].
+    #[cfg(feature = "stream")]
+    pub fn frames(&mut self, max_frame: usize) -> Frames<'_> {
+        Frames::new(self, max_frame)
+    }
+
+    /// Read until EOF, calling `f` with each chunk read.
+    ///
+    /// Returns the total number of bytes read. Stops at the first error of
+    /// `f` and returns it. Chunk boundaries follow the reads, not the
+    /// writes of the peer.
+    pub async fn for_each_chunk<F>(&mut self, mut f: F) -> io::Result<u64>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        let mut buf = vec![0; 8 * 1024];
+        let mut total = 0;
+        loop {
+            let n = io::AsyncReadExt::read(self, &mut buf).await?;
+            if n == 0 {
+                return Ok(total);
+            }
+            f(&buf[..n])?;
+            total += n as u64;
+        }
+    }
+
+    /// Capture to EOF, keeping up to `mem_cap` bytes in memory and the rest
+    /// in a temporary file.
+    ///
+    /// See [`SpilloverRead`].
+    #[cfg(feature = "spillover")]
+    pub fn spillover_reader(self, mem_cap: usize) -> SpilloverRead {
+        SpilloverRead::new(self, mem_cap)
+    }
+
+    /// Wrap to look ahead without consuming.
+    ///
+    /// See [`PeekableRead::peek_exact`].
+    pub fn peekable(self) -> PeekableRead {
+        PeekableRead::new(self)
+    }
+
+    /// Read `next` once this read half reached EOF.
+    ///
+    /// See [`ChainedRead`].
+    pub fn chain(self, next: AnonPipeRead) -> ChainedRead {
+        ChainedRead::new(self, next)
+    }
+
+    /// Wrap to read `prefix` first, then from the pipe.
+    ///
+    /// See [`PrefixedRead`].
+    pub fn with_prefix(self, prefix: Vec<u8>) -> PrefixedRead {
+        PrefixedRead::new(self, prefix)
+    }
+
+    /// Read into `len` bytes region starting at `ptr`.
+    ///
+    /// Useful for streaming into a memory-mapped file without an
+    /// intermediate buffer of the caller. The region may be uninitialized.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes of `len` bytes, and nothing else may
+    /// access the region until the returned future completes or is dropped.
+    pub async unsafe fn read_into_ptr(&mut self, ptr: *mut u8, len: usize) -> io::Result<usize> {
+        let region = std::slice::from_raw_parts_mut(ptr as *mut mem::MaybeUninit<u8>, len);
+        let mut buf = io::ReadBuf::uninit(region);
+        std::future::poll_fn(|cx| io::AsyncRead::poll_read(Pin::new(&mut *self), cx, &mut buf))
+            .await?;
+        Ok(buf.filled().len())
+    }
+
+    /// Fail pending and subsequent reads once `token` is cancelled.
+    ///
+    /// See [`Cancellable`].
+    #[cfg(feature = "tokio-util")]
+    pub fn with_cancellation(
+        self,
+        token: tokio_util::sync::CancellationToken,
+    ) -> Cancellable<Self> {
+        Cancellable::new(self, token)
+    }
+
+    /// Receive a message sent by [`AnonPipeWrite::send_msg`].
+    ///
+    /// Messages longer than `max_len` fail with `ErrorKind::InvalidData`.
+    /// EOF before the whole message arrived fails with
+    /// `ErrorKind::UnexpectedEof`.
+    pub async fn recv_msg(&mut self, max_len: usize) -> io::Result<Vec<u8>> {
+        match msg::read_msg(self, max_len).await? {
+            Some(msg) => Ok(msg),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    /// Read exactly `expected.len()` bytes and check they are `expected`,
+    /// e.g. a magic number and protocol version at the start of the stream.
+    ///
+    /// Fails with `ErrorKind::InvalidData` on mismatch, or
+    /// `ErrorKind::UnexpectedEof` if the stream ends before. Nothing past
+    /// the header is consumed.
+    pub async fn read_magic(&mut self, expected: &[u8]) -> io::Result<()> {
+        let mut actual = vec![0; expected.len()];
+        io::AsyncReadExt::read_exact(self, &mut actual).await?;
+        if actual != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("magic {:02x?} expected, got {:02x?}", expected, actual),
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl io::AsyncRead for AnonPipeRead {
@@ -196,12 +968,28 @@ impl io::AsyncRead for AnonPipeRead {
     }
 }
 
+/// Adopt a pipe handle as the read end, registered with the reactor as a
+/// client.
+///
+/// Fails with `ErrorKind::InvalidInput` if the handle is not a pipe
+/// (`GetFileType`) or was not opened for overlapped I/O, closing it. Must
+/// be called within a tokio runtime.
+impl TryFrom<OwnedHandle> for AnonPipeRead {
+    type Error = io::Error;
+
+    fn try_from(handle: OwnedHandle) -> io::Result<Self> {
+        let read = Self::Client(adopt(handle)?);
+        Ok(audit::track(read, None))
+    }
+}
+
 impl IntoRawHandle for AnonPipeRead {
     fn into_raw_handle(self) -> RawHandle {
         let h = match &self {
             Self::Server(inner) => inner.as_raw_handle(),
             Self::Client(inner) => inner.as_raw_handle(),
         };
+        audit::untrack(&self);
         mem::forget(self);
         h
     }
@@ -217,6 +1005,27 @@ impl AsRawHandle for AnonPipeRead {
 }
 
 /// Asyncronous Pipe Write.
+///
+/// Bound to the runtime it was created in, and yielding once the budget of
+/// the task is spent, see [`AnonPipeRead`](AnonPipeRead#runtime).
+///
+/// # Closing
+///
+/// Dropping does **not** flush and reports no error. Finish with
+/// [`close().await`](Self::close), which waits for in-flight writes and
+/// fails if the reader did not accept them:
+///
+/// ```no_run
+/// use tokio::io::AsyncWriteExt;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let (_r, mut w) = tokio_anon_pipe::anon_pipe().await?;
+/// w.write_all(b"last words").await?;
+/// w.close().await?;
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug)]
 pub enum AnonPipeWrite {
     Server(NamedPipeServer),
@@ -226,208 +1035,3102 @@ pub enum AnonPipeWrite {
 impl AnonPipeWrite {
     async fn connect(&self) -> io::Result<()> {
         match self {
-            Self::Server(inner) => inner.connect().await?,
+            Self::Server(inner) => connect_server(inner, Deadline::default()).await?,
             _ => panic!("not a server"),
         }
         Ok(())
     }
-}
 
-impl io::AsyncWrite for AnonPipeWrite {
-    fn poll_write(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<Result<usize, io::Error>> {
-        match self.get_mut() {
-            Self::Server(ref mut inner) => Pin::new(inner).poll_write(cx, buf),
-            Self::Client(ref mut inner) => Pin::new(inner).poll_write(cx, buf),
+    /// Terminal Services session id of the peer process.
+    ///
+    /// See [`AnonPipeBuilder::cross_session`].
+    pub fn peer_session_id(&self) -> io::Result<u32> {
+        peer_session_id(self.as_raw_handle(), matches!(self, Self::Server(..)))
+    }
+
+    /// Buffer sizes and mode of the pipe.
+    pub fn pipe_info(&self) -> io::Result<PipeInfo> {
+        PipeInfo::query(self.as_raw_handle())
+    }
+
+    /// Maximum number of instances of the pipe name, `255` meaning
+    /// unlimited.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` for a client end.
+    pub fn max_instances(&self) -> io::Result<u32> {
+        max_instances(self.as_raw_handle(), matches!(self, Self::Server(..)))
+    }
+
+    /// Number of instances of the pipe name currently open, including the
+    /// ones still waiting for a client.
+    ///
+    /// Instances come and go concurrently, the count may be stale as soon
+    /// as it is returned. Fails with `ErrorKind::InvalidInput` for a client
+    /// end.
+    pub fn current_instances(&self) -> io::Result<u32> {
+        current_instances(self.as_raw_handle(), matches!(self, Self::Server(..)))
+    }
+
+    /// `false` if the handle was closed behind this end, e.g. through a
+    /// copy of the raw handle.
+    ///
+    /// Only tells the handle value is open, it may have been reused by an
+    /// unrelated object in the meantime.
+    pub fn is_valid(&self) -> bool {
+        is_valid(self.as_raw_handle())
+    }
+
+    /// `true` if the reader is attached, without writing data.
+    ///
+    /// `false` before a client connected to a server end, after the reader
+    /// closed, or if the handle is no longer valid. The answer may be stale
+    /// as soon as it is returned: the reader can close right after.
+    ///
+    /// Checked with a zero-byte `PeekNamedPipe`. A write-only client end
+    /// lacks the access for it and is probed with a zero-byte write
+    /// instead (see [`with_keepalive`](Self::with_keepalive) for the
+    /// message mode caveat).
+    pub fn is_connected(&self) -> io::Result<bool> {
+        // https://www.rpi.edu/dept/cis/software/g77-mingw32/include/winerror.h
+        const ERROR_ACCESS_DENIED: i32 = 5;
+
+        match peek_connected(self.as_raw_handle()) {
+            Err(err) if err.raw_os_error() == Some(ERROR_ACCESS_DENIED) => {
+                match self.try_write(&[]) {
+                    Ok(..) => Ok(true),
+                    // an earlier write is in flight.
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(true),
+                    Err(err) if is_disconnected(&err) => Ok(false),
+                    Err(err) => Err(err),
+                }
+            }
+            result => result,
         }
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
-        match self.get_mut() {
-            Self::Server(ref mut inner) => Pin::new(inner).poll_flush(cx),
-            Self::Client(ref mut inner) => Pin::new(inner).poll_flush(cx),
+    /// Poll until a write would not block, i.e. no earlier write is in
+    /// flight.
+    ///
+    /// Readiness does not tell how much fits into the pipe, see
+    /// [`writable_n`](Self::writable_n).
+    ///
+    /// Cancel safe: a future giving up after `Poll::Pending` loses nothing.
+    /// Readiness may be stale, so pair it with [`try_write`](Self::try_write)
+    /// and poll again on `ErrorKind::WouldBlock`.
+    pub fn poll_write_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self {
+            Self::Server(inner) => inner.poll_write_ready(cx),
+            Self::Client(inner) => inner.poll_write_ready(cx),
         }
     }
 
-    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
-        match self.get_mut() {
-            Self::Server(ref mut inner) => Pin::new(inner).poll_shutdown(cx),
-            Self::Client(ref mut inner) => Pin::new(inner).poll_shutdown(cx),
+    /// Write from `buf` without waiting, for futures written by hand.
+    ///
+    /// Fails with `ErrorKind::WouldBlock` while an earlier write is in
+    /// flight, which clears the readiness: poll
+    /// [`poll_write_ready`](Self::poll_write_ready) again to be woken. The
+    /// bytes accepted are written, whatever happens to the future then.
+    pub fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Server(inner) => inner.try_write(buf),
+            Self::Client(inner) => inner.try_write(buf),
         }
     }
-}
 
-impl IntoRawHandle for AnonPipeWrite {
-    fn into_raw_handle(self) -> RawHandle {
-        let h = match &self {
-            Self::Server(inner) => inner.as_raw_handle(),
-            Self::Client(inner) => inner.as_raw_handle(),
-        };
-        mem::forget(self);
-        h
+    /// Wait until a write of `n` bytes fits into the pipe buffer without
+    /// blocking.
+    ///
+    /// Waits for write readiness, then checks the write quota available
+    /// (`FilePipeLocalInformation`), which accounts for both the buffer size
+    /// and the bytes buffered unread. The pipe does not notify freed space,
+    /// so the quota is polled with a backoff of up to
+    /// [`CLOSED_POLL_INTERVAL`]. Fails with `ErrorKind::BrokenPipe` if the
+    /// reader is gone.
+    ///
+    /// The quota is an estimate: a read the reader has pending counts as
+    /// space, and other writers may use the space first. `n` larger than
+    /// the buffer may never fit.
+    ///
+    /// Must be called within a tokio runtime with the time driver enabled.
+    pub async fn writable_n(&self, n: usize) -> io::Result<()> {
+        let mut backoff = std::time::Duration::from_millis(1);
+        loop {
+            std::future::poll_fn(|cx| self.poll_write_ready(cx)).await?;
+            let available = info::write_quota_available(self.as_raw_handle())?;
+            if available as usize >= n {
+                return Ok(());
+            }
+            match self.try_write(&[]) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+                Ok(..) => {}
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(CLOSED_POLL_INTERVAL);
+        }
     }
-}
 
-impl AsRawHandle for AnonPipeWrite {
-    fn as_raw_handle(&self) -> RawHandle {
-        match self {
-            Self::Server(inner) => inner.as_raw_handle(),
-            Self::Client(inner) => inner.as_raw_handle(),
+    /// Wait until writes would fail because the reader closed the pipe.
+    ///
+    /// The reader does not notify the write end, so the peer is probed with
+    /// a zero-byte write every [`CLOSED_POLL_INTERVAL`] (see
+    /// [`with_keepalive`](Self::with_keepalive) for the message mode
+    /// caveat).
+    ///
+    /// Must be called within a tokio runtime with the time driver enabled.
+    pub async fn closed(&self) {
+        loop {
+            if std::future::poll_fn(|cx| self.poll_write_ready(cx))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            match self.try_write(&[]) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(..) => return,
+                Ok(..) => {}
+            }
+            tokio::time::sleep(CLOSED_POLL_INTERVAL).await;
         }
     }
-}
 
-/// Represents connectability.
-#[derive(Debug)]
-pub struct Connect<T>(T);
+    /// Probe the peer with a zero-byte write every `interval`.
+    ///
+    /// Must be called within a tokio runtime, the probe runs on a spawned
+    /// task until the returned [`KeepAlive`] is dropped. When a probe fails
+    /// (the peer is gone), [`KeepAlive::is_alive`] turns `false` and every
+    /// subsequent write fails with `ErrorKind::BrokenPipe`.
+    ///
+    /// The pipes are created in byte mode, where a zero-byte write carries
+    /// no data. In message mode (set through
+    /// [`AnonPipeBuilder::configure_server`]), each probe is delivered as an
+    /// empty message which the reader observes as a zero length read, i.e.
+    /// EOF. Do not use keepalive with message mode.
+    pub fn with_keepalive(self, interval: std::time::Duration) -> KeepAlive {
+        KeepAlive::new(self, interval)
+    }
 
-impl Connect<AnonPipeRead> {
-    /// Connect to pair.
-    pub async fn connect(self) -> io::Result<AnonPipeRead> {
-        self.0.connect().await?;
-        Ok(self.0)
+    /// Fail pending and subsequent writes once `token` is cancelled.
+    ///
+    /// See [`Cancellable`].
+    #[cfg(feature = "tokio-util")]
+    pub fn with_cancellation(
+        self,
+        token: tokio_util::sync::CancellationToken,
+    ) -> Cancellable<Self> {
+        Cancellable::new(self, token)
     }
-}
+
+    /// Submit at most `max_chunk` bytes per write, e.g. the out buffer size
+    /// reported by `pipe_info`.
+    ///
+    /// Writes are not chunked by default. See [`ChunkedWrite`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_chunk` is `0`.
+    pub fn chunked_writes(self, max_chunk: usize) -> ChunkedWrite {
+        ChunkedWrite::new(self, max_chunk)
+    }
+
+    /// Close after the reader has read everything written so far.
+    ///
+    /// Waits for in-flight writes, then `FlushFileBuffers` on a blocking
+    /// thread, which returns once the reader drained the pipe. Fails with
+    /// `ErrorKind::BrokenPipe` if the reader closed without draining.
+    /// The handle is closed even if the returned future is dropped early.
+    pub async fn close_graceful(self) -> io::Result<()> {
+        std::future::poll_fn(|cx| self.poll_write_ready(cx)).await?;
+
+        let task = tokio::task::spawn_blocking(move || {
+            let ok = unsafe { sys::FlushFileBuffers(self.as_raw_handle() as _) };
+            let result = if ok == 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            };
+            drop(self);
+            result
+        });
+        task.await?
+    }
+
+    /// Shut down, then wait until the reader has read everything written.
+    ///
+    /// Like [`close_graceful`](Self::close_graceful), but bounded by
+    /// `timeout`. Returns `Ok(())` once the pipe is drained, fails with
+    /// `ErrorKind::BrokenPipe` if the reader closed without draining, and
+    /// with `ErrorKind::TimedOut` if the reader neither drained nor closed
+    /// in time. In that case the blocking `FlushFileBuffers` is cancelled.
+    /// The handle is closed in any case.
+    ///
+    /// Must be called within a tokio runtime with the time driver enabled
+    /// if `timeout` is given.
+    pub async fn shutdown_and_wait(
+        mut self,
+        timeout: Option<std::time::Duration>,
+    ) -> io::Result<()> {
+        io::AsyncWriteExt::shutdown(&mut self).await?;
+        std::future::poll_fn(|cx| self.poll_write_ready(cx)).await?;
+
+        // Id of the thread blocked in FlushFileBuffers, cleared once it
+        // returns, so that no unrelated I/O of a reused thread is cancelled.
+        let flushing = Arc::new(std::sync::Mutex::new(None));
+        let mut task = {
+            let flushing = flushing.clone();
+            tokio::task::spawn_blocking(move || {
+                *flushing.lock().unwrap() = Some(unsafe { sys::GetCurrentThreadId() });
+                let ok = unsafe { sys::FlushFileBuffers(self.as_raw_handle() as _) };
+                let result = if ok == 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(())
+                };
+                *flushing.lock().unwrap() = None;
+                drop(self);
+                result
+            })
+        };
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return task.await?,
+        };
+        if let Ok(result) = tokio::time::timeout(timeout, &mut task).await {
+            return result?;
+        }
+
+        // https://www.rpi.edu/dept/cis/software/g77-mingw32/include/winerror.h
+        const ERROR_OPERATION_ABORTED: i32 = 995;
+
+        // The thread may not have entered FlushFileBuffers yet, cancel
+        // until it returns.
+        loop {
+            if let Some(tid) = *flushing.lock().unwrap() {
+                unsafe {
+                    let thread = sys::OpenThread(sys::THREAD_TERMINATE, 0, tid);
+                    if !thread.is_null() {
+                        sys::CancelSynchronousIo(thread);
+                        sys::CloseHandle(thread);
+                    }
+                }
+            }
+            let wait = std::time::Duration::from_millis(10);
+            if let Ok(result) = tokio::time::timeout(wait, &mut task).await {
+                return match result? {
+                    Err(err) if err.raw_os_error() == Some(ERROR_OPERATION_ABORTED) => Err(
+                        io::Error::new(io::ErrorKind::TimedOut, "reader did not drain the pipe"),
+                    ),
+                    result => result,
+                };
+            }
+        }
+    }
+
+    /// Close immediately. Same as dropping.
+    ///
+    /// Data the reader has not read yet may be lost. Use
+    /// [`close_graceful`](Self::close_graceful) to wait for the reader.
+    pub fn close_forceful(self) {
+        drop(self)
+    }
+
+    /// Close, reporting the error of writes the reader has not accepted.
+    ///
+    /// Waits for in-flight writes, then checks the pipe with a zero-byte
+    /// write (see [`with_keepalive`](Self::with_keepalive) for the message
+    /// mode caveat), shuts down and closes the handle. Fails with
+    /// `ErrorKind::BrokenPipe` if the reader is gone. Unlike
+    /// [`close_graceful`](Self::close_graceful), does not wait for the
+    /// reader to drain the pipe. The handle is closed in any case.
+    pub async fn close(mut self) -> io::Result<()> {
+        loop {
+            std::future::poll_fn(|cx| self.poll_write_ready(cx)).await?;
+            match self.try_write(&[]) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(err) => return Err(err),
+                Ok(..) => break,
+            }
+        }
+        io::AsyncWriteExt::shutdown(&mut self).await?;
+        io::AsyncWriteExt::flush(&mut self).await
+    }
+
+    /// Send `msg` as one message of a message mode pipe, and wait until it
+    /// was written.
+    ///
+    /// The message mode is set through
+    /// [`AnonPipeBuilder::configure_server`]. `msg` is submitted with a
+    /// single write, which message mode delivers as one unit, so a reader
+    /// never observes it split or merged with the next one. Fails if the
+    /// write accepted only part of `msg`, or if the reader is gone.
+    ///
+    /// In byte mode, use [`send_msg`](Self::send_msg) instead.
+    pub async fn send_message(&mut self, msg: &[u8]) -> io::Result<()> {
+        let n = io::AsyncWriteExt::write(self, msg).await?;
+        if n != msg.len() {
+            return Err(io::Error::other(format!(
+                "message split after {} of {} bytes",
+                n,
+                msg.len()
+            )));
+        }
+        // completion of the write reports its error, if any.
+        std::future::poll_fn(|cx| self.poll_write_ready(cx)).await?;
+        io::AsyncWriteExt::flush(self).await
+    }
+
+    /// Send a message as 4-byte little-endian length, then the payload.
+    ///
+    /// Receive it with [`AnonPipeRead::recv_msg`].
+    pub async fn send_msg(&mut self, msg: &[u8]) -> io::Result<()> {
+        let header = msg::header(msg)?;
+        self.write_all_chunks(&[&header, msg]).await
+    }
+
+    /// Write all `chunks` in order with vectored writes.
+    ///
+    /// Saves concatenating the chunks into one buffer first.
+    pub async fn write_all_chunks(&mut self, chunks: &[&[u8]]) -> io::Result<()> {
+        use io::AsyncWriteExt;
+
+        let mut chunks = chunks
+            .iter()
+            .filter(|chunk| !chunk.is_empty())
+            .copied()
+            .collect::<Vec<_>>();
+        let mut head = 0;
+        while head < chunks.len() {
+            let slices = chunks[head..]
+                .iter()
+                .map(|chunk| std::io::IoSlice::new(chunk))
+                .collect::<Vec<_>>();
+            let mut n = self.write_vectored(&slices).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+
+            // advance through the written slices.
+            while n > 0 {
+                let chunk = &mut chunks[head];
+                if n < chunk.len() {
+                    *chunk = &chunk[n..];
+                    break;
+                }
+                n -= chunk.len();
+                head += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl io::AsyncWrite for AnonPipeWrite {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        match self.get_mut() {
+            Self::Server(ref mut inner) => Pin::new(inner).poll_write(cx, buf),
+            Self::Client(ref mut inner) => Pin::new(inner).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        match self.get_mut() {
+            Self::Server(ref mut inner) => Pin::new(inner).poll_write_vectored(cx, bufs),
+            Self::Client(ref mut inner) => Pin::new(inner).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Self::Server(inner) => inner.is_write_vectored(),
+            Self::Client(inner) => inner.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        match self.get_mut() {
+            Self::Server(ref mut inner) => Pin::new(inner).poll_flush(cx),
+            Self::Client(ref mut inner) => Pin::new(inner).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        match self.get_mut() {
+            Self::Server(ref mut inner) => Pin::new(inner).poll_shutdown(cx),
+            Self::Client(ref mut inner) => Pin::new(inner).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Adopt a pipe handle as the write end, registered with the reactor as a
+/// client.
+///
+/// Fails with `ErrorKind::InvalidInput` if the handle is not a pipe
+/// (`GetFileType`) or was not opened for overlapped I/O, closing it. Must
+/// be called within a tokio runtime.
+impl TryFrom<OwnedHandle> for AnonPipeWrite {
+    type Error = io::Error;
+
+    fn try_from(handle: OwnedHandle) -> io::Result<Self> {
+        let write = Self::Client(adopt(handle)?);
+        Ok(audit::track(write, None))
+    }
+}
+
+impl IntoRawHandle for AnonPipeWrite {
+    fn into_raw_handle(self) -> RawHandle {
+        let h = match &self {
+            Self::Server(inner) => inner.as_raw_handle(),
+            Self::Client(inner) => inner.as_raw_handle(),
+        };
+        audit::untrack(&self);
+        mem::forget(self);
+        h
+    }
+}
+
+impl AsRawHandle for AnonPipeWrite {
+    fn as_raw_handle(&self) -> RawHandle {
+        match self {
+            Self::Server(inner) => inner.as_raw_handle(),
+            Self::Client(inner) => inner.as_raw_handle(),
+        }
+    }
+}
+
+/// Represents connectability.
+///
+/// The pipe is created and opened synchronously, only waiting for the peer
+/// is left for [`connect`](Connect::connect). So creation can happen on a
+/// blocking thread, and the connect on the async side. Tokio pipes are
+/// registered with the reactor on creation, so the creating thread must be
+/// within a runtime context: a `spawn_blocking` thread, or a thread after
+/// `Handle::enter`.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// let rt = tokio::runtime::Builder::new_current_thread()
+///     .enable_io()
+///     .build()?;
+///
+/// let (r, w) = std::thread::spawn({
+///     let handle = rt.handle().clone();
+///     move || {
+///         let _guard = handle.enter();
+///         tokio_anon_pipe::anon_pipe_deferred()
+///     }
+/// })
+/// .join()
+/// .unwrap()?;
+///
+/// let r = rt.block_on(r.connect())?;
+/// # drop((r, w));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Connect<T>(T);
+
+impl<T> Connect<T> {
+    /// Gets a reference to the end, not connected yet.
+    pub fn get_ref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl Connect<AnonPipeRead> {
+    /// Connect to pair.
+    pub async fn connect(self) -> io::Result<AnonPipeRead> {
+        self.0.connect().await.map_err(Error::Connect)?;
+        Ok(self.0)
+    }
+}
 
 impl Connect<AnonPipeWrite> {
     /// Connect to pair.
     pub async fn connect(self) -> io::Result<AnonPipeWrite> {
-        self.0.connect().await?;
+        self.0.connect().await.map_err(Error::Connect)?;
+        Ok(self.0)
+    }
+}
+
+impl Connect<AnonPipeDuplex> {
+    /// Wait for a client.
+    pub async fn connect(self) -> io::Result<AnonPipeDuplex> {
+        if let AnonPipeDuplex::Server(server) = &self.0 {
+            connect_server(server, Deadline::default())
+                .await
+                .map_err(Error::Connect)?;
+        }
         Ok(self.0)
     }
-}
+}
+
+type ConfigureServer = Arc<dyn Fn(&mut ServerOptions) + Send + Sync>;
+type ConfigureClient = Arc<dyn Fn(&mut ClientOptions) + Send + Sync>;
+type OnRetry = Arc<dyn Fn(RetryEvent<'_>) + Send + Sync>;
+
+/// Failed attempt of creating the server end, which is retried.
+///
+/// See [`AnonPipeBuilder::on_retry`].
+#[derive(Debug)]
+pub struct RetryEvent<'a> {
+    attempt: usize,
+    error: &'a io::Error,
+    reject_remote_clients_toggled: bool,
+}
+
+impl RetryEvent<'_> {
+    /// Number of the failed attempt, starting at `1`.
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /// Error of the failed attempt.
+    pub fn error(&self) -> &io::Error {
+        self.error
+    }
+
+    /// `true` if the attempt failed because `reject_remote_clients` is not
+    /// supported, and the retry is made without it.
+    /// Such a retry does not count as an attempt.
+    pub fn reject_remote_clients_toggled(&self) -> bool {
+        self.reject_remote_clients_toggled
+    }
+}
+
+/// Anonymous Pipe Pair builder.
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let (r, w) = tokio_anon_pipe::AnonPipeBuilder::new()
+///     .configure_server(|opts| {
+///         opts.in_buffer_size(65536);
+///     })
+///     .anon_pipe()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct AnonPipeBuilder {
+    max_instances: Option<usize>,
+    security: Security,
+    configure_server: Option<ConfigureServer>,
+    configure_client: Option<ConfigureClient>,
+    on_retry: Option<OnRetry>,
+    max_tries: Option<usize>,
+    buffer_size_fallbacks: Vec<u32>,
+    security_attributes: Option<RawSecurityAttributes>,
+    runtime: Option<tokio::runtime::Handle>,
+    creation_timeout: Option<std::time::Duration>,
+    default_client_wait: Option<std::time::Duration>,
+    write_through: bool,
+    unpredictable_names: bool,
+}
+
+impl fmt::Debug for AnonPipeBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnonPipeBuilder")
+            .field("max_instances", &self.max_instances)
+            .field("security", &self.security)
+            .field("configure_server", &self.configure_server.is_some())
+            .field("configure_client", &self.configure_client.is_some())
+            .field("on_retry", &self.on_retry.is_some())
+            .field("max_tries", &self.max_tries)
+            .field("buffer_size_fallbacks", &self.buffer_size_fallbacks)
+            .field("security_attributes", &self.security_attributes)
+            .field("runtime", &self.runtime)
+            .field("creation_timeout", &self.creation_timeout)
+            .field("default_client_wait", &self.default_client_wait)
+            .field("write_through", &self.write_through)
+            .field("unpredictable_names", &self.unpredictable_names)
+            .finish()
+    }
+}
+
+impl AnonPipeBuilder {
+    /// Create new builder with default options.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Maximum number of instances sharing the pipe name. Default is `1`.
+    ///
+    /// Only meaningful with [`server`](Self::server), where every accepted
+    /// client occupies an instance.
+    pub fn max_instances(&mut self, instances: usize) -> &mut Self {
+        self.max_instances = Some(instances);
+        self
+    }
+
+    /// Grant read/write access to `sid`.
+    ///
+    /// `sid` is a string SID (`S-1-5-21-...`) or a SDDL alias (`IU`).
+    /// The server end is then created with a DACL granting full access to
+    /// LocalSystem, Administrators and the owner, plus read/write access to
+    /// each allowed sid. Everyone and Anonymous get no access, unlike the
+    /// default named pipe DACL.
+    pub fn allow_sid(&mut self, sid: impl Into<String>) -> &mut Self {
+        self.security.allow_sid(sid.into());
+        self
+    }
+
+    /// Preset for a service in session 0 exchanging the pipe with a helper
+    /// in the interactive user's session.
+    ///
+    /// Grants read/write access to the interactive user (`IU`).
+    /// Use [`allow_sid`](Self::allow_sid) to grant a specific user instead.
+    ///
+    /// Remote clients are still rejected, both ends are on the same machine.
+    /// Validate which session actually connected with `peer_session_id`.
+    pub fn cross_session(&mut self) -> &mut Self {
+        self.allow_sid("IU")
+    }
+
+    /// Let medium integrity clients connect to a pipe created by an elevated
+    /// (high integrity) process, e.g. an elevated updater talking to a
+    /// non-elevated UI.
+    ///
+    /// Attaches a medium mandatory label (no write up) to the server end and
+    /// grants read/write access to the interactive user.
+    ///
+    /// # Security
+    ///
+    /// Any medium integrity process of the interactive user, not only the
+    /// intended one, may then open the pipe by name while it is waiting for
+    /// its client. Treat everything read from such a pipe as untrusted input.
+    pub fn medium_integrity_clients(&mut self, enable: bool) -> &mut Self {
+        self.security.medium_integrity(enable);
+        self
+    }
+
+    /// Create the server end with caller supplied `SECURITY_ATTRIBUTES`,
+    /// passed to `ServerOptions::create_with_security_attributes_raw` as is.
+    ///
+    /// The inherit flag of the attributes is honored, the handle is not made
+    /// non-inheritable as usual. Creation fails with
+    /// `ErrorKind::InvalidInput` if [`allow_sid`](Self::allow_sid),
+    /// [`cross_session`](Self::cross_session) or
+    /// [`medium_integrity_clients`](Self::medium_integrity_clients) is set
+    /// as well.
+    ///
+    /// # Safety
+    ///
+    /// `attrs` must point to a valid `SECURITY_ATTRIBUTES` (and the security
+    /// descriptor it refers to) whenever a server end is created, i.e. as
+    /// long as this builder, its clones, or an [`AnonPipeServer`] created
+    /// from it is in use.
+    pub unsafe fn security_attributes_raw(&mut self, attrs: *mut c_void) -> &mut Self {
+        self.security_attributes = Some(RawSecurityAttributes(attrs));
+        self
+    }
+
+    /// Register the ends with the reactor of `runtime`, instead of the
+    /// runtime the creating thread is in.
+    ///
+    /// Both ends are opened with `FILE_FLAG_OVERLAPPED` (see
+    /// `PipeInfo::is_overlapped`) and associated with the I/O completion
+    /// port of the reactor. Completions are dequeued by whichever thread
+    /// drives that reactor, there is no per-pipe thread affinity. Pinning
+    /// the pipes to a runtime whose reactor runs on a known thread (e.g. a
+    /// current-thread runtime) is the way to get predictable completion
+    /// handling. The ends must then be used within that runtime.
+    pub fn runtime(&mut self, runtime: tokio::runtime::Handle) -> &mut Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Tweak `ServerOptions` before the server end is created.
+    ///
+    /// The crate applies its defaults first, then runs the callback.
+    /// The callback may be called multiple times, once per creation attempt.
+    ///
+    /// The access direction, `first_pipe_instance` and `max_instances` are
+    /// required by the crate and applied again after the callback, so
+    /// changing them has no effect. Use [`max_instances`](Self::max_instances)
+    /// for the latter. Everything else (e.g. buffer sizes, pipe mode) is up to
+    /// the callback, check the result with `pipe_info`.
+    pub fn configure_server<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&mut ServerOptions) + Send + Sync + 'static,
+    {
+        self.configure_server = Some(Arc::new(f));
+        self
+    }
+
+    /// Tweak `ClientOptions` before the client end is opened.
+    ///
+    /// The crate applies its defaults first, then runs the callback.
+    /// The access direction is applied again after the callback.
+    pub fn configure_client<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&mut ClientOptions) + Send + Sync + 'static,
+    {
+        self.configure_client = Some(Arc::new(f));
+        self
+    }
+
+    /// Observe retried attempts of creating the server end.
+    ///
+    /// The callback is called before each retry, e.g. when the generated
+    /// name collided. Default is no-op.
+    pub fn on_retry<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(RetryEvent<'_>) + Send + Sync + 'static,
+    {
+        self.on_retry = Some(Arc::new(f));
+        self
+    }
+
+    /// Maximum number of attempts of creating the server end. Default is
+    /// `10`.
+    ///
+    /// Each attempt uses a fresh name. With `1`, the first failure is
+    /// returned as is, classified as [`Error`]. The retry without
+    /// `reject_remote_clients` on systems not supporting it does not count
+    /// as an attempt, see [`RetryEvent::reject_remote_clients_toggled`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tries` is `0`.
+    pub fn max_tries(&mut self, tries: usize) -> &mut Self {
+        assert!(tries > 0, "max_tries must be positive");
+        self.max_tries = Some(tries);
+        self
+    }
+
+    /// Bound the whole creation of a pair (or server, listener) by
+    /// `timeout`, including the retries of every stage.
+    ///
+    /// Once the deadline passes, creation fails with `ErrorKind::TimedOut`
+    /// classified as [`Error::TimedOut`], reporting the stage in progress.
+    /// The ends created so far are closed. A retry whose backoff would end
+    /// after the deadline is not attempted. Default is no timeout.
+    pub fn creation_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+        self.creation_timeout = Some(timeout);
+        self
+    }
+
+    fn deadline(&self) -> Deadline {
+        Deadline(
+            self.creation_timeout
+                .map(|timeout| std::time::Instant::now() + timeout),
+        )
+    }
+
+    /// Bound of [`connect_by_name`](Self::connect_by_name) waiting for an
+    /// instance while all of them are busy.
+    ///
+    /// The wait is done with `WaitNamedPipe`, so the connect succeeds as
+    /// soon as an instance is listening again, or fails once `wait` has
+    /// passed. Default is the timeout of the pipe, which tokio creates with
+    /// the system default of 50 ms, for up to 10 waits.
+    pub fn default_client_wait(&mut self, wait: std::time::Duration) -> &mut Self {
+        self.default_client_wait = Some(wait);
+        self
+    }
+
+    /// Open both ends with `FILE_FLAG_WRITE_THROUGH`, including the client
+    /// end opened by [`connect_by_name`](Self::connect_by_name). Default is
+    /// `false`.
+    ///
+    /// Writes then complete only once the data was transmitted. The
+    /// tradeoff is latency against throughput: without the flag, the client
+    /// of a pipe reached over the network collects small writes into one
+    /// transmission, delaying each; with it, every write is sent at once,
+    /// costing a round trip per write. The local pipes created here buffer
+    /// nothing on the client side, so the main observable effect is on
+    /// performance. Some security tooling expects the flag. Reported by
+    /// [`PipeInfo::is_write_through`].
+    pub fn write_through(&mut self, enable: bool) -> &mut Self {
+        self.write_through = enable;
+        self
+    }
+
+    /// Generate the pipe names from 128 bits of CSPRNG output only.
+    /// Default is `false`.
+    ///
+    /// The default names carry the pid, thread id and a counter, which help
+    /// debugging but let a local observer tell which process a pipe belongs
+    /// to and guess the names it creates next. With this flag, the name is
+    /// the crate marker followed by 32 hex digits, and creation fails
+    /// rather than falling back to a predictable source. A name taken
+    /// over in advance is still detected as a collision, as the server is
+    /// always created as the first instance. [`parse_pipe_name`] does not
+    /// recognize these names.
+    pub fn unpredictable_names(&mut self, enable: bool) -> &mut Self {
+        self.unpredictable_names = enable;
+        self
+    }
+
+    /// Fail on the first failed attempt of creating the server end.
+    ///
+    /// Same as `max_tries(1)`.
+    pub fn no_retry(&mut self) -> &mut Self {
+        self.max_tries(1)
+    }
+
+    /// Buffer sizes of the server end to try in order.
+    ///
+    /// Each size is applied to both the input and output buffer, after
+    /// [`configure_server`](Self::configure_server). When creation fails for
+    /// lack of system resources or quota, as on some sandboxed systems with
+    /// large buffers, the next size is tried. Other errors are not retried.
+    /// Default is empty, creating once with the configured sizes.
+    ///
+    /// The size used is reported by `pipe_info`.
+    pub fn buffer_size_fallbacks(&mut self, sizes: &[u32]) -> &mut Self {
+        self.buffer_size_fallbacks = sizes.to_vec();
+        self
+    }
+
+    /// Create server accepting sequential clients on one name.
+    pub fn server(&self) -> io::Result<AnonPipeServer> {
+        AnonPipeServer::new(self)
+    }
+
+    /// Create listener accepting concurrent clients on one name.
+    pub fn listener(&self) -> io::Result<AnonPipeListener> {
+        AnonPipeListener::new(self)
+    }
+
+    /// Connect to `name` of an [`AnonPipeListener`] (or any duplex pipe).
+    ///
+    /// Waits for an instance while all of them are busy, see
+    /// [`default_client_wait`](Self::default_client_wait). Fails with
+    /// `ErrorKind::InvalidInput` if `name` is not a valid pipe name (see
+    /// [`validate_pipe_name`]).
+    pub async fn connect_by_name(&self, name: &str) -> io::Result<AnonPipeDuplex> {
+        validate_pipe_name(name)?;
+        let deadline = self
+            .default_client_wait
+            .map(|wait| std::time::Instant::now() + wait);
+        let mut tries = 0;
+        loop {
+            tries += 1;
+            match open_client(self, name, Access::Duplex) {
+                Err(err) if errors::is_retryable_open(&err) => {
+                    let wait = match deadline {
+                        Some(deadline) => {
+                            match deadline.checked_duration_since(std::time::Instant::now()) {
+                                Some(wait) if !wait.is_zero() => Some(wait),
+                                _ => return Err(Error::OpenClient(err).into()),
+                            }
+                        }
+                        None if tries < MAX_TRIES => None,
+                        None => return Err(Error::OpenClient(err).into()),
+                    };
+                    #[cfg(feature = "stats")]
+                    stats::incr(&stats::CLIENT_BUSY_RETRIES);
+                    match wait_named_pipe(name, wait).await {
+                        // raced with another client, or timed out.
+                        Err(err) if errors::is_retryable_open(&err) => {}
+                        Err(err) => return Err(Error::OpenClient(err).into()),
+                        Ok(()) => {}
+                    }
+                }
+                Err(err) => return Err(Error::OpenClient(err).into()),
+                Ok(client) => return Ok(audit::track(AnonPipeDuplex::Client(client), Some(name))),
+            }
+        }
+    }
+
+    /// Open duplex Anonynous Pipe Pair.
+    /// Pair is connected.
+    ///
+    /// Backs off on the runtime timer while the pipe is busy, so the
+    /// runtime must have the time driver enabled.
+    pub async fn anon_pipe_duplex_pair(&self) -> io::Result<(AnonPipeDuplex, AnonPipeDuplex)> {
+        let deadline = self.deadline();
+        let (name, server) = try_new_server_async(self, Access::Duplex, deadline).await?;
+        let client = new_client_async(self, &name, Access::Duplex, deadline).await?;
+
+        connect_server(&server, deadline)
+            .await
+            .map_err(|err| error::classify(err, Error::Connect))?;
+
+        let server = audit::track(AnonPipeDuplex::Server(server), Some(&name));
+        let client = audit::track(AnonPipeDuplex::Client(client), Some(&name));
+        Ok((server, client))
+    }
+
+    /// Open Anonynous Pipe Pair.
+    /// Pair is connected.
+    ///
+    /// Backs off on the runtime timer while the pipe is busy, so the
+    /// runtime must have the time driver enabled.
+    pub async fn anon_pipe(&self) -> io::Result<(AnonPipeRead, AnonPipeWrite)> {
+        let deadline = self.deadline();
+        let (name, server) = try_new_server_async(self, Access::Inbound, deadline).await?;
+        let client = new_client_async(self, &name, Access::Inbound, deadline).await?;
+
+        connect_server(&server, deadline)
+            .await
+            .map_err(|err| error::classify(err, Error::Connect))?;
+
+        let read = audit::track(AnonPipeRead::Server(server), Some(&name));
+        let write = audit::track(AnonPipeWrite::Client(client), Some(&name));
+        Ok((read, write))
+    }
+
+    /// Open Anonynous Pipe Pair.
+    /// Pair is not connected yet.
+    pub fn anon_pipe_we_read(&self) -> io::Result<(Connect<AnonPipeRead>, AnonPipeWrite)> {
+        let deadline = self.deadline();
+        let (name, server) = try_new_server(self, Access::Inbound, deadline)?;
+        let client = new_client(self, &name, Access::Inbound, deadline)?;
+
+        let read = Connect(audit::track(AnonPipeRead::Server(server), Some(&name)));
+        let write = audit::track(AnonPipeWrite::Client(client), Some(&name));
+        Ok((read, write))
+    }
+
+    /// Open Anonynous Pipe Pair, counting the bytes through it.
+    /// Pair is connected.
+    ///
+    /// Both ends share one [`PipeStats`].
+    pub async fn anon_pipe_counted(
+        &self,
+    ) -> io::Result<(Counted<AnonPipeRead>, Counted<AnonPipeWrite>)> {
+        let (r, w) = self.anon_pipe().await?;
+        let stats = PipeStats::new();
+        Ok((
+            Counted::with_stats(r, stats.clone()),
+            Counted::with_stats(w, stats),
+        ))
+    }
+
+    /// Open Anonynous Pipe Pair, reading everything from the read half
+    /// on a spawned task.
+    ///
+    /// The task connects, reads to EOF and returns the data read.
+    /// Must be called within a tokio runtime.
+    pub fn anon_pipe_collecting(
+        &self,
+    ) -> io::Result<(AnonPipeWrite, tokio::task::JoinHandle<io::Result<Vec<u8>>>)> {
+        let (r, w) = self.anon_pipe_we_read()?;
+        let task = tokio::spawn(async move {
+            let mut r = r.connect().await?;
+            let mut buf = vec![];
+            io::AsyncReadExt::read_to_end(&mut r, &mut buf).await?;
+            Ok(buf)
+        });
+        Ok((w, task))
+    }
+
+    /// Open Anonynous Pipe Pair without any async work.
+    /// Pair is not connected yet.
+    ///
+    /// Same as [`anon_pipe_we_read`](Self::anon_pipe_we_read).
+    /// See [`Connect`] for creating outside the async context.
+    pub fn anon_pipe_deferred(&self) -> io::Result<(Connect<AnonPipeRead>, AnonPipeWrite)> {
+        self.anon_pipe_we_read()
+    }
+
+    /// Open Anonynous Pipe Pair
+    /// Pair is not connected yet.
+    pub fn anon_pipe_we_write(&self) -> io::Result<(AnonPipeRead, Connect<AnonPipeWrite>)> {
+        let deadline = self.deadline();
+        let (name, server) = try_new_server(self, Access::Outbound, deadline)?;
+        let client = new_client(self, &name, Access::Outbound, deadline)?;
+
+        let read = audit::track(AnonPipeRead::Client(client), Some(&name));
+        let write = Connect(audit::track(AnonPipeWrite::Server(server), Some(&name)));
+        Ok((read, write))
+    }
+}
+
+/// Data flow between the ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    /// client to server
+    Inbound,
+    /// server to client
+    Outbound,
+    /// both
+    Duplex,
+}
+
+impl Access {
+    fn inbound(self) -> bool {
+        self != Self::Outbound
+    }
+
+    fn outbound(self) -> bool {
+        self != Self::Inbound
+    }
+}
+
+fn new_server(
+    builder: &AnonPipeBuilder,
+    sd: Option<&SecurityDescriptor>,
+    name: &str,
+    first: bool,
+    reject_remote_clients: bool,
+    access: Access,
+) -> io::Result<NamedPipeServer> {
+    let _guard = builder.runtime.as_ref().map(|runtime| runtime.enter());
+    let max_instances = defaults::max_instances(builder.max_instances).unwrap_or(1);
+    let mut opts = ServerOptions::new();
+    opts.reject_remote_clients(reject_remote_clients);
+    if let Some(defaults) = defaults::global_defaults() {
+        defaults.apply(&mut opts);
+    }
+    if let Some(f) = &builder.configure_server {
+        f(&mut opts);
+    }
+    opts.access_inbound(access.inbound())
+        .access_outbound(access.outbound())
+        .first_pipe_instance(first)
+        .max_instances(max_instances);
+
+    let mut sizes = builder.buffer_size_fallbacks.iter();
+    loop {
+        if let Some(&size) = sizes.next() {
+            opts.in_buffer_size(size).out_buffer_size(size);
+        }
+        match create_server(builder, sd, &opts, name) {
+            Err(err) if sizes.len() > 0 && is_buffer_size_error(&err) => continue,
+            result => return result,
+        }
+    }
+}
+
+/// Create the server end with `opts` once.
+///
+/// The caller holds a creation permit, see [`limit`].
+fn create_server(
+    builder: &AnonPipeBuilder,
+    sd: Option<&SecurityDescriptor>,
+    opts: &ServerOptions,
+    name: &str,
+) -> io::Result<NamedPipeServer> {
+    let server = match (sd, builder.security_attributes) {
+        (Some(sd), _) => {
+            let mut attrs = sd.attributes();
+            unsafe {
+                opts.create_with_security_attributes_raw(name, &mut attrs as *mut _ as *mut c_void)?
+            }
+        }
+        (None, Some(attrs)) => unsafe { opts.create_with_security_attributes_raw(name, attrs.0)? },
+        (None, None) => opts.create(name)?,
+    };
+    // unless the caller chose whether to inherit.
+    if builder.security_attributes.is_none() {
+        no_inherit(server.as_raw_handle())?;
+    }
+    if builder.write_through {
+        info::set_write_through(server.as_raw_handle())?;
+    }
+    Ok(server)
+}
+
+/// Creation failed for the buffer size, a smaller one may succeed.
+fn is_buffer_size_error(err: &io::Error) -> bool {
+    // https://www.rpi.edu/dept/cis/software/g77-mingw32/include/winerror.h
+    const ERROR_NOT_ENOUGH_MEMORY: i32 = 8;
+    const ERROR_OUTOFMEMORY: i32 = 14;
+    const ERROR_NO_SYSTEM_RESOURCES: i32 = 1450;
+    const ERROR_NONPAGED_SYSTEM_RESOURCES: i32 = 1451;
+    const ERROR_PAGED_SYSTEM_RESOURCES: i32 = 1452;
+    const ERROR_NOT_ENOUGH_QUOTA: i32 = 1816;
+
+    matches!(
+        err.raw_os_error(),
+        Some(ERROR_NOT_ENOUGH_MEMORY)
+            | Some(ERROR_OUTOFMEMORY)
+            | Some(ERROR_NO_SYSTEM_RESOURCES)
+            | Some(ERROR_NONPAGED_SYSTEM_RESOURCES)
+            | Some(ERROR_PAGED_SYSTEM_RESOURCES)
+            | Some(ERROR_NOT_ENOUGH_QUOTA)
+    )
+}
+
+/// Open the client end, blocking the thread while backing off.
+///
+/// For the constructors which are not async, see [`new_client_async`].
+fn new_client(
+    builder: &AnonPipeBuilder,
+    name: &str,
+    access: Access,
+    deadline: Deadline,
+) -> io::Result<NamedPipeClient> {
+    deadline.check(CreationStage::OpenClient)?;
+    retry_open(deadline, || open_client(builder, name, access))
+        .map_err(|err| error::classify(err, Error::OpenClient))
+}
+
+/// Open the client end, yielding to the runtime while backing off.
+async fn new_client_async(
+    builder: &AnonPipeBuilder,
+    name: &str,
+    access: Access,
+    deadline: Deadline,
+) -> io::Result<NamedPipeClient> {
+    deadline.check(CreationStage::OpenClient)?;
+    retry_open_async(deadline, || open_client(builder, name, access))
+        .await
+        .map_err(|err| error::classify(err, Error::OpenClient))
+}
+
+/// Open the client end once.
+fn open_client(
+    builder: &AnonPipeBuilder,
+    name: &str,
+    access: Access,
+) -> io::Result<NamedPipeClient> {
+    let _guard = builder.runtime.as_ref().map(|runtime| runtime.enter());
+    let mut opts = ClientOptions::new();
+    if let Some(f) = &builder.configure_client {
+        f(&mut opts);
+    }
+    opts.read(access.outbound()).write(access.inbound());
+    let client = opts.open(name)?;
+    no_inherit(client.as_raw_handle())?;
+    if builder.write_through {
+        info::set_write_through(client.as_raw_handle())?;
+    }
+    Ok(client)
+}
+
+/// Wait until an instance of `name` is listening, up to `timeout`, or the
+/// timeout of the pipe if `None`.
+///
+/// `WaitNamedPipe` blocks, so runs on the blocking pool.
+async fn wait_named_pipe(name: &str, timeout: Option<std::time::Duration>) -> io::Result<()> {
+    const NMPWAIT_USE_DEFAULT_WAIT: u32 = 0;
+    const NMPWAIT_WAIT_FOREVER: u32 = 0xffff_ffff;
+
+    let timeout = match timeout {
+        // neither of the special values.
+        Some(timeout) => timeout
+            .as_millis()
+            .clamp(1, (NMPWAIT_WAIT_FOREVER - 1) as u128) as u32,
+        None => NMPWAIT_USE_DEFAULT_WAIT,
+    };
+    let wide = name.encode_utf16().chain(Some(0)).collect::<Vec<_>>();
+    tokio::task::spawn_blocking(move || {
+        let ok = unsafe { sys::WaitNamedPipeW(wide.as_ptr(), timeout) };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    })
+    .await?
+}
+
+/// Check `handle` is an overlapped pipe and register it as a client.
+fn adopt(handle: OwnedHandle) -> io::Result<NamedPipeClient> {
+    let file_type = unsafe { sys::GetFileType(handle.as_raw_handle() as _) };
+    if file_type != sys::FILE_TYPE_PIPE {
+        let err = io::Error::last_os_error();
+        // FILE_TYPE_UNKNOWN with an error set.
+        if file_type == 0 && err.raw_os_error() != Some(0) {
+            return Err(err);
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "handle is not a pipe",
+        ));
+    }
+    if !PipeInfo::query(handle.as_raw_handle())?.is_overlapped() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "pipe handle is not opened for overlapped I/O",
+        ));
+    }
+    // e.g. inherited from a parent which registered it. fails before
+    // Windows 8.1, registering then fails if the handle is still associated.
+    let _ = info::clear_completion_port(handle.as_raw_handle());
+    // the client owns the handle from here, closing it on failure.
+    unsafe { NamedPipeClient::from_raw_handle(handle.into_raw_handle()) }
+}
+
+/// Clear `HANDLE_FLAG_INHERIT`, so that children spawned with
+/// inheritance enabled (e.g. by `std::process::Command`) do not hold the
+/// handle and delay EOF of the peer.
+fn no_inherit(handle: RawHandle) -> io::Result<()> {
+    let ok = unsafe { sys::SetHandleInformation(handle as _, sys::HANDLE_FLAG_INHERIT, 0) };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+use errors::{ERROR_ACCESS_DENIED, ERROR_PIPE_LISTENING};
+
+/// Maximum number of attempts for creating the server / opening the client.
+const MAX_TRIES: usize = 10;
+
+/// Deadline of a whole creation, see [`AnonPipeBuilder::creation_timeout`].
+#[derive(Debug, Clone, Copy, Default)]
+struct Deadline(Option<std::time::Instant>);
+
+impl Deadline {
+    /// `true` if the deadline passes within `wait`.
+    fn expires_within(self, wait: std::time::Duration) -> bool {
+        self.0
+            .is_some_and(|deadline| std::time::Instant::now() + wait >= deadline)
+    }
+
+    /// Fail if the deadline passed before `stage` started.
+    fn check(self, stage: CreationStage) -> io::Result<()> {
+        if self.expires_within(std::time::Duration::ZERO) {
+            return Err(stage.timed_out(None));
+        }
+        Ok(())
+    }
+}
+
+/// Retry `open` with a short backoff while it fails transiently.
+///
+/// Sleeps the thread while backing off, see [`retry_open_async`] for the
+/// async paths.
+fn retry_open<T>(deadline: Deadline, mut open: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut tries = 0;
+    let mut backoff = std::time::Duration::from_millis(1);
+    loop {
+        tries += 1;
+        match open() {
+            Err(err) if tries < MAX_TRIES && errors::is_retryable_open(&err) => {
+                if deadline.expires_within(backoff) {
+                    return Err(CreationStage::OpenClient.timed_out(Some(err)));
+                }
+                #[cfg(feature = "stats")]
+                stats::incr(&stats::CLIENT_BUSY_RETRIES);
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Same as [`retry_open`], but sleeps on the runtime timer while backing
+/// off instead of parking the worker.
+async fn retry_open_async<T>(
+    deadline: Deadline,
+    mut open: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut tries = 0;
+    let mut backoff = std::time::Duration::from_millis(1);
+    loop {
+        tries += 1;
+        match open() {
+            Err(err) if tries < MAX_TRIES && errors::is_retryable_open(&err) => {
+                if deadline.expires_within(backoff) {
+                    return Err(CreationStage::OpenClient.timed_out(Some(err)));
+                }
+                #[cfg(feature = "stats")]
+                stats::incr(&stats::CLIENT_BUSY_RETRIES);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Wait for the client of `server`, which opened the pipe already.
+async fn connect_server(server: &NamedPipeServer, deadline: Deadline) -> io::Result<()> {
+    let connect = retry_connect(deadline, || server.connect());
+    match deadline.0 {
+        Some(at) => tokio::time::timeout_at(at.into(), connect)
+            .await
+            .unwrap_or_else(|_| Err(CreationStage::Connect.timed_out(None))),
+        None => connect.await,
+    }
+}
+
+/// Retry `connect` while the pipe is reported as still listening.
+///
+/// Wine may fail `ConnectNamedPipe` with `ERROR_PIPE_LISTENING` although
+/// the client opened the pipe already.
+async fn retry_connect<F, Fut>(deadline: Deadline, mut connect: F) -> io::Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = io::Result<()>>,
+{
+    let mut tries = 0;
+    let mut backoff = std::time::Duration::from_millis(1);
+    loop {
+        tries += 1;
+        match connect().await {
+            Err(err) if tries < MAX_TRIES && err.raw_os_error() == Some(ERROR_PIPE_LISTENING) => {
+                if deadline.expires_within(backoff) {
+                    return Err(CreationStage::Connect.timed_out(Some(err)));
+                }
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn security_descriptor(builder: &AnonPipeBuilder) -> io::Result<Option<SecurityDescriptor>> {
+    if builder.security.is_default() {
+        return Ok(None);
+    }
+    if builder.security_attributes.is_some() {
+        let err = io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "security_attributes_raw can not be combined with other security options",
+        );
+        return Err(Error::CreateServer(err).into());
+    }
+    let sddl = builder.security.sddl().map_err(Error::CreateServer)?;
+    let sd = SecurityDescriptor::from_sddl(&sddl).map_err(Error::CreateServer)?;
+    Ok(Some(sd))
+}
+
+/// Create the server end under a fresh name, blocking the thread while
+/// waiting for a creation permit.
+///
+/// For the constructors which are not async, see [`try_new_server_async`].
+fn try_new_server(
+    builder: &AnonPipeBuilder,
+    access: Access,
+    deadline: Deadline,
+) -> io::Result<(String, NamedPipeServer)> {
+    let sd = security_descriptor(builder)?;
+    let _permit = limit::acquire();
+    try_new_server_with(builder, sd.as_ref(), access, deadline)
+}
+
+/// Same as [`try_new_server`], yielding to the runtime while waiting for a
+/// creation permit.
+async fn try_new_server_async(
+    builder: &AnonPipeBuilder,
+    access: Access,
+    deadline: Deadline,
+) -> io::Result<(String, NamedPipeServer)> {
+    let sd = security_descriptor(builder)?;
+    let _permit = limit::acquire_async().await;
+    try_new_server_with(builder, sd.as_ref(), access, deadline)
+}
+
+/// Same as [`try_new_server`], with the descriptor of `builder` built
+/// already, for the servers creating more instances with it. The caller
+/// holds a creation permit.
+fn try_new_server_with(
+    builder: &AnonPipeBuilder,
+    sd: Option<&SecurityDescriptor>,
+    access: Access,
+    deadline: Deadline,
+) -> io::Result<(String, NamedPipeServer)> {
+    deadline.check(CreationStage::CreateServer)?;
+    retry_create(builder, deadline, |reject_remote_clients| {
+        let name = if builder.unpredictable_names {
+            unpredictable_name()?
+        } else {
+            genname()
+        };
+        let server = new_server(builder, sd, &name, true, reject_remote_clients, access)?;
+        Ok((name, server))
+    })
+}
+
+/// Call `create` with fresh names until it does not collide.
+///
+/// `create` receives whether to reject remote clients.
+fn retry_create<T>(
+    builder: &AnonPipeBuilder,
+    deadline: Deadline,
+    mut create: impl FnMut(bool) -> io::Result<T>,
+) -> io::Result<T> {
+    // https://www.rpi.edu/dept/cis/software/g77-mingw32/include/winerror.h
+    const ERROR_INVALID_PARAMETER: i32 = 87;
+
+    let max_tries = builder.max_tries.unwrap_or(MAX_TRIES);
+    let notify = |attempt, error: &io::Error, reject_remote_clients_toggled| {
+        if let Some(f) = &builder.on_retry {
+            f(RetryEvent {
+                attempt,
+                error,
+                reject_remote_clients_toggled,
+            });
+        }
+    };
+
+    #[cfg(feature = "stats")]
+    stats::incr(&stats::ATTEMPTED);
+
+    let mut tries = 0;
+    let mut reject_remote_clients = true;
+    loop {
+        tries += 1;
+
+        let server = match create(reject_remote_clients) {
+            Ok(server) => server,
+            Err(err) if tries < max_tries => {
+                if deadline.expires_within(std::time::Duration::ZERO) {
+                    return Err(CreationStage::CreateServer.timed_out(Some(err)));
+                }
+                match err.raw_os_error() {
+                    Some(ERROR_INVALID_PARAMETER) if reject_remote_clients => {
+                        // https://github.com/rust-lang/rust/blob/456a03227e3c81a51631f87ec80cac301e5fa6d7/library/std/src/sys/windows/pipe.rs#L101
+                        #[cfg(feature = "stats")]
+                        stats::incr(&stats::REMOTE_CLIENTS_DOWNGRADES);
+                        notify(tries, &err, true);
+                        reject_remote_clients = false;
+                        tries -= 1;
+                        continue;
+                    }
+                    _ if errors::is_transient(&err) => {
+                        // on a collided name too, try a fresh one.
+                        #[cfg(feature = "stats")]
+                        if err.raw_os_error() == Some(ERROR_ACCESS_DENIED) {
+                            stats::incr(&stats::NAME_COLLISIONS);
+                        }
+                        notify(tries, &err, false);
+                        continue;
+                    }
+                    _ => return Err(Error::CreateServer(err).into()),
+                }
+            }
+            Err(err) if err.raw_os_error() == Some(ERROR_ACCESS_DENIED) => {
+                return Err(Error::NameCollision {
+                    attempts: tries,
+                    source: err,
+                }
+                .into())
+            }
+            Err(err) => return Err(Error::CreateServer(err).into()),
+        };
+        #[cfg(feature = "stats")]
+        stats::incr(&stats::SUCCEEDED);
+        return Ok(server);
+    }
+}
+
+/// Open duplex Anonynous Pipe Pair.
+/// Pair is connected.
+pub async fn anon_pipe_duplex_pair() -> io::Result<(AnonPipeDuplex, AnonPipeDuplex)> {
+    AnonPipeBuilder::new().anon_pipe_duplex_pair().await
+}
+
+/// Open Anonynous Pipe Pair.
+/// Pair is connected.
+pub async fn anon_pipe() -> io::Result<(AnonPipeRead, AnonPipeWrite)> {
+    AnonPipeBuilder::new().anon_pipe().await
+}
+
+/// Open Anonynous Pipe Pair within `timeout`, retries included.
+/// Pair is connected.
+///
+/// See [`AnonPipeBuilder::creation_timeout`].
+pub async fn anon_pipe_with_timeout(
+    timeout: std::time::Duration,
+) -> io::Result<(AnonPipeRead, AnonPipeWrite)> {
+    AnonPipeBuilder::new()
+        .creation_timeout(timeout)
+        .anon_pipe()
+        .await
+}
+
+/// Open Anonynous Pipe Pair registered with the reactor of `handle`, from
+/// a thread outside of any runtime.
+/// Pair is connected.
+///
+/// Blocks the calling thread until created, see
+/// [`AnonPipeBuilder::runtime`]. The connect does not wait, as the client
+/// end is opened first, so `handle` may be a current-thread runtime which
+/// nothing drives at the moment. The ends are serviced while it is driven.
+///
+/// # Panics
+///
+/// Panics if called within an asynchronous execution context, as
+/// `Handle::block_on` does.
+pub fn anon_pipe_on(handle: &tokio::runtime::Handle) -> io::Result<(AnonPipeRead, AnonPipeWrite)> {
+    handle.block_on(AnonPipeBuilder::new().runtime(handle.clone()).anon_pipe())
+}
+
+/// Open Anonynous Pipe Pair.
+/// Pair is not connected yet.
+pub fn anon_pipe_we_read() -> io::Result<(Connect<AnonPipeRead>, AnonPipeWrite)> {
+    AnonPipeBuilder::new().anon_pipe_we_read()
+}
+
+/// Connect to `name` of an [`AnonPipeListener`] (or any duplex pipe).
+pub async fn connect_by_name(name: &str) -> io::Result<AnonPipeDuplex> {
+    AnonPipeBuilder::new().connect_by_name(name).await
+}
+
+/// Open Anonynous Pipe Pair, reading everything from the read half
+/// on a spawned task.
+///
+/// ```
+/// use tokio::io::AsyncWriteExt;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let (mut w, collected) = tokio_anon_pipe::anon_pipe_collecting()?;
+/// w.write_all(b"Hello, World!").await?;
+/// drop(w);
+/// assert_eq!(collected.await??, b"Hello, World!");
+/// # Ok(())
+/// # }
+/// ```
+pub fn anon_pipe_collecting(
+) -> io::Result<(AnonPipeWrite, tokio::task::JoinHandle<io::Result<Vec<u8>>>)> {
+    AnonPipeBuilder::new().anon_pipe_collecting()
+}
+
+/// Open Anonynous Pipe Pair, counting the bytes through it.
+/// Pair is connected.
+pub async fn anon_pipe_counted() -> io::Result<(Counted<AnonPipeRead>, Counted<AnonPipeWrite>)> {
+    AnonPipeBuilder::new().anon_pipe_counted().await
+}
+
+/// Open Anonynous Pipe Pair without any async work.
+/// Pair is not connected yet.
+///
+/// See [`Connect`] for creating outside the async context.
+pub fn anon_pipe_deferred() -> io::Result<(Connect<AnonPipeRead>, AnonPipeWrite)> {
+    AnonPipeBuilder::new().anon_pipe_deferred()
+}
+
+/// Open Anonynous Pipe Pair
+/// Pair is not connected yet.
+pub fn anon_pipe_we_write() -> io::Result<(AnonPipeRead, Connect<AnonPipeWrite>)> {
+    AnonPipeBuilder::new().anon_pipe_we_write()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{ERROR_PIPE_BUSY, ERROR_SEM_TIMEOUT};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_anon_pipe_on() -> io::Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        // on a thread without runtime.
+        let handle = rt.handle().clone();
+        let (mut r, mut w) = std::thread::spawn(move || anon_pipe_on(&handle))
+            .join()
+            .unwrap()?;
+
+        let buf = rt.block_on(async move {
+            w.write_all(b"Hello").await?;
+            drop(w);
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            Ok::<_, io::Error>(buf)
+        })?;
+        assert_eq!(&buf, b"Hello");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_write_flood_yields() -> io::Result<()> {
+        use std::sync::atomic::AtomicBool;
+
+        let (mut r, mut w) = anon_pipe().await?;
+        let writes = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = tokio::spawn(async move {
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await.map(|_| buf.len())
+        });
+        // low priority: only runs when the flooding task yields.
+        let ticker = tokio::spawn({
+            let writes = writes.clone();
+            let done = done.clone();
+            async move {
+                let (mut last, mut max_gap) = (0, 0);
+                while !done.load(Ordering::SeqCst) {
+                    let now = writes.load(Ordering::SeqCst);
+                    max_gap = max_gap.max(now - last);
+                    last = now;
+                    tokio::task::yield_now().await;
+                }
+                max_gap
+            }
+        });
+
+        let chunk = [0x5a; 4096];
+        for _ in 0..4096 {
+            w.write_all(&chunk).await?;
+            writes.fetch_add(1, Ordering::SeqCst);
+        }
+        w.close().await?;
+        done.store(true, Ordering::SeqCst);
+
+        assert_eq!(reader.await.unwrap()?, 4096 * 4096);
+        // the budget of a task is 128 units, each write spends at least one.
+        assert!(ticker.await.unwrap() <= 128);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test2() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"Hello, World!").await?;
+        let mut buf = vec![0; 13];
+        let mut n = 0;
+        while n < 13 {
+            n += r.read(&mut buf[n..]).await?;
+        }
+        assert_eq!(&b"Hello, World!"[..], &buf);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_we_write() -> io::Result<()> {
+        let (mut r, w) = anon_pipe_we_write()?;
+
+        let mut w = w.connect().await?;
+        w.write_all(b"Hello, World!").await?;
+        let mut buf = vec![0; 13];
+        let mut n = 0;
+        while n < 13 {
+            n += r.read(&mut buf[n..]).await?;
+        }
+        assert_eq!(&b"Hello, World!"[..], &buf);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_we_read() -> io::Result<()> {
+        let (r, mut w) = anon_pipe_we_read()?;
+
+        let mut r = r.connect().await?;
+        w.write_all(b"Hello, World!").await?;
+        let mut buf = vec![0; 13];
+        let mut n = 0;
+        while n < 13 {
+            n += r.read(&mut buf[n..]).await?;
+        }
+        assert_eq!(&b"Hello, World!"[..], &buf);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_woken_on_disconnect() -> io::Result<()> {
+        async fn check(mut r: AnonPipeRead, w: AnonPipeWrite) -> io::Result<()> {
+            let reader = tokio::spawn(async move {
+                let mut buf = [0; 16];
+                r.read(&mut buf).await
+            });
+            // let the read go in flight first.
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            assert!(!reader.is_finished());
+
+            drop(w);
+            let n = tokio::time::timeout(std::time::Duration::from_millis(100), reader)
+                .await
+                .expect("reader woken by the disconnect")
+                .unwrap()?;
+            assert_eq!(n, 0);
+            Ok(())
+        }
+
+        // server role reading
+        let (r, w) = anon_pipe_we_read()?;
+        let r = r.connect().await?;
+        check(r, w).await?;
+        // client role reading
+        let (r, w) = anon_pipe_we_write()?;
+        let w = w.connect().await?;
+        check(r, w).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_magic() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        w.write_all(b"APv2payload").await?;
+        r.read_magic(b"APv2").await?;
+        let mut buf = [0; 7];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"payload");
+
+        w.write_all(b"APv1").await?;
+        let err = r.read_magic(b"APv2").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        w.write_all(b"AP").await?;
+        drop(w);
+        let err = r.read_magic(b"APv2").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_builder_configure() -> io::Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let server_calls = Arc::new(AtomicUsize::new(0));
+        let client_calls = Arc::new(AtomicUsize::new(0));
+        let (mut r, mut w) = {
+            let server_calls = server_calls.clone();
+            let client_calls = client_calls.clone();
+            AnonPipeBuilder::new()
+                .configure_server(move |opts| {
+                    server_calls.fetch_add(1, Ordering::SeqCst);
+                    opts.in_buffer_size(1024);
+                })
+                .configure_client(move |_| {
+                    client_calls.fetch_add(1, Ordering::SeqCst);
+                })
+                .anon_pipe()
+                .await?
+        };
+        assert!(server_calls.load(Ordering::SeqCst) >= 1);
+        assert_eq!(client_calls.load(Ordering::SeqCst), 1);
+
+        w.write_all(b"Hello, World!").await?;
+        let mut buf = vec![0; 13];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&b"Hello, World!"[..], &buf);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_allow_sid() -> io::Result<()> {
+        let (mut r, mut w) = AnonPipeBuilder::new()
+            .cross_session()
+            .allow_sid("S-1-5-32-545") // BUILTIN\Users
+            .anon_pipe()
+            .await?;
+
+        w.write_all(b"Hello, World!").await?;
+        let mut buf = vec![0; 13];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&b"Hello, World!"[..], &buf);
+        assert_eq!(r.peer_session_id()?, w.peer_session_id()?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_allow_sid_malformed() {
+        let err = AnonPipeBuilder::new()
+            .allow_sid("IU)(A;;GA;;;WD")
+            .anon_pipe()
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires running as a service in session 0"]
+    async fn test_cross_session() -> io::Result<()> {
+        extern "system" {
+            fn ProcessIdToSessionId(_: u32, _: *mut u32) -> i32;
+        }
+        let mut session = 0;
+        if unsafe { ProcessIdToSessionId(process::id(), &mut session) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        assert_eq!(session, 0);
+
+        let (r, w) = AnonPipeBuilder::new().cross_session().anon_pipe().await?;
+        assert_eq!(r.peer_session_id()?, session);
+        assert_eq!(w.peer_session_id()?, session);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_medium_integrity_clients() -> io::Result<()> {
+        let (mut r, mut w) = AnonPipeBuilder::new()
+            .medium_integrity_clients(true)
+            .anon_pipe()
+            .await?;
+
+        w.write_all(b"Hello, World!").await?;
+        let mut buf = vec![0; 13];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&b"Hello, World!"[..], &buf);
+        Ok(())
+    }
+
+    fn label_sddl(handle: RawHandle) -> String {
+        extern "system" {
+            fn GetSecurityInfo(
+                _: RawHandle,
+                _: u32,
+                _: u32,
+                _: *mut c_void,
+                _: *mut c_void,
+                _: *mut c_void,
+                _: *mut c_void,
+                _: *mut *mut c_void,
+            ) -> u32;
+            fn ConvertSecurityDescriptorToStringSecurityDescriptorW(
+                _: *mut c_void,
+                _: u32,
+                _: u32,
+                _: *mut *mut u16,
+                _: *mut u32,
+            ) -> i32;
+            fn LocalFree(_: *mut c_void) -> *mut c_void;
+        }
+        const SE_KERNEL_OBJECT: u32 = 6;
+        const LABEL_SECURITY_INFORMATION: u32 = 0x10;
+
+        let mut sd = std::ptr::null_mut();
+        let mut sddl = std::ptr::null_mut();
+        unsafe {
+            let null = std::ptr::null_mut();
+            let ret = GetSecurityInfo(
+                handle,
+                SE_KERNEL_OBJECT,
+                LABEL_SECURITY_INFORMATION,
+                null,
+                null,
+                null,
+                null,
+                &mut sd,
+            );
+            assert_eq!(ret, 0, "failed to GetSecurityInfo");
+            let ok = ConvertSecurityDescriptorToStringSecurityDescriptorW(
+                sd,
+                1,
+                LABEL_SECURITY_INFORMATION,
+                &mut sddl,
+                null as _,
+            );
+            assert_ne!(
+                ok, 0,
+                "failed to ConvertSecurityDescriptorToStringSecurityDescriptorW"
+            );
+            let len = (0..).take_while(|i| *sddl.add(*i) != 0).count();
+            let s = String::from_utf16_lossy(std::slice::from_raw_parts(sddl, len));
+            LocalFree(sddl as _);
+            LocalFree(sd);
+            s
+        }
+    }
+
+    #[tokio::test]
+    async fn test_medium_integrity_clients_label() -> io::Result<()> {
+        let (r, _w) = AnonPipeBuilder::new()
+            .medium_integrity_clients(true)
+            .anon_pipe()
+            .await?;
+        assert!(label_sddl(r.as_raw_handle()).contains("(ML;;NW;;;ME)"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore = "requires running elevated"]
+    async fn test_medium_integrity_clients_elevated() -> io::Result<()> {
+        let mut server = AnonPipeBuilder::new()
+            .medium_integrity_clients(true)
+            .server()?;
+        let name = server.name().to_owned();
+
+        // open from a thread impersonating a medium integrity copy of our
+        // (high integrity) token.
+        let client = std::thread::spawn(move || open_as_medium_integrity(&name));
+        let mut r = server.accept_next().await?;
+        let mut file = client.join().unwrap()?;
+
+        std::io::Write::write_all(&mut file, b"Hello")?;
+        drop(file);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"Hello");
+        Ok(())
+    }
+
+    fn open_as_medium_integrity(name: &str) -> io::Result<std::fs::File> {
+        // TOKEN_MANDATORY_LABEL
+        #[repr(C)]
+        struct MandatoryLabel {
+            sid: *mut c_void,
+            attributes: u32,
+        }
+        extern "system" {
+            fn GetCurrentProcess() -> RawHandle;
+            fn OpenProcessToken(_: RawHandle, _: u32, _: *mut RawHandle) -> i32;
+            fn DuplicateTokenEx(
+                _: RawHandle,
+                _: u32,
+                _: *mut c_void,
+                _: u32,
+                _: u32,
+                _: *mut RawHandle,
+            ) -> i32;
+            fn ConvertStringSidToSidW(_: *const u16, _: *mut *mut c_void) -> i32;
+            fn GetLengthSid(_: *mut c_void) -> u32;
+            fn SetTokenInformation(_: RawHandle, _: u32, _: *mut c_void, _: u32) -> i32;
+            fn SetThreadToken(_: *mut RawHandle, _: RawHandle) -> i32;
+            fn RevertToSelf() -> i32;
+            fn CloseHandle(_: RawHandle) -> i32;
+            fn LocalFree(_: *mut c_void) -> *mut c_void;
+        }
+        const TOKEN_ALL_ACCESS: u32 = 0x000F_01FF;
+        const SECURITY_IMPERSONATION: u32 = 2;
+        const TOKEN_IMPERSONATION: u32 = 2;
+        const TOKEN_INTEGRITY_LEVEL: u32 = 25;
+        const SE_GROUP_INTEGRITY: u32 = 0x20;
+
+        fn check(ok: i32) -> io::Result<()> {
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        unsafe {
+            let mut process = std::ptr::null_mut();
+            check(OpenProcessToken(
+                GetCurrentProcess(),
+                TOKEN_ALL_ACCESS,
+                &mut process,
+            ))?;
+            let mut token = std::ptr::null_mut();
+            let ok = DuplicateTokenEx(
+                process,
+                TOKEN_ALL_ACCESS,
+                std::ptr::null_mut(),
+                SECURITY_IMPERSONATION,
+                TOKEN_IMPERSONATION,
+                &mut token,
+            );
+            CloseHandle(process);
+            check(ok)?;
+
+            let medium = "S-1-16-8192\0".encode_utf16().collect::<Vec<_>>();
+            let mut sid = std::ptr::null_mut();
+            check(ConvertStringSidToSidW(medium.as_ptr(), &mut sid))?;
+            let mut label = MandatoryLabel {
+                sid,
+                attributes: SE_GROUP_INTEGRITY,
+            };
+            let ok = SetTokenInformation(
+                token,
+                TOKEN_INTEGRITY_LEVEL,
+                &mut label as *mut _ as *mut _,
+                std::mem::size_of::<MandatoryLabel>() as u32 + GetLengthSid(sid),
+            );
+            LocalFree(sid);
+            let ok = check(ok).and_then(|_| check(SetThreadToken(std::ptr::null_mut(), token)));
+            CloseHandle(token);
+            ok?;
+
+            let file = std::fs::OpenOptions::new().write(true).open(name);
+            check(RevertToSelf())?;
+            file
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_all_chunks() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        let large = (0..200_000).map(|n| n as u8).collect::<Vec<_>>();
+        let chunks = [&b"Hello"[..], b"", b", ", &large, b"World!"];
+        let expected = chunks.concat();
+
+        let w_task = tokio::spawn(async move {
+            let chunks = [&b"Hello"[..], b"", b", ", &large, b"World!"];
+            w.write_all_chunks(&chunks).await
+        });
+        let mut buf = vec![0; expected.len()];
+        r.read_exact(&mut buf).await?;
+        w_task.await.unwrap()?;
+        assert_eq!(expected, buf);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keepalive() -> io::Result<()> {
+        use std::time::Duration;
+
+        let (mut r, w) = anon_pipe().await?;
+        let mut w = w.with_keepalive(Duration::from_millis(10));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(w.is_alive());
+        w.write_all(b"Hello, World!").await?;
+        let mut buf = vec![0; 13];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&b"Hello, World!"[..], &buf);
+
+        drop(r);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!w.is_alive());
+        let err = w.write_all(b"Hello, World!").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_shutdown() -> io::Result<()> {
+        let (mut r, w) = anon_pipe().await?;
+        let mut w = w.with_keepalive(Duration::from_millis(1));
+
+        w.write_all(b"Hello").await?;
+        w.shutdown().await?;
+        let mut buf = vec![0; 5];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"Hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_open() {
+        let mut calls = 0;
+        let result = retry_open(Deadline::default(), || {
+            calls += 1;
+            match calls {
+                1 | 2 => Err(io::Error::from_raw_os_error(ERROR_SEM_TIMEOUT)),
+                3 => Err(io::Error::from_raw_os_error(ERROR_PIPE_BUSY)),
+                _ => Ok(calls),
+            }
+        });
+        assert_eq!(result.unwrap(), 4);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_retry_open_async() {
+        // backing off must not park the only worker.
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                    tokio::task::yield_now().await;
+                }
+            }
+        });
+
+        let mut calls = 0;
+        let result = retry_open_async(Deadline::default(), || {
+            calls += 1;
+            match calls {
+                1 | 2 => Err(io::Error::from_raw_os_error(ERROR_SEM_TIMEOUT)),
+                3 => Err(io::Error::from_raw_os_error(ERROR_PIPE_BUSY)),
+                _ => Ok(calls),
+            }
+        })
+        .await;
+        ticker.abort();
+        assert_eq!(result.unwrap(), 4);
+        assert!(ticks.load(Ordering::SeqCst) > 0);
+    }
+
+    #[test]
+    fn test_retry_open_gives_up() {
+        let mut calls = 0;
+        let err = retry_open(Deadline::default(), || -> io::Result<()> {
+            calls += 1;
+            Err(io::Error::from_raw_os_error(ERROR_SEM_TIMEOUT))
+        })
+        .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(ERROR_SEM_TIMEOUT));
+        assert_eq!(calls, MAX_TRIES);
+    }
+
+    #[test]
+    fn test_retry_open_permanent() {
+        const ERROR_FILE_NOT_FOUND: i32 = 2;
+
+        let mut calls = 0;
+        let err = retry_open(Deadline::default(), || -> io::Result<()> {
+            calls += 1;
+            Err(io::Error::from_raw_os_error(ERROR_FILE_NOT_FOUND))
+        })
+        .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(ERROR_FILE_NOT_FOUND));
+        assert_eq!(calls, 1);
+        assert!(!errors::is_retryable_open(&err));
+    }
+
+    #[tokio::test]
+    async fn test_buffer_size_fallbacks() -> io::Result<()> {
+        let (r, _w) = AnonPipeBuilder::new()
+            .configure_server(|opts| {
+                opts.in_buffer_size(1024);
+            })
+            .buffer_size_fallbacks(&[8192, 4096])
+            .anon_pipe()
+            .await?;
+
+        assert_eq!(r.pipe_info()?.in_buffer_size(), 8192);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_buffer_size_error() {
+        assert!(is_buffer_size_error(&io::Error::from_raw_os_error(1450)));
+        assert!(is_buffer_size_error(&io::Error::from_raw_os_error(1816)));
+        assert!(!is_buffer_size_error(&io::Error::from_raw_os_error(5)));
+        assert!(!is_buffer_size_error(&io::Error::from_raw_os_error(87)));
+    }
+
+    #[tokio::test]
+    async fn test_builder_configure_pipe_info() -> io::Result<()> {
+        let (r, w) = AnonPipeBuilder::new()
+            .configure_server(|opts| {
+                opts.in_buffer_size(8192)
+                    .out_buffer_size(0)
+                    .access_outbound(true)
+                    .max_instances(5);
+            })
+            .configure_client(|opts| {
+                opts.read(true);
+            })
+            .anon_pipe()
+            .await?;
+
+        let info = r.pipe_info()?;
+        assert!(info.is_server_end());
+        assert!(!info.is_message_type());
+        assert_eq!(info.in_buffer_size(), 8192);
+        assert_eq!(info.max_instances(), 1);
+
+        let info = w.pipe_info()?;
+        assert!(!info.is_server_end());
+        assert!(info.is_overlapped());
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_runtime() -> anyhow::Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()?;
+
+        // not within any runtime here.
+        let (r, mut w) = AnonPipeBuilder::new()
+            .runtime(rt.handle().clone())
+            .anon_pipe_deferred()?;
+        assert!(r.0.pipe_info()?.is_overlapped());
+
+        rt.block_on(async move {
+            let mut r = r.connect().await?;
+            w.write_all(b"Hello, World!").await?;
+            drop(w);
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            assert_eq!(&buf, b"Hello, World!");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_deferred() -> anyhow::Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()?;
+
+        let (r, mut w) = std::thread::spawn({
+            let handle = rt.handle().clone();
+            move || {
+                let _guard = handle.enter();
+                anon_pipe_deferred()
+            }
+        })
+        .join()
+        .unwrap()?;
+
+        rt.block_on(async move {
+            let mut r = r.connect().await?;
+            w.write_all(b"Hello, World!").await?;
+            drop(w);
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            assert_eq!(&buf, b"Hello, World!");
+            Ok(())
+        })
+    }
+
+    #[tokio::test]
+    async fn test_deferred_spawn_blocking() -> anyhow::Result<()> {
+        let (r, w) = tokio::task::spawn_blocking(anon_pipe_deferred).await??;
+        let r = r.connect().await?;
+        drop((r, w));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_security_attributes_raw() -> anyhow::Result<()> {
+        let mut attrs = sys::SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<sys::SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: std::ptr::null_mut(),
+            bInheritHandle: 1,
+        };
+        let mut builder = AnonPipeBuilder::new();
+        unsafe { builder.security_attributes_raw(&mut attrs as *mut _ as *mut c_void) };
+        let (r, w) = builder.anon_pipe().await?;
+
+        let mut flags = 0;
+        let ok = unsafe { sys::GetHandleInformation(r.as_raw_handle() as _, &mut flags) };
+        assert_ne!(ok, 0, "{}", io::Error::last_os_error());
+        assert_eq!(flags & sys::HANDLE_FLAG_INHERIT, sys::HANDLE_FLAG_INHERIT);
+
+        let ok = unsafe { sys::GetHandleInformation(w.as_raw_handle() as _, &mut flags) };
+        assert_ne!(ok, 0, "{}", io::Error::last_os_error());
+        assert_eq!(flags & sys::HANDLE_FLAG_INHERIT, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_security_attributes_raw_exclusive() -> io::Result<()> {
+        let mut attrs = sys::SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<sys::SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: std::ptr::null_mut(),
+            bInheritHandle: 0,
+        };
+        let mut builder = AnonPipeBuilder::new();
+        unsafe { builder.security_attributes_raw(&mut attrs as *mut _ as *mut c_void) };
+        let err = builder.allow_sid("IU").anon_pipe().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_anon_pipe_collecting() -> anyhow::Result<()> {
+        let (mut w, collected) = anon_pipe_collecting()?;
+        let data = (0..100_000).map(|n| n as u8).collect::<Vec<_>>();
+
+        w.write_all(&data).await?;
+        drop(w);
+        assert_eq!(collected.await??, data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_for_each_chunk() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        let data = (0..100_000).map(|n| n as u8).collect::<Vec<_>>();
+
+        let writer = {
+            let data = data.clone();
+            tokio::spawn(async move { w.write_all(&data).await })
+        };
+        let mut received = vec![];
+        let total = r
+            .for_each_chunk(|chunk| {
+                received.extend_from_slice(chunk);
+                Ok(())
+            })
+            .await?;
+        writer.await.unwrap()?;
+        assert_eq!(total, 100_000);
+        assert_eq!(received, data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_for_each_chunk_error() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"Hello, World!").await?;
+        let mut calls = 0;
+        let err = r
+            .for_each_chunk(|_| {
+                calls += 1;
+                Err(io::Error::other("stop"))
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "stop");
+        assert_eq!(calls, 1);
+        Ok(())
+    }
 
-#[cfg(windows)]
-fn new_server(name: &str, reject_remote_clients: bool, write: bool) -> io::Result<NamedPipeServer> {
-    ServerOptions::new()
-        .access_inbound(!write) // client to server
-        .access_outbound(write) // server to client
-        .first_pipe_instance(true)
-        .reject_remote_clients(reject_remote_clients)
-        .max_instances(1)
-        .create(&name)
-}
+    #[tokio::test]
+    async fn test_global_defaults() -> io::Result<()> {
+        // process-wide, keep it compatible with the other tests.
+        let mut defaults = AnonPipeDefaults::new();
+        defaults.in_buffer_size(65536);
+        let _ = set_global_defaults(defaults);
+        assert!(set_global_defaults(AnonPipeDefaults::new()).is_err());
 
-#[cfg(windows)]
-fn new_client(name: &str, write: bool) -> io::Result<NamedPipeClient> {
-    ClientOptions::new().read(!write).write(write).open(&name)
-}
+        let (r, _w) = anon_pipe().await?;
+        assert_eq!(r.pipe_info()?.in_buffer_size(), 65536);
+        assert_eq!(r.pipe_info()?.max_instances(), 1);
 
-fn try_new_server(write: bool) -> io::Result<(String, NamedPipeServer)> {
-    // https://www.rpi.edu/dept/cis/software/g77-mingw32/include/winerror.h
-    const ERROR_ACCESS_DENIED: i32 = 5;
-    const ERROR_INVALID_PARAMETER: i32 = 87;
+        let (r, _w) = AnonPipeBuilder::new()
+            .configure_server(|opts| {
+                opts.in_buffer_size(8192);
+            })
+            .anon_pipe()
+            .await?;
+        assert_eq!(r.pipe_info()?.in_buffer_size(), 8192);
+        Ok(())
+    }
 
-    let mut tries = 0;
-    let mut reject_remote_clients = true;
-    loop {
-        tries += 1;
-        let name = genname();
+    #[tokio::test]
+    async fn test_is_valid() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        assert!(r.is_valid());
+        assert!(w.is_valid());
 
-        let server = match new_server(&name, reject_remote_clients, write) {
-            Ok(server) => server,
-            Err(err) if tries < 10 => {
-                match err.raw_os_error() {
-                    Some(ERROR_ACCESS_DENIED) => continue,
-                    Some(ERROR_INVALID_PARAMETER) if reject_remote_clients => {
-                        // https://github.com/rust-lang/rust/blob/456a03227e3c81a51631f87ec80cac301e5fa6d7/library/std/src/sys/windows/pipe.rs#L101
-                        reject_remote_clients = false;
-                        tries -= 1;
-                        continue;
-                    }
-                    _ => return Err(err),
+        let ok = unsafe { sys::CloseHandle(w.as_raw_handle() as _) };
+        assert_ne!(ok, 0);
+        assert!(!w.is_valid());
+        assert!(r.is_valid());
+        // the handle is closed already.
+        mem::forget(w);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_eof_after_spawn() -> anyhow::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        // unrelated child, spawned with handle inheritance enabled.
+        let mut child = process::Command::new("cmd")
+            .args(["/C", "ping -n 5 127.0.0.1 >NUL"])
+            .spawn()?;
+
+        w.write_all(b"Hello").await?;
+        drop(w);
+        let mut buf = vec![];
+        let result =
+            tokio::time::timeout(std::time::Duration::from_secs(1), r.read_to_end(&mut buf)).await;
+        child.kill()?;
+        child.wait()?;
+        result??;
+        assert_eq!(&buf, b"Hello");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_closed() -> anyhow::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        w.write_all(b"unread").await?;
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(300), r.closed())
+                .await
+                .is_err()
+        );
+
+        drop(w);
+        tokio::time::timeout(std::time::Duration::from_secs(1), r.closed()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_closed() -> anyhow::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(300), w.closed())
+                .await
+                .is_err()
+        );
+
+        drop(r);
+        tokio::time::timeout(std::time::Duration::from_secs(1), w.closed()).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_retry() {
+        const ERROR_ACCESS_DENIED: i32 = 5;
+        const ERROR_INVALID_PARAMETER: i32 = 87;
+
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut builder = AnonPipeBuilder::new();
+        builder.on_retry({
+            let events = events.clone();
+            move |event| {
+                events.lock().unwrap().push((
+                    event.attempt(),
+                    event.error().raw_os_error(),
+                    event.reject_remote_clients_toggled(),
+                ))
+            }
+        });
+
+        let mut calls = 0;
+        let result = retry_create(&builder, Deadline::default(), |reject_remote_clients| {
+            calls += 1;
+            match calls {
+                1 => Err(io::Error::from_raw_os_error(ERROR_ACCESS_DENIED)),
+                2 => Err(io::Error::from_raw_os_error(ERROR_INVALID_PARAMETER)),
+                _ => {
+                    assert!(!reject_remote_clients);
+                    Ok(calls)
                 }
             }
-            Err(err) => return Err(err),
+        });
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                (1, Some(ERROR_ACCESS_DENIED), false),
+                (2, Some(ERROR_INVALID_PARAMETER), true),
+            ]
+        );
+
+        events.lock().unwrap().clear();
+        let err = retry_create(&builder, Deadline::default(), |_| -> io::Result<()> {
+            Err(io::Error::from_raw_os_error(ERROR_ACCESS_DENIED))
+        })
+        .unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(Error::NameCollision { attempts: 10, .. })
+        ));
+        assert_eq!(events.lock().unwrap().len(), MAX_TRIES - 1);
+    }
+
+    fn timed_out_stage(err: &io::Error) -> Option<CreationStage> {
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        match err.get_ref()?.downcast_ref::<Error>()? {
+            Error::TimedOut { stage, .. } => Some(*stage),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_creation_timeout() {
+        let within = |ms| Deadline(Some(std::time::Instant::now() + Duration::from_millis(ms)));
+
+        // backoff 1, 2, 4ms, then 8ms would pass the deadline.
+        let mut calls = 0;
+        let err = retry_open(within(10), || -> io::Result<()> {
+            calls += 1;
+            Err(io::Error::from_raw_os_error(ERROR_PIPE_BUSY))
+        })
+        .unwrap_err();
+        assert_eq!(timed_out_stage(&err), Some(CreationStage::OpenClient));
+        assert_eq!(
+            err.get_ref()
+                .unwrap()
+                .downcast_ref::<Error>()
+                .unwrap()
+                .raw_os_error(),
+            Some(ERROR_PIPE_BUSY)
+        );
+        assert!(calls < MAX_TRIES, "{}", calls);
+
+        let mut calls = 0;
+        let err = retry_create(&AnonPipeBuilder::new(), within(0), |_| -> io::Result<()> {
+            calls += 1;
+            Err(io::Error::from_raw_os_error(5))
+        })
+        .unwrap_err();
+        assert_eq!(timed_out_stage(&err), Some(CreationStage::CreateServer));
+        assert_eq!(calls, 1);
+
+        let err = retry_connect(within(10), || async {
+            Err(io::Error::from_raw_os_error(ERROR_PIPE_LISTENING))
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(timed_out_stage(&err), Some(CreationStage::Connect));
+
+        let err = anon_pipe_with_timeout(Duration::ZERO).await.unwrap_err();
+        assert_eq!(timed_out_stage(&err), Some(CreationStage::CreateServer));
+        let err = AnonPipeBuilder::new()
+            .creation_timeout(Duration::ZERO)
+            .anon_pipe_duplex_pair()
+            .await
+            .unwrap_err();
+        assert_eq!(timed_out_stage(&err), Some(CreationStage::CreateServer));
+    }
+
+    #[tokio::test]
+    async fn test_write_through() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        assert!(!r.pipe_info()?.is_write_through());
+        assert!(!w.pipe_info()?.is_write_through());
+
+        let (mut r, mut w) = AnonPipeBuilder::new()
+            .write_through(true)
+            .anon_pipe()
+            .await?;
+        assert!(r.pipe_info()?.is_write_through());
+        assert!(w.pipe_info()?.is_write_through());
+        let (a, b) = AnonPipeBuilder::new()
+            .write_through(true)
+            .anon_pipe_duplex_pair()
+            .await?;
+        assert!(a.pipe_info()?.is_write_through());
+        assert!(b.pipe_info()?.is_write_through());
+
+        let mut attrs = sys::SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<sys::SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: std::ptr::null_mut(),
+            bInheritHandle: 0,
         };
-        return Ok((name, server));
+        let mut builder = AnonPipeBuilder::new();
+        unsafe { builder.security_attributes_raw(&mut attrs as *mut _ as *mut c_void) };
+        let (r2, w2) = builder.write_through(true).anon_pipe().await?;
+        assert!(r2.pipe_info()?.is_write_through());
+        assert!(w2.pipe_info()?.is_write_through());
+
+        w.write_all(b"written through").await?;
+        drop(w);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(buf, b"written through");
+        Ok(())
     }
-}
 
-/// Open Anonynous Pipe Pair.
-/// Pair is connected.
-pub async fn anon_pipe() -> io::Result<(AnonPipeRead, AnonPipeWrite)> {
-    let (name, server) = try_new_server(false)?;
-    let client = new_client(&name, true)?;
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn test_try_from_owned_handle() -> io::Result<()> {
+        use std::os::windows::fs::OpenOptionsExt;
 
-    server.connect().await?;
+        // https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-createfilew
+        const FILE_FLAG_OVERLAPPED: u32 = 0x4000_0000;
 
-    let read = AnonPipeRead::Server(server);
-    let write = AnonPipeWrite::Client(client);
-    Ok((read, write))
-}
+        let mut server = AnonPipeBuilder::new().server()?;
+        // not registered with a completion port yet.
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(FILE_FLAG_OVERLAPPED)
+            .open(server.name())?;
+        let mut w = AnonPipeWrite::try_from(OwnedHandle::from(file))?;
+        let mut r = server.accept_next().await?;
+        w.write_all(b"adopted").await?;
+        drop(w);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(buf, b"adopted");
 
-/// Open Anonynous Pipe Pair.
-/// Pair is not connected yet.
-pub fn anon_pipe_we_read() -> io::Result<(Connect<AnonPipeRead>, AnonPipeWrite)> {
-    let (name, server) = try_new_server(false)?;
-    let client = new_client(&name, true)?;
+        let server = AnonPipeBuilder::new().server()?;
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(server.name())?;
+        let err = AnonPipeWrite::try_from(OwnedHandle::from(file)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
 
-    let read = Connect(AnonPipeRead::Server(server));
-    let write = AnonPipeWrite::Client(client);
-    Ok((read, write))
-}
+        let file = std::fs::File::open(std::env::current_exe()?)?;
+        let err = AnonPipeRead::try_from(OwnedHandle::from(file)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "handle is not a pipe");
+        Ok(())
+    }
 
-/// Open Anonynous Pipe Pair
-/// Pair is not connected yet.
-pub fn anon_pipe_we_write() -> io::Result<(AnonPipeRead, Connect<AnonPipeWrite>)> {
-    let (name, server) = try_new_server(true)?;
-    let client = new_client(&name, false)?;
+    #[tokio::test]
+    async fn test_anon_pipe_with_timeout() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe_with_timeout(Duration::from_secs(10)).await?;
+        w.write_all(b"in time").await?;
+        let mut buf = [0; 7];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"in time");
+        Ok(())
+    }
 
-    let read = AnonPipeRead::Client(client);
-    let write = Connect(AnonPipeWrite::Server(server));
-    Ok((read, write))
-}
+    #[tokio::test]
+    async fn test_retry_connect() {
+        const ERROR_NO_DATA: i32 = 232;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let mut calls = 0;
+        let result = retry_connect(Deadline::default(), || {
+            calls += 1;
+            let result = if calls < 3 {
+                Err(io::Error::from_raw_os_error(ERROR_PIPE_LISTENING))
+            } else {
+                Ok(())
+            };
+            async move { result }
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let err = retry_connect(Deadline::default(), || {
+            calls += 1;
+            async { Err(io::Error::from_raw_os_error(ERROR_NO_DATA)) }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(ERROR_NO_DATA));
+        assert_eq!(calls, 1);
+
+        let mut calls = 0;
+        let err = retry_connect(Deadline::default(), || {
+            calls += 1;
+            async { Err(io::Error::from_raw_os_error(ERROR_PIPE_LISTENING)) }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(ERROR_PIPE_LISTENING));
+        assert_eq!(calls, MAX_TRIES);
+    }
+
+    #[test]
+    fn test_no_retry() {
+        const ERROR_ACCESS_DENIED: i32 = 5;
+        const ERROR_PIPE_BUSY: i32 = 231;
+
+        let mut builder = AnonPipeBuilder::new();
+        builder.no_retry();
+
+        let mut calls = 0;
+        let err = retry_create(&builder, Deadline::default(), |_| -> io::Result<()> {
+            calls += 1;
+            Err(io::Error::from_raw_os_error(ERROR_ACCESS_DENIED))
+        })
+        .unwrap_err();
+        assert_eq!(calls, 1);
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(matches!(
+            err.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(Error::NameCollision { attempts: 1, .. })
+        ));
+
+        let err = retry_create(&builder, Deadline::default(), |_| -> io::Result<()> {
+            Err(io::Error::from_raw_os_error(ERROR_PIPE_BUSY))
+        })
+        .unwrap_err();
+        let err = err.get_ref().and_then(|e| e.downcast_ref::<Error>());
+        assert_eq!(err.and_then(Error::raw_os_error), Some(ERROR_PIPE_BUSY));
+    }
+
+    #[test]
+    fn test_genname() {
+        let a = genname();
+        let b = genname();
+        assert_ne!(a, b);
+        assert!(a.starts_with(r"\\.\pipe\__tokio_anonymous_pipe0__."));
+        let parts = a.rsplit('.').collect::<Vec<_>>();
+        assert_eq!(parts[0].len(), 32);
+        assert!(parts[0].chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')));
+        assert!(parts[1..4].iter().all(|p| p.parse::<u64>().is_ok()));
+        assert_eq!(parts[3], process::id().to_string());
+
+        let parsed = parse_pipe_name(&b).unwrap();
+        assert_eq!(parsed.pid(), process::id());
+        assert!(parsed.counter() > parts[1].parse::<u64>().unwrap());
+    }
+
+    #[test]
+    fn test_unpredictable_name() -> io::Result<()> {
+        let mut names = std::collections::HashSet::new();
+        for _ in 0..100_000 {
+            let name = unpredictable_name()?;
+            let random = name
+                .strip_prefix(r"\\.\pipe\__tokio_anonymous_pipe0__.")
+                .unwrap();
+            assert_eq!(random.len(), 32);
+            assert!(random.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')));
+            assert_eq!(validate_pipe_name(&name), Ok(()));
+            assert!(parse_pipe_name(&name).is_none());
+            assert!(names.insert(name));
+        }
+        Ok(())
+    }
 
     #[tokio::test]
-    async fn test2() -> io::Result<()> {
-        let (mut r, mut w) = anon_pipe().await?;
+    async fn test_unpredictable_names() -> io::Result<()> {
+        let (mut r, mut w) = AnonPipeBuilder::new()
+            .unpredictable_names(true)
+            .anon_pipe()
+            .await?;
+        w.write_all(b"Hello").await?;
+        drop(w);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"Hello");
 
-        w.write_all(b"Hello, World!").await?;
-        let mut buf = vec![0; 13];
-        let mut n = 0;
-        while n < 13 {
-            n += r.read(&mut buf[n..]).await?;
+        let listener = AnonPipeBuilder::new()
+            .unpredictable_names(true)
+            .listener()?;
+        let name = listener.name().to_owned();
+        assert!(parse_pipe_name(&name).is_none());
+        let (accepted, _client) = tokio::try_join!(listener.accept(), connect_by_name(&name))?;
+        drop(accepted);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "rand"))]
+    #[test]
+    fn test_fallback_entropy() {
+        assert_ne!(fallback_entropy(), [0; 16]);
+        assert_ne!(entropy(), entropy());
+    }
+
+    #[test]
+    fn test_genname_parallel() {
+        let threads = (0..16)
+            .map(|_| std::thread::spawn(|| (0..10_000).map(|_| genname()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        let mut names = std::collections::HashSet::new();
+        for thread in threads {
+            for name in thread.join().unwrap() {
+                assert!(names.insert(name));
+            }
+        }
+        assert_eq!(names.len(), 16 * 10_000);
+    }
+
+    #[test]
+    fn test_create_storm() {
+        #[cfg(feature = "stats")]
+        let before = creation_stats();
+        let threads = (0..64)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    let rt = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .unwrap();
+                    rt.block_on(async {
+                        for _ in 0..1000 {
+                            anon_pipe().await?;
+                        }
+                        Ok::<_, io::Error>(())
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+
+        // other tests create pipes concurrently, only the delta is ours.
+        #[cfg(feature = "stats")]
+        {
+            let after = creation_stats();
+            assert!(after.succeeded() - before.succeeded() >= 64 * 1000);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_creations() {
+        let start = Arc::new(std::sync::Barrier::new(256));
+        let threads = (0..256)
+            .map(|_| {
+                let start = start.clone();
+                std::thread::spawn(move || {
+                    let rt = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .unwrap();
+                    start.wait();
+                    rt.block_on(async {
+                        let (mut r, mut w) = anon_pipe().await?;
+                        w.write_all(b"ok").await?;
+                        let mut buf = [0; 2];
+                        r.read_exact(&mut buf).await?;
+                        assert_eq!(&buf, b"ok");
+                        Ok::<_, io::Error>(())
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_accept_next() -> io::Result<()> {
+        let mut server = AnonPipeBuilder::new().max_instances(2).server()?;
+
+        for msg in [&b"one"[..], b"two", b"three"] {
+            let mut client = ClientOptions::new()
+                .read(false)
+                .write(true)
+                .open(server.name())?;
+            let mut r = server.accept_next().await?;
+            client.write_all(msg).await?;
+            drop(client);
+
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            assert_eq!(msg, &buf[..]);
         }
-        assert_eq!(&b"Hello, World!"[..], &buf);
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_we_write() -> io::Result<()> {
-        let (mut r, w) = anon_pipe_we_write()?;
+    async fn test_server_single_instance() -> io::Result<()> {
+        let mut server = AnonPipeBuilder::new().server()?;
 
-        let mut w = w.connect().await?;
-        w.write_all(b"Hello, World!").await?;
+        let client = ClientOptions::new()
+            .read(false)
+            .write(true)
+            .open(server.name())?;
+        let r = server.accept_next().await?;
+        drop(client);
+
+        // the only instance is still held by `r`.
+        assert!(ClientOptions::new()
+            .read(false)
+            .write(true)
+            .open(server.name())
+            .is_err());
+
+        drop(r);
+        let name = server.name().to_owned();
+        let accept = tokio::spawn(async move { server.accept_next().await });
+        let mut client = loop {
+            // the next instance is created once accept_next is polled.
+            match ClientOptions::new().read(false).write(true).open(&name) {
+                Ok(client) => break client,
+                Err(..) => tokio::time::sleep(std::time::Duration::from_millis(1)).await,
+            }
+        };
+        let mut r = accept.await.unwrap()?;
+        client.write_all(b"Hello, World!").await?;
         let mut buf = vec![0; 13];
-        let mut n = 0;
-        while n < 13 {
-            n += r.read(&mut buf[n..]).await?;
-        }
+        r.read_exact(&mut buf).await?;
         assert_eq!(&b"Hello, World!"[..], &buf);
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_we_read() -> io::Result<()> {
-        let (r, mut w) = anon_pipe_we_read()?;
-
-        let mut r = r.connect().await?;
+    async fn test_read_into_ptr() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
         w.write_all(b"Hello, World!").await?;
-        let mut buf = vec![0; 13];
+
+        let mut region = Vec::<u8>::with_capacity(32);
         let mut n = 0;
         while n < 13 {
-            n += r.read(&mut buf[n..]).await?;
+            n += unsafe { r.read_into_ptr(region.as_mut_ptr().add(n), 32 - n).await? };
         }
+        unsafe { region.set_len(n) };
+        assert_eq!(&b"Hello, World!"[..], &region);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_graceful() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"Hello, World!").await?;
+        let close = tokio::spawn(w.close_graceful());
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!close.is_finished());
+
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
         assert_eq!(&b"Hello, World!"[..], &buf);
+        close.await.unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_graceful_reader_gone() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"Hello, World!").await?;
+        drop(r);
+        let err = w.close_graceful().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_and_wait() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        let reader = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            Ok::<_, io::Error>(buf)
+        });
+        w.write_all(b"Hello, World!").await?;
+        w.shutdown_and_wait(Some(std::time::Duration::from_secs(10)))
+            .await?;
+        assert_eq!(&reader.await.unwrap()?, b"Hello, World!");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_and_wait_reader_gone() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        let reader = tokio::spawn(async move {
+            let mut buf = [0; 5];
+            r.read_exact(&mut buf).await?;
+            Ok::<_, io::Error>(())
+        });
+        w.write_all(b"Hello, World!").await?;
+        reader.await.unwrap()?;
+        let err = w
+            .shutdown_and_wait(Some(std::time::Duration::from_secs(10)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_and_wait_timeout() -> io::Result<()> {
+        let (_r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"Hello, World!").await?;
+        let err = w
+            .shutdown_and_wait(Some(std::time::Duration::from_millis(100)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_message() -> io::Result<()> {
+        #[cfg(windows)]
+        use tokio::net::windows::named_pipe::PipeMode;
+
+        let (mut r, mut w) = AnonPipeBuilder::new()
+            .configure_server(|opts| {
+                opts.pipe_mode(PipeMode::Message);
+            })
+            .anon_pipe()
+            .await?;
+        assert!(r.pipe_info()?.is_message_type());
+
+        w.send_message(b"one").await?;
+        w.send_message(b"three").await?;
+        let mut buf = [0; 16];
+        let n = r.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"one");
+        let n = r.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"three");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_writable_n() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+
+        tokio::time::timeout(std::time::Duration::from_secs(1), w.writable_n(16)).await??;
+        drop(r);
+        let result =
+            tokio::time::timeout(std::time::Duration::from_secs(1), w.writable_n(usize::MAX))
+                .await?;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_is_connected() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        assert!(r.is_connected()?);
+        assert!(w.is_connected()?);
+        drop(w);
+        assert!(!r.is_connected()?);
+
+        let (r, w) = anon_pipe().await?;
+        drop(r);
+        assert!(!w.is_connected()?);
+
+        // server write end.
+        let (r, w) = AnonPipeBuilder::new().anon_pipe_we_write()?;
+        let w = w.connect().await?;
+        assert!(w.is_connected()?);
+        drop(r);
+        assert!(!w.is_connected()?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_is_connected_listening() -> io::Result<()> {
+        let (listener, name) = AnonPipeListener::bind()?;
+        let instance = listener.add_instance()?;
+        assert!(!instance.get_ref().is_connected()?);
+
+        let client = connect_by_name(&name).await?;
+        let server = instance.connect().await?;
+        assert!(server.is_connected()?);
+        assert!(client.is_connected()?);
+        drop(client);
+        assert!(!server.is_connected()?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_peek_nonblocking() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        let peek = |r: &AnonPipeRead| {
+            std::thread::scope(|s| {
+                s.spawn(|| {
+                    let mut buf = [0; 16];
+                    r.peek_nonblocking(&mut buf).map(|n| buf[..n].to_vec())
+                })
+                .join()
+                .unwrap()
+            })
+        };
+        assert_eq!(peek(&r)?, b"");
+
+        // the reader is idle, nothing takes the data out of the buffer.
+        w.write_all(b"healthy").await?;
+        assert_eq!(peek(&r)?, b"healthy");
+        drop(w);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"healthy");
+
+        let err = peek(&r).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"Hello").await?;
+        w.close().await?;
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"Hello");
+        r.close().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_reader_gone() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+
+        r.close().await?;
+        let _ = w.write_all(b"Hello").await;
+        let err = w.close().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_pending_read() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        // leaves a read in flight.
+        let mut buf = [0; 16];
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(50), r.read(&mut buf))
+                .await
+                .is_err()
+        );
+        r.close().await?;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let err = w.write_all(b"Hello").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_forceful() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"Hello").await?;
+        w.close_forceful();
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert!(buf.len() <= 5);
         Ok(())
     }
 
@@ -486,4 +4189,31 @@ mod tests {
 
         drop(r)
     }
+
+    #[tokio::test]
+    async fn test_eof_after_graceful_shutdown() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        w.write_all(b"bye").await?;
+        w.shutdown().await?;
+        drop(w);
+
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&b"bye"[..], &buf);
+        assert_eq!(r.read(&mut [0; 1]).await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_eof_after_drop_without_shutdown() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        w.write_all(b"bye").await?;
+        drop(w);
+
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&b"bye"[..], &buf);
+        assert_eq!(r.read(&mut [0; 1]).await?, 0);
+        Ok(())
+    }
 }
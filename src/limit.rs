@@ -0,0 +1,127 @@
+//! Process-wide limit of concurrent pipe creations.
+use std::sync::{Condvar, Mutex, OnceLock, PoisonError};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::defaults;
+
+/// Default of
+/// [`AnonPipeDefaults::max_concurrent_creations`](crate::AnonPipeDefaults::max_concurrent_creations).
+pub const DEFAULT_MAX_CONCURRENT_CREATIONS: usize = 16;
+
+static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+// Blocking waiters only, the async ones queue on the semaphore.
+static WAITING: Mutex<()> = Mutex::new(());
+static RELEASED: Condvar = Condvar::new();
+
+/// Sized by the limit when first used, the defaults are set before.
+fn permits() -> &'static Semaphore {
+    PERMITS.get_or_init(|| Semaphore::new(defaults::max_concurrent_creations()))
+}
+
+/// Permit to create server ends, released on drop.
+#[derive(Debug)]
+pub(crate) struct Permit(Option<SemaphorePermit<'static>>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        drop(self.0.take());
+        // under the lock, so that a blocking waiter can not miss it between
+        // trying and waiting.
+        let _waiting = WAITING.lock().unwrap_or_else(PoisonError::into_inner);
+        RELEASED.notify_all();
+    }
+}
+
+/// Wait until less than the limit of creations are in progress, blocking
+/// the thread.
+///
+/// For the constructors which are not async, the others wait with
+/// [`acquire_async`].
+pub(crate) fn acquire() -> Permit {
+    let mut waiting = WAITING.lock().unwrap_or_else(PoisonError::into_inner);
+    loop {
+        // never closed, fails only without permits left.
+        if let Ok(permit) = permits().try_acquire() {
+            return Permit(Some(permit));
+        }
+        waiting = RELEASED
+            .wait(waiting)
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+/// Wait until less than the limit of creations are in progress, yielding
+/// to the runtime meanwhile.
+pub(crate) async fn acquire_async() -> Permit {
+    let permit = permits().acquire().await.expect("never closed");
+    Permit(Some(permit))
+}
+
+/// A permit if less than the limit of creations are in progress right now.
+pub(crate) fn try_acquire() -> Option<Permit> {
+    permits()
+        .try_acquire()
+        .ok()
+        .map(|permit| Permit(Some(permit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_acquire() {
+        let current = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        let threads = (0..DEFAULT_MAX_CONCURRENT_CREATIONS * 4)
+            .map(|_| {
+                let current = current.clone();
+                let max = max.clone();
+                std::thread::spawn(move || {
+                    let _permit = acquire();
+                    let n = current.fetch_add(1, Ordering::SeqCst) + 1;
+                    max.fetch_max(n, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(10));
+                    current.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        // other tests may hold permits too.
+        assert!(max.load(Ordering::SeqCst) <= defaults::max_concurrent_creations());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_acquire_async() {
+        // waiting must not block the only worker, the holders could never
+        // release then.
+        let current = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        let tasks = (0..DEFAULT_MAX_CONCURRENT_CREATIONS * 4)
+            .map(|_| {
+                let current = current.clone();
+                let max = max.clone();
+                tokio::spawn(async move {
+                    let _permit = acquire_async().await;
+                    let n = current.fetch_add(1, Ordering::SeqCst) + 1;
+                    max.fetch_max(n, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    current.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect::<Vec<_>>();
+        // a blocking waiter shares the limit.
+        let blocking = tokio::task::spawn_blocking(|| drop(acquire()));
+        for task in tasks {
+            task.await.unwrap();
+        }
+        blocking.await.unwrap();
+        assert!(max.load(Ordering::SeqCst) <= defaults::max_concurrent_creations());
+    }
+}
@@ -0,0 +1,253 @@
+//! Listener handing out a private duplex channel per client.
+use std::sync::Mutex;
+
+use tokio::io;
+
+use crate::security::SecurityDescriptor;
+use crate::server::new_instance;
+use crate::{
+    audit, connect_server, defaults, limit, security_descriptor, try_new_server_with, Access,
+    AnonPipeBuilder, AnonPipeDuplex, Connect, Deadline, Error, NamedPipeServer,
+};
+
+/// Maximum number of instances tokio accepts, short of unlimited.
+const MAX_INSTANCES: usize = 254;
+
+/// Listener accepting concurrent clients on one name.
+///
+/// Created by [`AnonPipeListener::bind`] or [`AnonPipeBuilder::listener`].
+/// Clients connect with [`connect_by_name`](crate::connect_by_name), and
+/// each gets a private [`AnonPipeDuplex`] with the listener side.
+///
+/// A fresh instance is prepared as soon as the previous one is taken by
+/// [`accept`](Self::accept), so a client connecting meanwhile does not
+/// find the pipe busy. Unless set on the builder, up to 254 instances
+/// are allowed.
+#[derive(Debug)]
+pub struct AnonPipeListener {
+    builder: AnonPipeBuilder,
+    sd: Option<SecurityDescriptor>,
+    name: String,
+    next: Mutex<Option<NamedPipeServer>>,
+}
+
+impl AnonPipeListener {
+    /// Bind to a random name with default options, returning the name
+    /// clients connect to.
+    pub fn bind() -> io::Result<(Self, String)> {
+        let listener = AnonPipeBuilder::new().listener()?;
+        let name = listener.name.clone();
+        Ok((listener, name))
+    }
+
+    pub(crate) fn new(builder: &AnonPipeBuilder) -> io::Result<Self> {
+        let mut builder = builder.clone();
+        if defaults::max_instances(builder.max_instances).is_none() {
+            builder.max_instances(MAX_INSTANCES);
+        }
+        let sd = security_descriptor(&builder)?;
+        let _permit = limit::acquire();
+        let (name, server) =
+            try_new_server_with(&builder, sd.as_ref(), Access::Duplex, builder.deadline())?;
+        Ok(Self {
+            builder,
+            sd,
+            name,
+            next: Mutex::new(Some(server)),
+        })
+    }
+
+    /// Name clients connect to.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Wait for the next client and return the connected duplex.
+    ///
+    /// May be called concurrently. Not cancel safe: a client connecting
+    /// while cancelled is disconnected.
+    pub async fn accept(&self) -> io::Result<AnonPipeDuplex> {
+        // covers both instances created below.
+        let permit = limit::acquire_async().await;
+        let server = {
+            let mut next = self.next.lock().unwrap();
+            let server = match next.take() {
+                Some(server) => server,
+                None => self.new_instance()?,
+            };
+            // Otherwise the instance is created on the next call.
+            *next = self.new_instance().ok();
+            server
+        };
+        drop(permit);
+        connect_server(&server, Deadline::default())
+            .await
+            .map_err(Error::Connect)?;
+        Ok(audit::track(
+            AnonPipeDuplex::Server(server),
+            Some(&self.name),
+        ))
+    }
+
+    /// Create another instance of the name, to connect outside of
+    /// [`accept`](Self::accept), e.g. a diagnostic tap.
+    ///
+    /// The instance has the options and security of the listener and
+    /// counts against its maximum number of instances. Wait for a client
+    /// with [`connect`](Connect::connect).
+    pub fn add_instance(&self) -> io::Result<Connect<AnonPipeDuplex>> {
+        let _permit = limit::acquire();
+        let server = AnonPipeDuplex::Server(self.new_instance()?);
+        Ok(Connect(audit::track(server, Some(&self.name))))
+    }
+
+    fn new_instance(&self) -> io::Result<NamedPipeServer> {
+        let sd = self.sd.as_ref();
+        new_instance(&self.builder, sd, &self.name, Access::Duplex)
+            .map_err(|err| Error::CreateServer(err).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{connect_by_name, NameError};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_listener() -> io::Result<()> {
+        let (listener, name) = AnonPipeListener::bind()?;
+        let listener = Arc::new(listener);
+        assert_eq!(listener.name(), name);
+
+        let server = tokio::spawn({
+            let listener = listener.clone();
+            async move {
+                for _ in 0..10 {
+                    let mut pipe = listener.accept().await?;
+                    tokio::spawn(async move {
+                        let mut id = [0; 1];
+                        pipe.read_exact(&mut id).await?;
+                        pipe.write_all(&[id[0]; 100]).await?;
+                        Ok::<_, io::Error>(())
+                    });
+                }
+                Ok::<_, io::Error>(())
+            }
+        });
+
+        let clients = (0..10u8)
+            .map(|id| {
+                let name = name.clone();
+                tokio::spawn(async move {
+                    let mut pipe = connect_by_name(&name).await?;
+                    pipe.write_all(&[id]).await?;
+                    let mut buf = vec![];
+                    pipe.read_to_end(&mut buf).await?;
+                    assert_eq!(buf, vec![id; 100]);
+                    Ok::<_, io::Error>(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for client in clients {
+            client.await.unwrap()?;
+        }
+        server.await.unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_instance() -> io::Result<()> {
+        let (listener, name) = AnonPipeListener::bind()?;
+        let tap = listener.add_instance()?;
+        let accepted = tokio::spawn(async move { listener.accept().await });
+        let tapped = tokio::spawn(tap.connect());
+
+        let mut clients = [connect_by_name(&name).await?, connect_by_name(&name).await?];
+        let mut servers = [accepted.await.unwrap()?, tapped.await.unwrap()?];
+        for (tag, server) in servers.iter_mut().enumerate() {
+            server.write_all(&[tag as u8]).await?;
+        }
+        let mut tags = vec![];
+        for client in &mut clients {
+            let mut tag = [0; 1];
+            client.read_exact(&mut tag).await?;
+            client.write_all(&[tag[0], b'!']).await?;
+            tags.push(tag[0]);
+        }
+        tags.sort_unstable();
+        assert_eq!(tags, [0, 1]);
+        for (tag, server) in servers.iter_mut().enumerate() {
+            let mut reply = [0; 2];
+            server.read_exact(&mut reply).await?;
+            assert_eq!(reply, [tag as u8, b'!']);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_instance_exhausted() -> io::Result<()> {
+        let listener = AnonPipeBuilder::new().max_instances(1).listener()?;
+        let err = listener.add_instance().unwrap_err();
+        let err = err.get_ref().and_then(|e| e.downcast_ref::<Error>());
+        assert!(matches!(err, Some(Error::CreateServer(..))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instance_counts() -> io::Result<()> {
+        let listener = Arc::new(AnonPipeBuilder::new().max_instances(3).listener()?);
+        let mut servers = vec![];
+        let mut clients = vec![];
+        for _ in 0..2 {
+            let accepted = tokio::spawn({
+                let listener = listener.clone();
+                async move { listener.accept().await }
+            });
+            clients.push(connect_by_name(listener.name()).await?);
+            servers.push(accepted.await.unwrap()?);
+        }
+
+        for server in &servers {
+            assert_eq!(server.max_instances()?, 3);
+            // plus the instances waiting for the next clients.
+            assert!(server.current_instances()? >= 2);
+        }
+        let err = clients[0].current_instances().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = clients[0].max_instances().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_client_wait() -> io::Result<()> {
+        let listener = AnonPipeBuilder::new().max_instances(1).listener()?;
+        // takes the only instance.
+        let _client = connect_by_name(listener.name()).await?;
+
+        let wait = Duration::from_millis(200);
+        let started = Instant::now();
+        let err = AnonPipeBuilder::new()
+            .default_client_wait(wait)
+            .connect_by_name(listener.name())
+            .await
+            .unwrap_err();
+        let elapsed = started.elapsed();
+        let err = err.get_ref().and_then(|e| e.downcast_ref::<Error>());
+        assert!(matches!(err, Some(Error::OpenClient(..))));
+        assert!(elapsed >= wait - Duration::from_millis(20), "{:?}", elapsed);
+        assert!(elapsed < wait * 3, "{:?}", elapsed);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connect_by_invalid_name() {
+        let err = connect_by_name(r"\\.\pipe\a\b").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = err.get_ref().and_then(|e| e.downcast_ref::<NameError>());
+        assert_eq!(err, Some(&NameError::Backslash { offset: 10 }));
+    }
+}
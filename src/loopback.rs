@@ -0,0 +1,210 @@
+//! Echo utility for tests.
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::{AnonPipeRead, AnonPipeWrite};
+
+/// Echo everything read from a read half back into a write half.
+///
+/// Turns two unidirectional pairs into an echo target:
+///
+/// ```no_run
+/// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+/// use tokio_anon_pipe::{anon_pipe, Loopback};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let (r1, mut w1) = anon_pipe().await?;
+/// let (mut r2, w2) = anon_pipe().await?;
+/// let loopback = Loopback::new(r1, w2).spawn();
+///
+/// w1.write_all(b"ping").await?;
+/// let mut buf = [0; 4];
+/// r2.read_exact(&mut buf).await?;
+/// assert_eq!(&buf, b"ping");
+/// loopback.shutdown().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Loopback {
+    read: AnonPipeRead,
+    write: AnonPipeWrite,
+    latency: Duration,
+    chunk_size: usize,
+}
+
+impl Loopback {
+    /// Echo from `read` into `write`.
+    pub fn new(read: AnonPipeRead, write: AnonPipeWrite) -> Self {
+        Self {
+            read,
+            write,
+            latency: Duration::from_secs(0),
+            chunk_size: 8 * 1024,
+        }
+    }
+
+    /// Delay each chunk before echoing it. Default is no delay.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Maximum bytes read and echoed at once. Default is 8 KiB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is `0`.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk_size must be positive");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Start echoing on a spawned task. Must be called within a tokio
+    /// runtime.
+    pub fn spawn(self) -> LoopbackHandle {
+        let echoed = Arc::new(AtomicU64::new(0));
+        let shutdown = Arc::new(Notify::new());
+        let task = tokio::spawn(self.run(echoed.clone(), shutdown.clone()));
+        LoopbackHandle {
+            echoed,
+            shutdown,
+            task,
+        }
+    }
+
+    async fn run(mut self, echoed: Arc<AtomicU64>, shutdown: Arc<Notify>) -> io::Result<u64> {
+        let mut buf = vec![0; self.chunk_size];
+        let notified = shutdown.notified();
+        tokio::pin!(notified);
+        loop {
+            let read = self.read.read(&mut buf);
+            tokio::pin!(read);
+            let n = std::future::poll_fn(|cx| {
+                if notified.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(None);
+                }
+                read.as_mut().poll(cx).map(Some)
+            })
+            .await;
+            let n = match n {
+                Some(n) => n?,
+                None => break, // shutdown requested.
+            };
+            if n == 0 {
+                break;
+            }
+            if self.latency > Duration::from_secs(0) {
+                tokio::time::sleep(self.latency).await;
+            }
+            self.write.write_all(&buf[..n]).await?;
+            echoed.fetch_add(n as u64, Ordering::SeqCst);
+        }
+        self.write.shutdown().await?;
+        Ok(echoed.load(Ordering::SeqCst))
+    }
+}
+
+/// Handle of a running [`Loopback`].
+///
+/// The write half is dropped, i.e. the peer observes EOF, once the read
+/// half reached EOF, on [`shutdown`](Self::shutdown), or on an IO error.
+/// Dropping the handle leaves the loopback running.
+#[derive(Debug)]
+pub struct LoopbackHandle {
+    echoed: Arc<AtomicU64>,
+    shutdown: Arc<Notify>,
+    task: JoinHandle<io::Result<u64>>,
+}
+
+impl LoopbackHandle {
+    /// Number of bytes echoed so far.
+    pub fn echoed(&self) -> u64 {
+        self.echoed.load(Ordering::SeqCst)
+    }
+
+    /// Stop echoing once the current chunk is written, and wait for it.
+    ///
+    /// Returns the total number of bytes echoed, or the IO error which
+    /// stopped the loopback earlier.
+    pub async fn shutdown(self) -> io::Result<u64> {
+        self.shutdown.notify_one();
+        self.join().await
+    }
+
+    /// Wait until the read half reached EOF (or an IO error).
+    ///
+    /// Returns the total number of bytes echoed.
+    pub async fn join(self) -> io::Result<u64> {
+        match self.task.await {
+            Ok(result) => result,
+            Err(err) => Err(io::Error::other(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+
+    #[tokio::test]
+    async fn test_loopback() -> io::Result<()> {
+        let (r1, mut w1) = anon_pipe().await?;
+        let (mut r2, w2) = anon_pipe().await?;
+        let loopback = Loopback::new(r1, w2)
+            .latency(Duration::from_millis(10))
+            .chunk_size(3)
+            .spawn();
+
+        for request in [&b"ping"[..], b"Hello, World!"] {
+            w1.write_all(request).await?;
+            let mut response = vec![0; request.len()];
+            r2.read_exact(&mut response).await?;
+            assert_eq!(response, request);
+        }
+        assert_eq!(loopback.echoed(), 17);
+
+        drop(w1);
+        let mut rest = vec![];
+        r2.read_to_end(&mut rest).await?;
+        assert!(rest.is_empty());
+        assert_eq!(loopback.join().await?, 17);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_loopback_shutdown() -> io::Result<()> {
+        let (r1, _w1) = anon_pipe().await?;
+        let (mut r2, w2) = anon_pipe().await?;
+        let loopback = Loopback::new(r1, w2).spawn();
+
+        assert_eq!(loopback.shutdown().await?, 0);
+        let mut rest = vec![];
+        r2.read_to_end(&mut rest).await?;
+        assert!(rest.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_loopback_error() -> io::Result<()> {
+        let (r1, mut w1) = anon_pipe().await?;
+        let (r2, w2) = anon_pipe().await?;
+        let loopback = Loopback::new(r1, w2).spawn();
+
+        drop(r2);
+        w1.write_all(b"ping").await?;
+        let err = loopback.join().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+//! Length-prefixed messages.
+//!
+//! Each message is a 4-byte little-endian length followed by the payload.
+use std::convert::TryFrom;
+
+use tokio::io::{self, AsyncRead, AsyncReadExt};
+
+/// Read the length header, `None` on EOF before its first byte.
+pub(crate) async fn read_len<R>(r: &mut R) -> io::Result<Option<u32>>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut header = [0; 4];
+    let mut n = 0;
+    while n < header.len() {
+        match r.read(&mut header[n..]).await? {
+            0 if n == 0 => return Ok(None),
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            len => n += len,
+        }
+    }
+    Ok(Some(u32::from_le_bytes(header)))
+}
+
+/// Read one message, `None` on EOF at a message boundary.
+pub(crate) async fn read_msg<R>(r: &mut R, max_len: usize) -> io::Result<Option<Vec<u8>>>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let len = match read_len(r).await? {
+        Some(len) => len as usize,
+        None => return Ok(None),
+    };
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message length {} exceeds {}", len, max_len),
+        ));
+    }
+    let mut msg = vec![0; len];
+    r.read_exact(&mut msg).await?;
+    Ok(Some(msg))
+}
+
+/// Length header for `msg`.
+pub(crate) fn header(msg: &[u8]) -> io::Result<[u8; 4]> {
+    let len = u32::try_from(msg.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("message length {} exceeds {}", msg.len(), u32::MAX),
+        )
+    })?;
+    Ok(len.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anon_pipe;
+    use tokio::io::{self, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_msg() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        let max = vec![0xaa; 1024];
+
+        w.send_msg(b"").await?;
+        w.send_msg(b"Hello, World!").await?;
+        w.send_msg(&max).await?;
+        assert_eq!(r.recv_msg(1024).await?, b"");
+        assert_eq!(r.recv_msg(1024).await?, b"Hello, World!");
+        assert_eq!(r.recv_msg(1024).await?, max);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_msg_split_header() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        let recv = tokio::spawn(async move { r.recv_msg(16).await });
+        w.write_all(&[5, 0]).await?;
+        tokio::task::yield_now().await;
+        w.write_all(&[0, 0]).await?;
+        tokio::task::yield_now().await;
+        w.write_all(b"hello").await?;
+        assert_eq!(recv.await.unwrap()?, b"hello");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_msg_oversize() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.send_msg(&[0; 17]).await?;
+        let err = r.recv_msg(16).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_msg_eof() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(&[5, 0, 0, 0, b'h']).await?;
+        drop(w);
+        let err = r.recv_msg(16).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+//! Names of the pipes created by this crate.
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Local pipe namespace, which every name starts with.
+pub(crate) const NAMESPACE: &str = r"\\.\pipe\";
+
+/// Most UTF-16 code units of a pipe name after `\\.\pipe\`.
+pub const MAX_PIPE_NAME_LEN: usize = 256;
+
+/// Marks the names generated by this crate, followed by
+/// `{pid}.{thread id}.{counter}.{random hex}`.
+pub(crate) const MARKER: &str = "__tokio_anonymous_pipe0__.";
+
+/// Parts of a pipe name generated by this crate.
+///
+/// Returned by [`parse_pipe_name`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipeNameParts {
+    pid: u32,
+    thread_id: u32,
+    counter: u64,
+}
+
+impl PipeNameParts {
+    /// Id of the process which created the pipe.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Id of the thread which created the pipe.
+    pub fn thread_id(&self) -> u32 {
+        self.thread_id
+    }
+
+    /// Number of names generated before by the process.
+    pub fn counter(&self) -> u64 {
+        self.counter
+    }
+}
+
+/// Parse a pipe name generated by this crate, to attribute it to the
+/// process which created it.
+///
+/// Accepts the name with any prefix, e.g. `\\.\pipe\` or none as listed
+/// when enumerating `\\.\pipe\*`. Returns `None` for foreign names.
+///
+/// ```
+/// use tokio_anon_pipe::parse_pipe_name;
+///
+/// let parts = parse_pipe_name(
+///     r"\\.\pipe\__tokio_anonymous_pipe0__.1234.5678.9.0123456789abcdef0123456789abcdef",
+/// )
+/// .unwrap();
+/// assert_eq!(parts.pid(), 1234);
+/// assert_eq!(parts.thread_id(), 5678);
+/// assert!(parse_pipe_name(r"\\.\pipe\mojo.1234.5678").is_none());
+/// ```
+pub fn parse_pipe_name(name: &str) -> Option<PipeNameParts> {
+    let rest = &name[name.find(MARKER)? + MARKER.len()..];
+    let mut fields = rest.split('.');
+    let pid = fields.next()?.parse().ok()?;
+    let thread_id = fields.next()?.parse().ok()?;
+    let counter = fields.next()?.parse().ok()?;
+    let random = fields.next()?;
+    if random.is_empty()
+        || !random.bytes().all(|b| b.is_ascii_hexdigit())
+        || fields.next().is_some()
+    {
+        return None;
+    }
+    Some(PipeNameParts {
+        pid,
+        thread_id,
+        counter,
+    })
+}
+
+/// Why a pipe name is rejected by [`validate_pipe_name`].
+///
+/// Converts into an `io::Error` of `ErrorKind::InvalidInput` carrying it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NameError {
+    /// Doesn't start with `\\.\pipe\`.
+    MissingNamespace,
+    /// Nothing follows `\\.\pipe\`.
+    Empty,
+    /// More than [`MAX_PIPE_NAME_LEN`] UTF-16 code units follow
+    /// `\\.\pipe\`.
+    TooLong {
+        /// UTF-16 code units after `\\.\pipe\`.
+        len: usize,
+    },
+    /// Contains a NUL character.
+    Nul {
+        /// Byte offset in the name.
+        offset: usize,
+    },
+    /// Contains a backslash after `\\.\pipe\`.
+    Backslash {
+        /// Byte offset in the name.
+        offset: usize,
+    },
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingNamespace => write!(f, "pipe name must start with {}", NAMESPACE),
+            Self::Empty => write!(f, "pipe name is empty after {}", NAMESPACE),
+            Self::TooLong { len } => write!(
+                f,
+                "pipe name is {} characters after {}, at most {} allowed",
+                len, NAMESPACE, MAX_PIPE_NAME_LEN
+            ),
+            Self::Nul { offset } => write!(f, "pipe name contains NUL at byte {}", offset),
+            Self::Backslash { offset } => {
+                write!(f, "pipe name contains backslash at byte {}", offset)
+            }
+        }
+    }
+}
+
+impl error::Error for NameError {}
+
+impl From<NameError> for io::Error {
+    fn from(err: NameError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+/// Check that `name` is a valid local pipe name, `\\.\pipe\` followed by
+/// 1 to [`MAX_PIPE_NAME_LEN`] characters other than backslash and NUL.
+///
+/// Names are checked before use, so that an invalid one fails with a
+/// precise message instead of an opaque OS error.
+///
+/// ```
+/// use tokio_anon_pipe::{validate_pipe_name, NameError};
+///
+/// assert!(validate_pipe_name(r"\\.\pipe\my-service").is_ok());
+/// assert_eq!(
+///     validate_pipe_name(r"\\.\pipe\my\service"),
+///     Err(NameError::Backslash { offset: 11 }),
+/// );
+/// ```
+pub fn validate_pipe_name(name: &str) -> Result<(), NameError> {
+    if let Some(offset) = name.find('\0') {
+        return Err(NameError::Nul { offset });
+    }
+    let namespace = name.get(..NAMESPACE.len());
+    if !namespace.is_some_and(|ns| ns.eq_ignore_ascii_case(NAMESPACE)) {
+        return Err(NameError::MissingNamespace);
+    }
+    let rest = &name[NAMESPACE.len()..];
+    if rest.is_empty() {
+        return Err(NameError::Empty);
+    }
+    if let Some(offset) = rest.find('\\') {
+        return Err(NameError::Backslash {
+            offset: NAMESPACE.len() + offset,
+        });
+    }
+    let len = rest.encode_utf16().count();
+    if len > MAX_PIPE_NAME_LEN {
+        return Err(NameError::TooLong { len });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_pipe_name() {
+        assert_eq!(validate_pipe_name(r"\\.\pipe\a"), Ok(()));
+        assert_eq!(validate_pipe_name(r"\\.\PIPE\a"), Ok(()));
+        let longest = format!(r"\\.\pipe\{}", "a".repeat(MAX_PIPE_NAME_LEN));
+        assert_eq!(validate_pipe_name(&longest), Ok(()));
+
+        let too_long = format!("{}a", longest);
+        assert_eq!(
+            validate_pipe_name(&too_long),
+            Err(NameError::TooLong { len: 257 })
+        );
+        // counted in UTF-16 code units, as Windows does.
+        let too_long = format!(r"\\.\pipe\{}", "\u{1f980}".repeat(129));
+        assert_eq!(
+            validate_pipe_name(&too_long),
+            Err(NameError::TooLong { len: 258 })
+        );
+        assert_eq!(
+            validate_pipe_name("\\\\.\\pipe\\a\0b"),
+            Err(NameError::Nul { offset: 10 })
+        );
+        assert_eq!(
+            validate_pipe_name(r"\\.\pipe\a\b"),
+            Err(NameError::Backslash { offset: 10 })
+        );
+        assert_eq!(validate_pipe_name(r"\\.\pipe\"), Err(NameError::Empty));
+        for name in [
+            r"my-service",
+            r"\\host\pipe\a",
+            r"\\.\mailslot\a",
+            r"\\.\pip",
+            "",
+        ] {
+            assert_eq!(
+                validate_pipe_name(name),
+                Err(NameError::MissingNamespace),
+                "{}",
+                name
+            );
+        }
+
+        let err = io::Error::from(NameError::Empty);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), r"pipe name is empty after \\.\pipe\");
+    }
+
+    #[test]
+    fn test_parse_pipe_name() {
+        let name = "__tokio_anonymous_pipe0__.1234.5678.9.0123456789abcdef";
+        for prefix in [r"\\.\pipe\", r"\\?\pipe\", r"\\host\pipe\", ""] {
+            let parts = parse_pipe_name(&format!("{}{}", prefix, name)).unwrap();
+            assert_eq!(parts.pid(), 1234);
+            assert_eq!(parts.thread_id(), 5678);
+            assert_eq!(parts.counter(), 9);
+        }
+
+        for name in [
+            r"\\.\pipe\mojo.1234.5678.9.0123",
+            r"\\.\pipe\__tokio_anonymous_pipe0__.",
+            r"\\.\pipe\__tokio_anonymous_pipe0__.1234.5678.9",
+            r"\\.\pipe\__tokio_anonymous_pipe0__.1234.5678.9.",
+            r"\\.\pipe\__tokio_anonymous_pipe0__.1234.x.9.0123",
+            r"\\.\pipe\__tokio_anonymous_pipe0__.1234.5678.9.xyz",
+            r"\\.\pipe\__tokio_anonymous_pipe0__.1234.5678.9.0123.4",
+        ] {
+            assert_eq!(parse_pipe_name(name), None, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Reads and writes owning the end and the buffer.
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+#[cfg(feature = "bytes")]
+use tokio::io::AsyncWrite;
+use tokio::io::{self, AsyncRead, ReadBuf};
+
+use crate::AnonPipeRead;
+#[cfg(feature = "bytes")]
+use crate::{AnonPipeWrite, TimeoutPartial};
+
+/// Future of [`AnonPipeRead::read_owned`].
+///
+/// Resolves to the end, the buffer and the number of bytes filled. Poll
+/// it by reference (it is `Unpin`) to be able to give up on it: the end
+/// and the bytes read so far are then taken back with
+/// [`into_parts`](Self::into_parts). Nothing is lost, every chunk read is
+/// in the buffer and counted before the next poll.
+///
+/// ```no_run
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// use std::time::Duration;
+///
+/// let (r, w) = tokio_anon_pipe::anon_pipe().await?;
+/// let mut read = r.read_owned(vec![0; 1024]);
+/// tokio::select! {
+///     (r, buf, n) = &mut read => { /* ... */ }
+///     _ = tokio::time::sleep(Duration::from_secs(1)) => {
+///         let (r, buf, filled) = read.into_parts();
+///         // ... `buf[..filled]` was read ...
+///     }
+/// }
+/// # drop(w);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReadOwned {
+    inner: Option<AnonPipeRead>,
+    buf: Vec<u8>,
+    filled: usize,
+}
+
+impl AnonPipeRead {
+    /// Read until `buf` is full or EOF, taking the end and `buf` by value.
+    ///
+    /// Unlike `read_exact` into a borrowed buffer, the returned
+    /// [`ReadOwned`] can be given up on without losing track of the bytes
+    /// read: see [`ReadOwned::into_parts`]. Resolves to the number of bytes
+    /// filled, less than `buf.len()` only at EOF.
+    pub fn read_owned(self, buf: Vec<u8>) -> ReadOwned {
+        ReadOwned::new(self, buf)
+    }
+}
+
+impl ReadOwned {
+    fn new(inner: AnonPipeRead, buf: Vec<u8>) -> Self {
+        Self {
+            inner: Some(inner),
+            buf,
+            filled: 0,
+        }
+    }
+
+    /// Number of bytes read into the buffer so far.
+    pub fn filled(&self) -> usize {
+        self.filled
+    }
+
+    /// Give up reading, returning the end, the buffer and the number of
+    /// bytes read into it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the future resolved already.
+    pub fn into_parts(mut self) -> (AnonPipeRead, Vec<u8>, usize) {
+        let inner = self.inner.take().expect("ReadOwned resolved already");
+        (inner, mem::take(&mut self.buf), self.filled)
+    }
+
+    fn finish(&mut self, result: io::Result<usize>) -> <Self as Future>::Output {
+        let inner = self
+            .inner
+            .take()
+            .expect("ReadOwned polled after completion");
+        (inner, mem::take(&mut self.buf), result)
+    }
+}
+
+impl Future for ReadOwned {
+    type Output = (AnonPipeRead, Vec<u8>, io::Result<usize>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let inner = match &mut this.inner {
+            Some(inner) => inner,
+            None => panic!("ReadOwned polled after completion"),
+        };
+        while this.filled < this.buf.len() {
+            let mut buf = ReadBuf::new(&mut this.buf[this.filled..]);
+            match Pin::new(&mut *inner).poll_read(cx, &mut buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(())) if buf.filled().is_empty() => break, // EOF
+                Poll::Ready(Ok(())) => this.filled += buf.filled().len(),
+                Poll::Ready(Err(err)) => return Poll::Ready(this.finish(Err(err))),
+            }
+        }
+        let filled = this.filled;
+        Poll::Ready(this.finish(Ok(filled)))
+    }
+}
+
+/// Future of [`AnonPipeWrite::write_owned`].
+///
+/// Resolves to the end and the result of writing all of the data. Owns
+/// both, so it can be spawned. On failure, [`TimeoutPartial::transferred`]
+/// tells how many bytes at the start of the data the reader may observe.
+#[cfg(feature = "bytes")]
+#[derive(Debug)]
+pub struct WriteOwned {
+    inner: Option<AnonPipeWrite>,
+    data: Bytes,
+    written: usize,
+}
+
+#[cfg(feature = "bytes")]
+impl AnonPipeWrite {
+    /// Write all of `data`, taking the end and `data` by value.
+    ///
+    /// The returned [`WriteOwned`] borrows nothing, so it can be spawned,
+    /// and hands the end back once done.
+    pub fn write_owned(self, data: Bytes) -> WriteOwned {
+        WriteOwned::new(self, data)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl WriteOwned {
+    fn new(inner: AnonPipeWrite, data: Bytes) -> Self {
+        Self {
+            inner: Some(inner),
+            data,
+            written: 0,
+        }
+    }
+
+    fn finish(&mut self, result: Result<(), TimeoutPartial>) -> <Self as Future>::Output {
+        let inner = self
+            .inner
+            .take()
+            .expect("WriteOwned polled after completion");
+        (inner, result)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Future for WriteOwned {
+    type Output = (AnonPipeWrite, Result<(), TimeoutPartial>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let inner = match &mut this.inner {
+            Some(inner) => inner,
+            None => panic!("WriteOwned polled after completion"),
+        };
+        while this.written < this.data.len() {
+            let err = match Pin::new(&mut *inner).poll_write(cx, &this.data[this.written..]) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => io::ErrorKind::WriteZero.into(),
+                Poll::Ready(Ok(n)) => {
+                    this.written += n;
+                    continue;
+                }
+                Poll::Ready(Err(err)) => err,
+            };
+            let err = TimeoutPartial::new(this.written, err);
+            return Poll::Ready(this.finish(Err(err)));
+        }
+        Poll::Ready(this.finish(Ok(())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_read_owned() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        w.write_all(b"Hello, World!").await?;
+
+        let (r, buf, n) = r.read_owned(vec![0; 5]).await;
+        assert_eq!(n?, 5);
+        assert_eq!(&buf, b"Hello");
+
+        drop(w);
+        let (_, buf, n) = r.read_owned(vec![0; 100]).await;
+        // stops at EOF.
+        assert_eq!(n?, 8);
+        assert_eq!(&buf[..8], b", World!");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_owned_cancelled() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let mut read = r.read_owned(vec![0; 10]);
+
+        w.write_all(b"Hello").await?;
+        tokio::select! {
+            _ = &mut read => panic!("read completed"),
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+        assert_eq!(read.filled(), 5);
+        let (r, buf, filled) = read.into_parts();
+        assert_eq!(&buf[..filled], b"Hello");
+
+        // the end resumes after the recovered bytes.
+        w.write_all(b"World").await?;
+        let (_, buf, n) = r.read_owned(vec![0; 5]).await;
+        assert_eq!(n?, 5);
+        assert_eq!(&buf, b"World");
+        Ok(())
+    }
+
+    #[cfg(feature = "bytes")]
+    #[tokio::test]
+    async fn test_write_owned() -> io::Result<()> {
+        use tokio::io::AsyncReadExt;
+
+        let (mut r, mut w) = anon_pipe().await?;
+        for chunk in [&b"Hello"[..], b", ", b"World!"] {
+            let (end, result) = tokio::spawn(w.write_owned(Bytes::from_static(chunk)))
+                .await
+                .unwrap();
+            result?;
+            w = end;
+        }
+        drop(w);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"Hello, World!");
+        Ok(())
+    }
+
+    #[cfg(feature = "bytes")]
+    #[tokio::test]
+    async fn test_write_owned_broken() -> io::Result<()> {
+        use tokio::io::AsyncReadExt;
+
+        let (mut r, w) = anon_pipe().await?;
+        let len = 1024 * 1024;
+        let writing = tokio::spawn(w.write_owned(Bytes::from(vec![0; len])));
+        let mut buf = [0; 1000];
+        r.read_exact(&mut buf).await?;
+        drop(r);
+
+        let (_, result) = writing.await.unwrap();
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        assert!(err.transferred() >= 1000);
+        assert!(err.transferred() < len);
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+//! Pair assembled from a server and a client created elsewhere.
+use crate::{audit, AnonPipeRead, AnonPipeWrite, NamedPipeClient, NamedPipeServer};
+
+/// Direction the data flows in, relative to the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The client writes, the server reads (`PIPE_ACCESS_INBOUND`).
+    Inbound,
+    /// The server writes, the client reads (`PIPE_ACCESS_OUTBOUND`).
+    Outbound,
+}
+
+/// Assemble a pair from a server and a client of the same pipe, created
+/// and connected elsewhere.
+///
+/// `direction` tells which one reads. The ends are taken as they are:
+/// nothing checks that they belong to the same pipe, are connected, or
+/// were opened with the access `direction` needs.
+pub fn from_parts(
+    server: NamedPipeServer,
+    client: NamedPipeClient,
+    direction: Direction,
+) -> (AnonPipeRead, AnonPipeWrite) {
+    let (read, write) = match direction {
+        Direction::Inbound => (AnonPipeRead::Server(server), AnonPipeWrite::Client(client)),
+        Direction::Outbound => (AnonPipeRead::Client(client), AnonPipeWrite::Server(server)),
+    };
+    (audit::track(read, None), audit::track(write, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{genname, ClientOptions, ServerOptions};
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+
+    async fn connected(direction: Direction) -> io::Result<(NamedPipeServer, NamedPipeClient)> {
+        let name = genname();
+        let inbound = direction == Direction::Inbound;
+        let server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .access_inbound(inbound)
+            .access_outbound(!inbound)
+            .create(&name)?;
+        let client = ClientOptions::new()
+            .read(!inbound)
+            .write(inbound)
+            .open(&name)?;
+        server.connect().await?;
+        Ok((server, client))
+    }
+
+    #[tokio::test]
+    async fn test_from_parts() -> io::Result<()> {
+        for direction in [Direction::Inbound, Direction::Outbound] {
+            let (server, client) = connected(direction).await?;
+            let (mut r, mut w) = from_parts(server, client, direction);
+            assert_eq!(
+                matches!(r, AnonPipeRead::Server(_)),
+                direction == Direction::Inbound
+            );
+
+            w.write_all(b"Hello").await?;
+            drop(w);
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            assert_eq!(&buf, b"Hello");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+//! Read half with look-ahead.
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{self, AsyncRead, AsyncReadExt};
+
+use crate::{AnonPipeRead, AsRawHandle, RawHandle};
+
+/// Default maximum number of bytes [`PeekableRead::peek_exact`] accepts.
+pub const DEFAULT_MAX_PEEK: usize = 64 * 1024;
+
+/// Read half which can look ahead without consuming.
+///
+/// Created by [`AnonPipeRead::peekable`].
+///
+/// The pipe itself cannot be peeked reliably: the completion port driver
+/// keeps a read in flight and moves arriving data out of the pipe before
+/// `PeekNamedPipe` could see it. So peeked bytes are kept here instead, and
+/// handed out again by subsequent reads.
+#[derive(Debug)]
+pub struct PeekableRead {
+    inner: AnonPipeRead,
+    buf: Vec<u8>,
+    pos: usize,
+    max_peek: usize,
+}
+
+impl PeekableRead {
+    pub(crate) fn new(inner: AnonPipeRead) -> Self {
+        Self {
+            inner,
+            buf: vec![],
+            pos: 0,
+            max_peek: DEFAULT_MAX_PEEK,
+        }
+    }
+
+    /// Set maximum number of bytes to peek. Default is [`DEFAULT_MAX_PEEK`].
+    pub fn max_peek(mut self, max_peek: usize) -> Self {
+        self.max_peek = max_peek;
+        self
+    }
+
+    /// Wait until `buf.len()` bytes arrived, and copy them without
+    /// consuming.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if `buf` is longer than the
+    /// maximum peek size, and with `ErrorKind::UnexpectedEof` if the writer
+    /// closes first. Bytes received so far stay buffered in both cases.
+    pub async fn peek_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        if buf.len() > self.max_peek {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("peek length {} exceeds {}", buf.len(), self.max_peek),
+            ));
+        }
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        while self.buf.len() < buf.len() {
+            let filled = self.buf.len();
+            self.buf.resize(buf.len(), 0);
+            let result = self.inner.read(&mut self.buf[filled..]).await;
+            self.buf.truncate(filled + *result.as_ref().unwrap_or(&0));
+            if result? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        buf.copy_from_slice(&self.buf[..buf.len()]);
+        Ok(())
+    }
+
+    /// Bytes peeked and not read yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    /// Gets a reference to the underlying read half.
+    pub fn get_ref(&self) -> &AnonPipeRead {
+        &self.inner
+    }
+
+    /// Unwrap the underlying read half, with bytes peeked and not read yet.
+    pub fn into_parts(mut self) -> (AnonPipeRead, Vec<u8>) {
+        self.buf.drain(..self.pos);
+        (self.inner, self.buf)
+    }
+}
+
+impl AsyncRead for PeekableRead {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.pos < this.buf.len() {
+            let n = buf.remaining().min(this.buf.len() - this.pos);
+            buf.put_slice(&this.buf[this.pos..this.pos + n]);
+            this.pos += n;
+            if this.pos == this.buf.len() {
+                this.buf.clear();
+                this.pos = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsRawHandle for PeekableRead {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.inner.as_raw_handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_peek_exact() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let mut r = r.peekable();
+
+        let writer = tokio::spawn(async move {
+            for chunk in [&b"HE"[..], b"AD", b"ER", b"!!", b"body"] {
+                w.write_all(chunk).await?;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok::<_, io::Error>(())
+        });
+
+        let mut header = [0; 8];
+        r.peek_exact(&mut header).await?;
+        assert_eq!(&header, b"HEADER!!");
+        r.peek_exact(&mut header[..4]).await?;
+        assert_eq!(&header[..4], b"HEAD");
+
+        writer.await.unwrap()?;
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"HEADER!!body");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_peek_exact_eof() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let mut r = r.peekable();
+
+        w.write_all(b"HEAD").await?;
+        drop(w);
+        let mut header = [0; 8];
+        let err = r.peek_exact(&mut header).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let (_, rest) = r.into_parts();
+        assert_eq!(&rest, b"HEAD");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_peek_exact_too_long() -> io::Result<()> {
+        let (r, _w) = anon_pipe().await?;
+        let mut r = r.peekable().max_peek(4);
+
+        let mut header = [0; 8];
+        let err = r.peek_exact(&mut header).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+}
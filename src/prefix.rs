@@ -0,0 +1,111 @@
+//! Read half with bytes pushed back in front.
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{self, AsyncRead};
+
+use crate::{AnonPipeRead, AsRawHandle, RawHandle};
+
+/// Read half yielding `prefix` before the bytes of the pipe.
+///
+/// Created by [`AnonPipeRead::with_prefix`], e.g. to restore bytes a parser
+/// read past the end of its part of the stream.
+#[derive(Debug)]
+pub struct PrefixedRead {
+    inner: AnonPipeRead,
+    prefix: Vec<u8>,
+    pos: usize,
+}
+
+impl PrefixedRead {
+    pub(crate) fn new(inner: AnonPipeRead, prefix: Vec<u8>) -> Self {
+        Self {
+            inner,
+            prefix,
+            pos: 0,
+        }
+    }
+
+    /// Bytes of the prefix not read yet.
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix[self.pos..]
+    }
+
+    /// Gets a reference to the underlying read half.
+    pub fn get_ref(&self) -> &AnonPipeRead {
+        &self.inner
+    }
+
+    /// Unwrap the underlying read half, with bytes of the prefix not read
+    /// yet.
+    pub fn into_parts(mut self) -> (AnonPipeRead, Vec<u8>) {
+        self.prefix.drain(..self.pos);
+        (self.inner, self.prefix)
+    }
+}
+
+impl AsyncRead for PrefixedRead {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.pos < this.prefix.len() {
+            let n = buf.remaining().min(this.prefix.len() - this.pos);
+            buf.put_slice(&this.prefix[this.pos..this.pos + n]);
+            this.pos += n;
+            if this.pos == this.prefix.len() {
+                this.prefix = vec![];
+                this.pos = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsRawHandle for PrefixedRead {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.inner.as_raw_handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anon_pipe;
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_with_prefix() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+
+        w.write_all(b"HEADbody").await?;
+        drop(w);
+        // over-read into the body.
+        let mut head = [0; 6];
+        r.read_exact(&mut head).await?;
+        let mut r = r.with_prefix(head[4..].to_vec());
+
+        let mut buf = [0; 1];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"b");
+        assert_eq!(r.prefix(), b"o");
+        let mut rest = vec![];
+        r.read_to_end(&mut rest).await?;
+        assert_eq!(&rest, b"ody");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_with_prefix_into_parts() -> io::Result<()> {
+        let (r, _w) = anon_pipe().await?;
+        let mut r = r.with_prefix(b"abc".to_vec());
+
+        let mut buf = [0; 1];
+        r.read_exact(&mut buf).await?;
+        let (_r, prefix) = r.into_parts();
+        assert_eq!(&prefix, b"bc");
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+//! Forward any reader into the write half.
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::{errors, AnonPipeRead, AnonPipeWrite};
+
+/// Options of [`pump`].
+#[derive(Debug, Clone)]
+pub struct PumpOptions {
+    buffer_size: usize,
+    shutdown_on_eof: bool,
+    limit: Option<u64>,
+}
+
+impl Default for PumpOptions {
+    fn default() -> Self {
+        Self {
+            buffer_size: 8 * 1024,
+            shutdown_on_eof: true,
+            limit: None,
+        }
+    }
+}
+
+impl PumpOptions {
+    /// Create new options with default values.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Size of the intermediate buffer. Default is 8 KiB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is `0`.
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "buffer_size must be positive");
+        self.buffer_size = size;
+        self
+    }
+
+    /// Shut down the destination on EOF of the source. Default is `true`.
+    ///
+    /// Note that shutting down [`AnonPipeWrite`] does not close it, the
+    /// reader observes EOF once the write half is dropped.
+    pub fn shutdown_on_eof(mut self, enable: bool) -> Self {
+        self.shutdown_on_eof = enable;
+        self
+    }
+
+    /// Stop after `limit` bytes. Default is no limit.
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// Copy from `src` into `dst` until EOF of `src`, or the limit.
+///
+/// Waits for `dst` to accept each chunk before reading the next one. The
+/// writes into `dst` consume the coop budget of the task, which yields to
+/// the runtime once it is exhausted.
+/// Returns the number of bytes written into `dst`. If the reader of `dst`
+/// is gone, stops and returns the number of bytes written so far, rather
+/// than an error.
+pub async fn pump<R>(src: &mut R, dst: &mut AnonPipeWrite, opts: PumpOptions) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut buf = vec![0; opts.buffer_size];
+    let mut total = 0;
+    loop {
+        let len = match opts.limit {
+            Some(limit) if limit - total < buf.len() as u64 => (limit - total) as usize,
+            _ => buf.len(),
+        };
+        if len == 0 {
+            return Ok(total); // reached the limit.
+        }
+        let n = src.read(&mut buf[..len]).await?;
+        if n == 0 {
+            break;
+        }
+
+        let mut written = 0;
+        while written < n {
+            match dst.write(&buf[written..n]).await {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(m) => {
+                    written += m;
+                    total += m as u64;
+                }
+                Err(err) if errors::is_broken_pipe(&err) => return Ok(total),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+    if opts.shutdown_on_eof {
+        dst.shutdown().await?;
+    }
+    Ok(total)
+}
+
+/// Copy from `r` into `w` until EOF of `r`, then close `w`.
+///
+/// Both ends are consumed, and closed on return. `w` is closed with
+/// [`AnonPipeWrite::close`], so unlike [`pump`], fails with
+/// `ErrorKind::BrokenPipe` if the reader of `w` is gone before all the
+/// bytes were accepted. Returns the number of bytes copied.
+pub async fn pump_to_eof(mut r: AnonPipeRead, mut w: AnonPipeWrite) -> io::Result<u64> {
+    let opts = PumpOptions::new().shutdown_on_eof(false);
+    let total = pump(&mut r, &mut w, opts).await?;
+    w.close().await?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+
+    #[tokio::test]
+    async fn test_pump_file() -> io::Result<()> {
+        let data = (0..100_000).map(|n| n as u8).collect::<Vec<_>>();
+        let path =
+            std::env::temp_dir().join(format!("tokio-anon-pipe-pump-{}", std::process::id()));
+        std::fs::write(&path, &data)?;
+
+        let (mut r, mut w) = anon_pipe().await?;
+        let reader = tokio::spawn(async move {
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            Ok::<_, io::Error>(buf)
+        });
+
+        let mut file = tokio::fs::File::open(&path).await?;
+        let n = pump(&mut file, &mut w, PumpOptions::new().buffer_size(4096)).await?;
+        drop(w);
+        std::fs::remove_file(&path)?;
+        assert_eq!(n, 100_000);
+        assert_eq!(reader.await.unwrap()?, data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pump_limit() -> io::Result<()> {
+        let mut src = std::io::Cursor::new(b"Hello, World!".to_vec());
+        let (mut r, mut w) = anon_pipe().await?;
+
+        let n = pump(&mut src, &mut w, PumpOptions::new().buffer_size(3).limit(5)).await?;
+        drop(w);
+        assert_eq!(n, 5);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"Hello");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_pump_yields() -> io::Result<()> {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+        let ticker = {
+            let ticks = ticks.clone();
+            let done = done.clone();
+            tokio::spawn(async move {
+                while !done.load(Ordering::Relaxed) {
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+
+        let (mut r, mut w) = anon_pipe().await?;
+        let reader = tokio::spawn(async move {
+            let mut buf = vec![];
+            r.read_to_end(&mut buf).await?;
+            Ok::<_, io::Error>(buf.len())
+        });
+        let mut src = std::io::Cursor::new(vec![0; 16 * 1024 * 1024]);
+        let n = pump(&mut src, &mut w, PumpOptions::new()).await?;
+        let during = ticks.load(Ordering::Relaxed);
+        done.store(true, Ordering::Relaxed);
+        drop(w);
+
+        assert_eq!(n, 16 * 1024 * 1024);
+        assert_eq!(reader.await.unwrap()?, 16 * 1024 * 1024);
+        assert!(during > 0);
+        ticker.await.unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pump_to_eof() -> io::Result<()> {
+        let (src_r, mut src_w) = anon_pipe().await?;
+        let (mut dst_r, dst_w) = anon_pipe().await?;
+        let pumping = tokio::spawn(pump_to_eof(src_r, dst_w));
+
+        src_w.write_all(b"Hello, World!").await?;
+        drop(src_w);
+        assert_eq!(pumping.await.unwrap()?, 13);
+        // EOF, `dst_w` is closed.
+        let mut buf = vec![];
+        dst_r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"Hello, World!");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pump_to_eof_broken() -> io::Result<()> {
+        let (src_r, mut src_w) = anon_pipe().await?;
+        let (dst_r, dst_w) = anon_pipe().await?;
+        drop(dst_r);
+
+        src_w.write_all(b"lost").await?;
+        drop(src_w);
+        let err = pump_to_eof(src_r, dst_w).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pump_broken() -> io::Result<()> {
+        let mut src = std::io::Cursor::new(vec![0; 1024 * 1024]);
+        let (r, mut w) = anon_pipe().await?;
+
+        drop(r);
+        let n = pump(&mut src, &mut w, PumpOptions::new()).await?;
+        assert!(n < 1024 * 1024);
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+//! Security descriptor for the server end.
+use std::ffi::c_void;
+use std::io;
+use std::mem;
+use std::ptr;
+
+use crate::sys;
+
+/// DACL entries applied whenever a custom descriptor is requested.
+/// Same as the named pipe default, minus the Everyone/Anonymous read access.
+const BASE_DACL: &str = "D:(A;;GA;;;SY)(A;;GA;;;BA)(A;;GA;;;OW)";
+
+/// Medium mandatory label, no write up.
+const MEDIUM_LABEL_SACL: &str = "S:(ML;;NW;;;ME)";
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Security {
+    allow_sids: Vec<String>,
+    medium_integrity: bool,
+}
+
+impl Security {
+    pub(crate) fn allow_sid(&mut self, sid: String) {
+        if !self.allow_sids.contains(&sid) {
+            self.allow_sids.push(sid);
+        }
+    }
+
+    pub(crate) fn medium_integrity(&mut self, enable: bool) {
+        self.medium_integrity = enable;
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        self.allow_sids.is_empty() && !self.medium_integrity
+    }
+
+    pub(crate) fn sddl(&self) -> io::Result<String> {
+        let mut sddl = String::from(BASE_DACL);
+        for sid in &self.allow_sids {
+            validate_sid(sid)?;
+            sddl.push_str(&format!("(A;;GRGW;;;{})", sid));
+        }
+        if self.medium_integrity {
+            if !self.allow_sids.iter().any(|sid| sid == "IU") {
+                sddl.push_str("(A;;GRGW;;;IU)");
+            }
+            sddl.push_str(MEDIUM_LABEL_SACL);
+        }
+        Ok(sddl)
+    }
+}
+
+/// Accept either SDDL alias (`IU`) or string SID (`S-1-5-4`).
+fn validate_sid(sid: &str) -> io::Result<()> {
+    if sid.is_empty() || !sid.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid sid: {:?}", sid),
+        ));
+    }
+    Ok(())
+}
+
+/// Caller supplied `SECURITY_ATTRIBUTES`.
+///
+/// See [`AnonPipeBuilder::security_attributes_raw`](crate::AnonPipeBuilder::security_attributes_raw)
+/// for the validity requirements, upheld by the caller.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RawSecurityAttributes(pub(crate) *mut c_void);
+
+unsafe impl Send for RawSecurityAttributes {}
+unsafe impl Sync for RawSecurityAttributes {}
+
+/// Self-relative security descriptor allocated by
+/// `ConvertStringSecurityDescriptorToSecurityDescriptorW`.
+#[derive(Debug)]
+pub(crate) struct SecurityDescriptor(sys::PSECURITY_DESCRIPTOR);
+
+unsafe impl Send for SecurityDescriptor {}
+unsafe impl Sync for SecurityDescriptor {}
+
+impl SecurityDescriptor {
+    pub(crate) fn from_sddl(sddl: &str) -> io::Result<Self> {
+        let wide = sddl.encode_utf16().chain(Some(0)).collect::<Vec<_>>();
+        let mut sd = ptr::null_mut();
+        let ok = unsafe {
+            sys::ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                wide.as_ptr(),
+                sys::SDDL_REVISION_1,
+                &mut sd,
+                ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(sd))
+    }
+
+    /// `SECURITY_ATTRIBUTES` pointing this descriptor. Not inheritable.
+    pub(crate) fn attributes(&self) -> sys::SECURITY_ATTRIBUTES {
+        sys::SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<sys::SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: self.0,
+            bInheritHandle: 0,
+        }
+    }
+}
+
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        unsafe { sys::LocalFree(self.0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        let security = Security::default();
+        assert!(security.is_default());
+        assert_eq!(security.sddl().unwrap(), BASE_DACL);
+    }
+
+    #[test]
+    fn test_allow_interactive_user() {
+        let mut security = Security::default();
+        security.allow_sid("IU".into());
+        security.allow_sid("IU".into());
+        assert!(!security.is_default());
+        assert_eq!(
+            security.sddl().unwrap(),
+            "D:(A;;GA;;;SY)(A;;GA;;;BA)(A;;GA;;;OW)(A;;GRGW;;;IU)"
+        );
+    }
+
+    #[test]
+    fn test_allow_sid() {
+        let mut security = Security::default();
+        security.allow_sid("S-1-5-21-1-2-3-1001".into());
+        assert!(security
+            .sddl()
+            .unwrap()
+            .ends_with("(A;;GRGW;;;S-1-5-21-1-2-3-1001)"));
+    }
+
+    #[test]
+    fn test_reject_malformed_sid() {
+        let mut security = Security::default();
+        security.allow_sid("IU)(A;;GA;;;WD".into());
+        let err = security.sddl().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_medium_integrity() {
+        let mut security = Security::default();
+        security.medium_integrity(true);
+        assert!(!security.is_default());
+        assert_eq!(
+            security.sddl().unwrap(),
+            "D:(A;;GA;;;SY)(A;;GA;;;BA)(A;;GA;;;OW)(A;;GRGW;;;IU)S:(ML;;NW;;;ME)"
+        );
+
+        security.allow_sid("IU".into());
+        assert_eq!(
+            security.sddl().unwrap(),
+            "D:(A;;GA;;;SY)(A;;GA;;;BA)(A;;GA;;;OW)(A;;GRGW;;;IU)S:(ML;;NW;;;ME)"
+        );
+
+        let sd = SecurityDescriptor::from_sddl(&security.sddl().unwrap()).unwrap();
+        assert!(!sd.attributes().lpSecurityDescriptor.is_null());
+
+        security.medium_integrity(false);
+        assert!(!security.sddl().unwrap().contains("S:"));
+    }
+
+    #[test]
+    fn test_descriptor() {
+        let mut security = Security::default();
+        security.allow_sid("IU".into());
+        let sd = SecurityDescriptor::from_sddl(&security.sddl().unwrap()).unwrap();
+        let attrs = sd.attributes();
+        assert!(!attrs.lpSecurityDescriptor.is_null());
+        assert_eq!(attrs.bInheritHandle, 0);
+    }
+}
@@ -0,0 +1,58 @@
+//! Wait for the first of several read halves.
+use tokio::io;
+
+use crate::AnonPipeRead;
+
+/// Wait until one of `pipes` is readable, and return its index.
+///
+/// Readable means a read would not block: data arrived, or the writer is
+/// gone and the read returns EOF. When several are readable, the lowest
+/// index is returned. Fails with `ErrorKind::InvalidInput` if `pipes` is
+/// empty, and with the error of a pipe whose readiness failed.
+///
+/// Cancel safe, no data is consumed.
+pub async fn select_readable(pipes: &[AnonPipeRead]) -> io::Result<usize> {
+    if pipes.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no pipes to select from",
+        ));
+    }
+    std::future::poll_fn(|cx| {
+        for (index, pipe) in pipes.iter().enumerate() {
+            if let std::task::Poll::Ready(result) = pipe.poll_read_ready(cx) {
+                return std::task::Poll::Ready(result.map(|()| index));
+            }
+        }
+        std::task::Poll::Pending
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_select_readable() -> io::Result<()> {
+        let (r1, _w1) = anon_pipe().await?;
+        let (r2, mut w2) = anon_pipe().await?;
+        let (r3, _w3) = anon_pipe().await?;
+        let pipes = [r1, r2, r3];
+
+        let select = tokio::spawn(async move { select_readable(&pipes).await });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!select.is_finished());
+        w2.write_all(b"Hello").await?;
+        assert_eq!(select.await.unwrap()?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_select_readable_empty() {
+        let err = select_readable(&[]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,94 @@
+//! Server accepting sequential clients on one name.
+use tokio::io;
+
+use crate::security::SecurityDescriptor;
+use crate::{
+    audit, connect_server, limit, new_server, security_descriptor, try_new_server_with, Access,
+    AnonPipeBuilder, AnonPipeRead, Deadline, NamedPipeServer,
+};
+
+// https://www.rpi.edu/dept/cis/software/g77-mingw32/include/winerror.h
+const ERROR_INVALID_PARAMETER: i32 = 87;
+
+/// Server accepting sequential clients on one name.
+///
+/// Created by [`AnonPipeBuilder::server`]. Clients open [`name`](Self::name)
+/// for writing, and each connected instance is handed out as a fresh
+/// [`AnonPipeRead`] while the server keeps listening.
+///
+/// The first instance is created with `first_pipe_instance`, so creation
+/// fails rather than joining a pipe somebody else squatted on the name.
+/// Every following instance is created without it, since the name is ours
+/// by then. Instances count against [`AnonPipeBuilder::max_instances`]
+/// until their read half is dropped; with the default of `1`, the next
+/// client can be accepted only after the previous read half is dropped.
+#[derive(Debug)]
+pub struct AnonPipeServer {
+    builder: AnonPipeBuilder,
+    sd: Option<SecurityDescriptor>,
+    name: String,
+    next: Option<NamedPipeServer>,
+}
+
+impl AnonPipeServer {
+    pub(crate) fn new(builder: &AnonPipeBuilder) -> io::Result<Self> {
+        let sd = security_descriptor(builder)?;
+        let _permit = limit::acquire();
+        let (name, server) =
+            try_new_server_with(builder, sd.as_ref(), Access::Inbound, builder.deadline())?;
+        Ok(Self {
+            builder: builder.clone(),
+            sd,
+            name,
+            next: Some(server),
+        })
+    }
+
+    /// Name of the pipe clients open.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Wait for the next client and return the connected instance.
+    ///
+    /// Cancel safe. A client connecting while cancelled is returned by the
+    /// next call.
+    pub async fn accept_next(&mut self) -> io::Result<AnonPipeRead> {
+        if self.next.is_none() {
+            let _permit = limit::acquire_async().await;
+            self.next = Some(self.new_instance()?);
+        }
+        connect_server(self.next.as_ref().unwrap(), Deadline::default()).await?;
+        let server = self.next.take().unwrap();
+
+        // Listen for the next client right away, if instances and creation
+        // permits are left. Otherwise the instance is created on the next
+        // call; waiting here would lose `server` if cancelled.
+        if let Some(_permit) = limit::try_acquire() {
+            self.next = self.new_instance().ok();
+        }
+
+        Ok(audit::track(AnonPipeRead::Server(server), Some(&self.name)))
+    }
+
+    fn new_instance(&self) -> io::Result<NamedPipeServer> {
+        new_instance(&self.builder, self.sd.as_ref(), &self.name, Access::Inbound)
+    }
+}
+
+/// Create another instance of `name`, which is ours already.
+///
+/// The caller holds a creation permit, see [`limit`].
+pub(crate) fn new_instance(
+    builder: &AnonPipeBuilder,
+    sd: Option<&SecurityDescriptor>,
+    name: &str,
+    access: Access,
+) -> io::Result<NamedPipeServer> {
+    match new_server(builder, sd, name, false, true, access) {
+        Err(err) if err.raw_os_error() == Some(ERROR_INVALID_PARAMETER) => {
+            new_server(builder, sd, name, false, false, access)
+        }
+        result => result,
+    }
+}
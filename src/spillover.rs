@@ -0,0 +1,227 @@
+//! Capture with bounded memory usage.
+use std::io::SeekFrom;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::fs::File;
+use tokio::io::{self, AsyncRead, AsyncSeek, AsyncWrite};
+
+use crate::AnonPipeRead;
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+enum State {
+    Capturing,
+    Rewinding,
+    Reading,
+}
+
+/// Read half captured to EOF, into memory up to a cap, and the rest into
+/// a temporary file.
+///
+/// Created by [`AnonPipeRead::spillover_reader`]. Reading from it first
+/// captures the whole stream (or call [`capture`](Self::capture) to do so
+/// eagerly, e.g. to let a child process exit), then yields the captured
+/// bytes in order. The temporary file is deleted once this is dropped.
+#[derive(Debug)]
+pub struct SpilloverRead {
+    pipe: AnonPipeRead,
+    mem_cap: usize,
+    mem: Vec<u8>,
+    mem_pos: usize,
+    file: Option<File>,
+    chunk: Box<[u8]>,
+    chunk_pos: usize,
+    chunk_len: usize,
+    len: u64,
+    state: State,
+}
+
+impl SpilloverRead {
+    pub(crate) fn new(pipe: AnonPipeRead, mem_cap: usize) -> Self {
+        Self {
+            pipe,
+            mem_cap,
+            mem: vec![],
+            mem_pos: 0,
+            file: None,
+            chunk: vec![0; CHUNK_SIZE].into_boxed_slice(),
+            chunk_pos: 0,
+            chunk_len: 0,
+            len: 0,
+            state: State::Capturing,
+        }
+    }
+
+    /// Read the pipe to EOF. Returns the number of bytes captured.
+    pub async fn capture(&mut self) -> io::Result<u64> {
+        std::future::poll_fn(|cx| self.poll_capture(cx)).await?;
+        Ok(self.len)
+    }
+
+    /// Number of bytes captured so far.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// `true` if nothing was captured so far.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// `true` if the captured bytes exceeded the memory cap.
+    pub fn is_spilled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    fn poll_capture(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            match self.state {
+                State::Reading => return Poll::Ready(Ok(())),
+                State::Rewinding => {
+                    let file = self.file.as_mut().unwrap();
+                    match Pin::new(file).poll_complete(cx)? {
+                        Poll::Ready(..) => self.state = State::Reading,
+                        Poll::Pending => return Poll::Pending,
+                    }
+                    continue;
+                }
+                State::Capturing => {}
+            }
+
+            if self.chunk_pos < self.chunk_len {
+                let file = self.file.as_mut().unwrap();
+                let buf = &self.chunk[self.chunk_pos..self.chunk_len];
+                match Pin::new(file).poll_write(cx, buf)? {
+                    Poll::Ready(0) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                    Poll::Ready(n) => self.chunk_pos += n,
+                    Poll::Pending => return Poll::Pending,
+                }
+                continue;
+            }
+
+            let mut buf = io::ReadBuf::new(&mut self.chunk);
+            match Pin::new(&mut self.pipe).poll_read(cx, &mut buf)? {
+                Poll::Ready(()) => {}
+                Poll::Pending => return Poll::Pending,
+            }
+            let n = buf.filled().len();
+            if n == 0 {
+                match &mut self.file {
+                    Some(file) => {
+                        match Pin::new(&mut *file).poll_flush(cx)? {
+                            Poll::Ready(()) => {}
+                            Poll::Pending => return Poll::Pending,
+                        }
+                        Pin::new(file).start_seek(SeekFrom::Start(0))?;
+                        self.state = State::Rewinding;
+                    }
+                    None => self.state = State::Reading,
+                }
+                continue;
+            }
+
+            let take = n.min(self.mem_cap - self.mem.len());
+            self.mem.extend_from_slice(&self.chunk[..take]);
+            self.len += n as u64;
+            if take < n {
+                if self.file.is_none() {
+                    self.file = Some(File::from_std(tempfile()?));
+                }
+                self.chunk_pos = take;
+                self.chunk_len = n;
+            }
+        }
+    }
+}
+
+/// Create a temporary file, deleted once closed.
+///
+/// Blocks briefly, once per capture at most.
+fn tempfile() -> io::Result<std::fs::File> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let path = std::env::temp_dir().join(format!(
+        "tokio-anon-pipe-spillover.{}.{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut opts = std::fs::OpenOptions::new();
+    opts.read(true).write(true).create_new(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        // https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-createfilew
+        const FILE_FLAG_DELETE_ON_CLOSE: u32 = 0x0400_0000;
+        opts.custom_flags(FILE_FLAG_DELETE_ON_CLOSE);
+    }
+    opts.open(path)
+}
+
+impl AsyncRead for SpilloverRead {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_capture(cx)? {
+            Poll::Ready(()) => {}
+            Poll::Pending => return Poll::Pending,
+        }
+        if this.mem_pos < this.mem.len() {
+            let n = buf.remaining().min(this.mem.len() - this.mem_pos);
+            buf.put_slice(&this.mem[this.mem_pos..this.mem_pos + n]);
+            this.mem_pos += n;
+            return Poll::Ready(Ok(()));
+        }
+        match &mut this.file {
+            Some(file) => Pin::new(file).poll_read(cx, buf),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anon_pipe;
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_spillover_in_memory() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let mut r = r.spillover_reader(1024);
+
+        w.write_all(b"Hello, World!").await?;
+        drop(w);
+        assert_eq!(r.capture().await?, 13);
+        assert!(!r.is_spilled());
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"Hello, World!");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_spillover() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let mut r = r.spillover_reader(1000);
+        let data = (0..100_000).map(|n| n as u8).collect::<Vec<_>>();
+
+        let writer = {
+            let data = data.clone();
+            tokio::spawn(async move { w.write_all(&data).await })
+        };
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        writer.await.unwrap()?;
+        assert!(r.is_spilled());
+        assert_eq!(r.len(), 100_000);
+        assert_eq!(buf, data);
+        Ok(())
+    }
+}
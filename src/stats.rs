@@ -0,0 +1,96 @@
+//! Process-wide counters of pipe creation.
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub(crate) static ATTEMPTED: AtomicU64 = AtomicU64::new(0);
+pub(crate) static SUCCEEDED: AtomicU64 = AtomicU64::new(0);
+pub(crate) static NAME_COLLISIONS: AtomicU64 = AtomicU64::new(0);
+pub(crate) static REMOTE_CLIENTS_DOWNGRADES: AtomicU64 = AtomicU64::new(0);
+pub(crate) static CLIENT_BUSY_RETRIES: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn incr(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Snapshot of the creation counters.
+///
+/// Taken by [`creation_stats`]. The counters are updated independently, a
+/// snapshot taken while pipes are created may be slightly inconsistent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CreationStats {
+    attempted: u64,
+    succeeded: u64,
+    name_collisions: u64,
+    remote_clients_downgrades: u64,
+    client_busy_retries: u64,
+}
+
+impl CreationStats {
+    /// Number of server ends whose creation was started.
+    pub fn attempted(&self) -> u64 {
+        self.attempted
+    }
+
+    /// Number of server ends created.
+    pub fn succeeded(&self) -> u64 {
+        self.succeeded
+    }
+
+    /// Number of retries because the generated name was taken.
+    pub fn name_collisions(&self) -> u64 {
+        self.name_collisions
+    }
+
+    /// Number of retries without `reject_remote_clients`, which the system
+    /// does not support.
+    pub fn remote_clients_downgrades(&self) -> u64 {
+        self.remote_clients_downgrades
+    }
+
+    /// Number of retries of opening the client end because all instances
+    /// were busy.
+    pub fn client_busy_retries(&self) -> u64 {
+        self.client_busy_retries
+    }
+}
+
+/// Counters of pipe creation since the start of the process, or the last
+/// [`reset_creation_stats`].
+pub fn creation_stats() -> CreationStats {
+    CreationStats {
+        attempted: ATTEMPTED.load(Ordering::Relaxed),
+        succeeded: SUCCEEDED.load(Ordering::Relaxed),
+        name_collisions: NAME_COLLISIONS.load(Ordering::Relaxed),
+        remote_clients_downgrades: REMOTE_CLIENTS_DOWNGRADES.load(Ordering::Relaxed),
+        client_busy_retries: CLIENT_BUSY_RETRIES.load(Ordering::Relaxed),
+    }
+}
+
+/// Reset the counters to zero.
+pub fn reset_creation_stats() {
+    for counter in [
+        &ATTEMPTED,
+        &SUCCEEDED,
+        &NAME_COLLISIONS,
+        &REMOTE_CLIENTS_DOWNGRADES,
+        &CLIENT_BUSY_RETRIES,
+    ] {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io;
+
+    #[tokio::test]
+    async fn test_creation_stats() -> io::Result<()> {
+        let before = creation_stats();
+        anon_pipe().await?;
+        let after = creation_stats();
+        assert!(after.attempted() > before.attempted());
+        assert!(after.succeeded() > before.succeeded());
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! Write half whose target can be replaced mid-stream.
+use std::mem;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use tokio::io::{self, AsyncWrite, AsyncWriteExt};
+
+use crate::AnonPipeWrite;
+
+#[derive(Debug)]
+struct Slot {
+    target: AnonPipeWrite,
+    /// Writer waiting for the target, woken on swap.
+    waker: Option<Waker>,
+}
+
+/// Write half forwarding to a target which [`swap`](Self::swap) replaces,
+/// e.g. the pipe to a child restarted mid-stream.
+///
+/// Clones share the target, so one clone can be written to while another
+/// swaps. Each `poll_write` goes wholly to one target: a `write_all` in
+/// progress continues on the new target with the rest of its buffer.
+#[derive(Debug, Clone)]
+pub struct SwappableWrite {
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl SwappableWrite {
+    /// Forward to `target` until swapped.
+    pub fn new(target: AnonPipeWrite) -> Self {
+        let slot = Slot {
+            target,
+            waker: None,
+        };
+        Self {
+            slot: Arc::new(Mutex::new(slot)),
+        }
+    }
+
+    fn slot(&self) -> MutexGuard<'_, Slot> {
+        self.slot.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Forward the subsequent writes to `target`, and return the previous
+    /// target once the writes it accepted are flushed.
+    ///
+    /// A writer waiting for the previous target is woken to retry on
+    /// `target`. Fails if flushing fails, e.g. with `ErrorKind::BrokenPipe`
+    /// if its reader is gone; `target` is installed in any case.
+    pub async fn swap(&self, target: AnonPipeWrite) -> io::Result<AnonPipeWrite> {
+        let mut old = {
+            let mut slot = self.slot();
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+            mem::replace(&mut slot.target, target)
+        };
+        std::future::poll_fn(|cx| old.poll_write_ready(cx)).await?;
+        old.flush().await?;
+        Ok(old)
+    }
+
+    fn poll_target<T>(
+        &self,
+        cx: &mut Context<'_>,
+        f: impl FnOnce(Pin<&mut AnonPipeWrite>, &mut Context<'_>) -> Poll<T>,
+    ) -> Poll<T> {
+        let mut slot = self.slot();
+        let poll = f(Pin::new(&mut slot.target), cx);
+        if poll.is_pending() {
+            slot.waker = Some(cx.waker().clone());
+        }
+        poll
+    }
+}
+
+impl AsyncWrite for SwappableWrite {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        self.poll_target(cx, |target, cx| target.poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.poll_target(cx, |target, cx| target.poll_flush(cx))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.poll_target(cx, |target, cx| target.poll_shutdown(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_swap() -> io::Result<()> {
+        let (mut r1, w1) = anon_pipe().await?;
+        let (mut r2, w2) = anon_pipe().await?;
+        let mut w = SwappableWrite::new(w1);
+
+        w.write_all(b"first").await?;
+        drop(w.swap(w2).await?);
+        w.write_all(b"second").await?;
+        drop(w);
+
+        let mut buf = vec![];
+        r1.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"first");
+        buf.clear();
+        r2.read_to_end(&mut buf).await?;
+        assert_eq!(&buf, b"second");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_swap_mid_write() -> io::Result<()> {
+        let data = (0..1024 * 1024).map(|n| n as u8).collect::<Vec<_>>();
+        let (mut r1, w1) = anon_pipe().await?;
+        let (mut r2, w2) = anon_pipe().await?;
+        let w = SwappableWrite::new(w1);
+
+        // waits on the first target, nothing reads it yet.
+        let producer = tokio::spawn({
+            let mut w = w.clone();
+            let data = data.clone();
+            async move { w.write_all(&data).await }
+        });
+        let second = tokio::spawn(async move {
+            let mut buf = vec![];
+            r2.read_to_end(&mut buf).await?;
+            Ok::<_, io::Error>(buf)
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let mut first = vec![];
+        let (swapped, read) = tokio::join!(
+            async {
+                let old = w.swap(w2).await?;
+                drop((old, w));
+                Ok::<_, io::Error>(())
+            },
+            async {
+                // reads the flushed bytes, then EOF once `old` is dropped.
+                let mut buf = [0; 4096];
+                loop {
+                    match r1.read(&mut buf).await? {
+                        0 => return Ok::<_, io::Error>(()),
+                        n => first.extend_from_slice(&buf[..n]),
+                    }
+                }
+            },
+        );
+        swapped?;
+        read?;
+        producer.await.unwrap()?;
+        // no byte lost nor reordered across the swap.
+        let mut all = first;
+        all.extend(second.await.unwrap()?);
+        assert_eq!(all, data);
+        Ok(())
+    }
+}
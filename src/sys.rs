@@ -0,0 +1,338 @@
+//! Win32 bindings which are not covered by tokio.
+#![allow(non_camel_case_types, non_snake_case, clippy::upper_case_acronyms)]
+
+use std::ffi::c_void;
+
+pub(crate) type BOOL = i32;
+pub(crate) type DWORD = u32;
+pub(crate) type NTSTATUS = i32;
+pub(crate) type HANDLE = *mut c_void;
+pub(crate) type HLOCAL = *mut c_void;
+pub(crate) type LPCWSTR = *const u16;
+pub(crate) type PSECURITY_DESCRIPTOR = *mut c_void;
+
+pub(crate) const SDDL_REVISION_1: DWORD = 1;
+pub(crate) const HANDLE_FLAG_INHERIT: DWORD = 0x0000_0001;
+pub(crate) const THREAD_TERMINATE: DWORD = 0x0001;
+pub(crate) const FILE_TYPE_PIPE: DWORD = 0x0003;
+
+#[repr(C)]
+pub(crate) struct SECURITY_ATTRIBUTES {
+    pub(crate) nLength: DWORD,
+    pub(crate) lpSecurityDescriptor: *mut c_void,
+    pub(crate) bInheritHandle: BOOL,
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub(crate) struct FILE_PIPE_LOCAL_INFORMATION {
+    pub(crate) NamedPipeType: u32,
+    pub(crate) NamedPipeConfiguration: u32,
+    pub(crate) MaximumInstances: u32,
+    pub(crate) CurrentInstances: u32,
+    pub(crate) InboundQuota: u32,
+    pub(crate) ReadDataAvailable: u32,
+    pub(crate) OutboundQuota: u32,
+    pub(crate) WriteQuotaAvailable: u32,
+    pub(crate) NamedPipeState: u32,
+    pub(crate) NamedPipeEnd: u32,
+}
+
+#[repr(C)]
+pub(crate) struct FILE_COMPLETION_INFORMATION {
+    pub(crate) Port: HANDLE,
+    pub(crate) Key: *mut c_void,
+}
+
+#[repr(C)]
+pub(crate) struct OVERLAPPED {
+    pub(crate) Internal: usize,
+    pub(crate) InternalHigh: usize,
+    pub(crate) Offset: DWORD,
+    pub(crate) OffsetHigh: DWORD,
+    pub(crate) hEvent: HANDLE,
+}
+
+#[repr(C)]
+pub(crate) struct IO_STATUS_BLOCK {
+    pub(crate) Status: usize,
+    pub(crate) Information: usize,
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    pub(crate) fn LocalFree(hMem: HLOCAL) -> HLOCAL;
+    pub(crate) fn GetCurrentThreadId() -> DWORD;
+    #[cfg(not(feature = "rand"))]
+    pub(crate) fn QueryPerformanceCounter(lpPerformanceCount: *mut i64) -> BOOL;
+    pub(crate) fn FlushFileBuffers(hFile: HANDLE) -> BOOL;
+    pub(crate) fn SetHandleInformation(hObject: HANDLE, dwMask: DWORD, dwFlags: DWORD) -> BOOL;
+    pub(crate) fn GetHandleInformation(hObject: HANDLE, lpdwFlags: *mut DWORD) -> BOOL;
+    pub(crate) fn CloseHandle(hObject: HANDLE) -> BOOL;
+    pub(crate) fn GetFileType(hFile: HANDLE) -> DWORD;
+    pub(crate) fn CancelIoEx(hFile: HANDLE, lpOverlapped: *mut c_void) -> BOOL;
+    pub(crate) fn OpenThread(
+        dwDesiredAccess: DWORD,
+        bInheritHandle: BOOL,
+        dwThreadId: DWORD,
+    ) -> HANDLE;
+    pub(crate) fn CancelSynchronousIo(hThread: HANDLE) -> BOOL;
+    pub(crate) fn GetNamedPipeInfo(
+        hNamedPipe: HANDLE,
+        lpFlags: *mut DWORD,
+        lpOutBufferSize: *mut DWORD,
+        lpInBufferSize: *mut DWORD,
+        lpMaxInstances: *mut DWORD,
+    ) -> BOOL;
+    pub(crate) fn PeekNamedPipe(
+        hNamedPipe: HANDLE,
+        lpBuffer: *mut c_void,
+        nBufferSize: DWORD,
+        lpBytesRead: *mut DWORD,
+        lpTotalBytesAvail: *mut DWORD,
+        lpBytesLeftThisMessage: *mut DWORD,
+    ) -> BOOL;
+    pub(crate) fn GetNamedPipeClientSessionId(Pipe: HANDLE, ClientSessionId: *mut DWORD) -> BOOL;
+    pub(crate) fn GetNamedPipeServerSessionId(Pipe: HANDLE, ServerSessionId: *mut DWORD) -> BOOL;
+    pub(crate) fn WaitNamedPipeW(lpNamedPipeName: LPCWSTR, nTimeOut: DWORD) -> BOOL;
+    pub(crate) fn CreateEventW(
+        lpEventAttributes: *mut SECURITY_ATTRIBUTES,
+        bManualReset: BOOL,
+        bInitialState: BOOL,
+        lpName: LPCWSTR,
+    ) -> HANDLE;
+    pub(crate) fn WriteFile(
+        hFile: HANDLE,
+        lpBuffer: *const c_void,
+        nNumberOfBytesToWrite: DWORD,
+        lpNumberOfBytesWritten: *mut DWORD,
+        lpOverlapped: *mut OVERLAPPED,
+    ) -> BOOL;
+    pub(crate) fn WaitForSingleObject(hHandle: HANDLE, dwMilliseconds: DWORD) -> DWORD;
+    pub(crate) fn GetOverlappedResult(
+        hFile: HANDLE,
+        lpOverlapped: *mut OVERLAPPED,
+        lpNumberOfBytesTransferred: *mut DWORD,
+        bWait: BOOL,
+    ) -> BOOL;
+    pub(crate) fn GetNamedPipeHandleStateW(
+        hNamedPipe: HANDLE,
+        lpState: *mut DWORD,
+        lpCurInstances: *mut DWORD,
+        lpMaxCollectionCount: *mut DWORD,
+        lpCollectDataTimeout: *mut DWORD,
+        lpUserName: *mut u16,
+        nMaxUserNameSize: DWORD,
+    ) -> BOOL;
+}
+
+#[cfg(windows)]
+#[link(name = "ntdll")]
+extern "system" {
+    pub(crate) fn NtQueryInformationFile(
+        FileHandle: HANDLE,
+        IoStatusBlock: *mut IO_STATUS_BLOCK,
+        FileInformation: *mut c_void,
+        Length: DWORD,
+        FileInformationClass: DWORD,
+    ) -> NTSTATUS;
+    pub(crate) fn NtSetInformationFile(
+        FileHandle: HANDLE,
+        IoStatusBlock: *mut IO_STATUS_BLOCK,
+        FileInformation: *mut c_void,
+        Length: DWORD,
+        FileInformationClass: DWORD,
+    ) -> NTSTATUS;
+    pub(crate) fn RtlNtStatusToDosError(Status: NTSTATUS) -> DWORD;
+}
+
+#[cfg(windows)]
+#[link(name = "advapi32")]
+extern "system" {
+    pub(crate) fn ConvertStringSecurityDescriptorToSecurityDescriptorW(
+        StringSecurityDescriptor: LPCWSTR,
+        StringSDRevision: DWORD,
+        SecurityDescriptor: *mut PSECURITY_DESCRIPTOR,
+        SecurityDescriptorSize: *mut DWORD,
+    ) -> BOOL;
+}
+
+#[cfg(not(windows))]
+pub(crate) use stub::*;
+
+#[cfg(not(windows))]
+mod stub {
+    #![allow(unused_variables, clippy::missing_safety_doc)]
+    //! stub for non windows.
+    use super::*;
+
+    pub(crate) unsafe fn LocalFree(hMem: HLOCAL) -> HLOCAL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn GetCurrentThreadId() -> DWORD {
+        panic!("stub")
+    }
+
+    #[cfg(not(feature = "rand"))]
+    pub(crate) unsafe fn QueryPerformanceCounter(lpPerformanceCount: *mut i64) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn FlushFileBuffers(hFile: HANDLE) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn SetHandleInformation(
+        hObject: HANDLE,
+        dwMask: DWORD,
+        dwFlags: DWORD,
+    ) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn GetHandleInformation(hObject: HANDLE, lpdwFlags: *mut DWORD) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn CloseHandle(hObject: HANDLE) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn GetFileType(hFile: HANDLE) -> DWORD {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn CancelIoEx(hFile: HANDLE, lpOverlapped: *mut c_void) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn OpenThread(
+        dwDesiredAccess: DWORD,
+        bInheritHandle: BOOL,
+        dwThreadId: DWORD,
+    ) -> HANDLE {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn CancelSynchronousIo(hThread: HANDLE) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn GetNamedPipeInfo(
+        hNamedPipe: HANDLE,
+        lpFlags: *mut DWORD,
+        lpOutBufferSize: *mut DWORD,
+        lpInBufferSize: *mut DWORD,
+        lpMaxInstances: *mut DWORD,
+    ) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn PeekNamedPipe(
+        hNamedPipe: HANDLE,
+        lpBuffer: *mut c_void,
+        nBufferSize: DWORD,
+        lpBytesRead: *mut DWORD,
+        lpTotalBytesAvail: *mut DWORD,
+        lpBytesLeftThisMessage: *mut DWORD,
+    ) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn GetNamedPipeClientSessionId(
+        Pipe: HANDLE,
+        ClientSessionId: *mut DWORD,
+    ) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn GetNamedPipeServerSessionId(
+        Pipe: HANDLE,
+        ServerSessionId: *mut DWORD,
+    ) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn WaitNamedPipeW(lpNamedPipeName: LPCWSTR, nTimeOut: DWORD) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn CreateEventW(
+        lpEventAttributes: *mut SECURITY_ATTRIBUTES,
+        bManualReset: BOOL,
+        bInitialState: BOOL,
+        lpName: LPCWSTR,
+    ) -> HANDLE {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn WriteFile(
+        hFile: HANDLE,
+        lpBuffer: *const c_void,
+        nNumberOfBytesToWrite: DWORD,
+        lpNumberOfBytesWritten: *mut DWORD,
+        lpOverlapped: *mut OVERLAPPED,
+    ) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn WaitForSingleObject(hHandle: HANDLE, dwMilliseconds: DWORD) -> DWORD {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn GetOverlappedResult(
+        hFile: HANDLE,
+        lpOverlapped: *mut OVERLAPPED,
+        lpNumberOfBytesTransferred: *mut DWORD,
+        bWait: BOOL,
+    ) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn GetNamedPipeHandleStateW(
+        hNamedPipe: HANDLE,
+        lpState: *mut DWORD,
+        lpCurInstances: *mut DWORD,
+        lpMaxCollectionCount: *mut DWORD,
+        lpCollectDataTimeout: *mut DWORD,
+        lpUserName: *mut u16,
+        nMaxUserNameSize: DWORD,
+    ) -> BOOL {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn NtQueryInformationFile(
+        FileHandle: HANDLE,
+        IoStatusBlock: *mut IO_STATUS_BLOCK,
+        FileInformation: *mut c_void,
+        Length: DWORD,
+        FileInformationClass: DWORD,
+    ) -> NTSTATUS {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn NtSetInformationFile(
+        FileHandle: HANDLE,
+        IoStatusBlock: *mut IO_STATUS_BLOCK,
+        FileInformation: *mut c_void,
+        Length: DWORD,
+        FileInformationClass: DWORD,
+    ) -> NTSTATUS {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn RtlNtStatusToDosError(Status: NTSTATUS) -> DWORD {
+        panic!("stub")
+    }
+
+    pub(crate) unsafe fn ConvertStringSecurityDescriptorToSecurityDescriptorW(
+        StringSecurityDescriptor: LPCWSTR,
+        StringSDRevision: DWORD,
+        SecurityDescriptor: *mut PSECURITY_DESCRIPTOR,
+        SecurityDescriptorSize: *mut DWORD,
+    ) -> BOOL {
+        panic!("stub")
+    }
+}
@@ -0,0 +1,167 @@
+//! Pipe with a one-byte buffer for interleaving tests.
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{self, AsyncRead, AsyncWrite};
+
+use crate::{AnonPipeBuilder, AnonPipeRead, AnonPipeWrite};
+
+/// Create a pair which transfers one byte per read and write.
+///
+/// The server end is created with one-byte buffers. The system rounds the
+/// buffer sizes up, check the granted sizes with `get_ref().pipe_info()`.
+/// If they are rounded up, which is the usual case, each end is throttled
+/// to one byte per read and write (see [`Tiny::is_throttled`]), so every
+/// transfer is fragmented as much as possible either way.
+pub async fn anon_pipe_tiny() -> io::Result<(Tiny<AnonPipeRead>, Tiny<AnonPipeWrite>)> {
+    let (r, w) = AnonPipeBuilder::new()
+        .configure_server(|opts| {
+            opts.in_buffer_size(1).out_buffer_size(1);
+        })
+        .anon_pipe()
+        .await?;
+    let info = r.pipe_info()?;
+    let throttle = info.in_buffer_size() > 1 || info.out_buffer_size() > 1;
+    Ok((Tiny::new(r, throttle), Tiny::new(w, throttle)))
+}
+
+/// End of [`anon_pipe_tiny`].
+#[derive(Debug)]
+pub struct Tiny<T> {
+    inner: T,
+    throttle: bool,
+}
+
+impl<T> Tiny<T> {
+    fn new(inner: T, throttle: bool) -> Self {
+        Self { inner, throttle }
+    }
+
+    /// `true` if reads and writes are limited to one byte, because the
+    /// system granted a larger buffer.
+    pub fn is_throttled(&self) -> bool {
+        self.throttle
+    }
+
+    /// Gets a reference to the underlying end.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Unwraps the underlying end, without throttling.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Tiny<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.throttle || buf.remaining() == 0 {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        let mut byte = [0; 1];
+        let mut one = io::ReadBuf::new(&mut byte);
+        match Pin::new(&mut this.inner).poll_read(cx, &mut one)? {
+            Poll::Ready(()) => {
+                buf.put_slice(one.filled());
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Tiny<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let len = if this.throttle {
+            buf.len().min(1)
+        } else {
+            buf.len()
+        };
+        Pin::new(&mut this.inner).poll_write(cx, &buf[..len])
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// xorshift64, enough for picking sizes.
+    fn next(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    #[tokio::test]
+    async fn test_tiny_single_byte() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe_tiny().await?;
+
+        if r.is_throttled() {
+            assert_eq!(w.write(b"Hello").await?, 1);
+            let mut buf = [0; 5];
+            assert_eq!(r.read(&mut buf).await?, 1);
+            assert_eq!(buf[0], b'H');
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tiny_random_sizes() -> io::Result<()> {
+        for seed in 1..=8 {
+            let (mut r, mut w) = anon_pipe_tiny().await?;
+            let mut state = seed;
+            let data = (0..4096)
+                .map(|_| next(&mut state) as u8)
+                .collect::<Vec<_>>();
+
+            let writer = {
+                let data = data.clone();
+                let mut state = seed ^ 0x5555;
+                tokio::spawn(async move {
+                    let mut rest = &data[..];
+                    while !rest.is_empty() {
+                        let len = (next(&mut state) % 64 + 1) as usize;
+                        let len = len.min(rest.len());
+                        w.write_all(&rest[..len]).await?;
+                        rest = &rest[len..];
+                    }
+                    Ok::<_, io::Error>(())
+                })
+            };
+
+            let mut received = vec![];
+            loop {
+                let mut buf = vec![0; (next(&mut state) % 64 + 1) as usize];
+                let n = r.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buf[..n]);
+            }
+            writer.await.unwrap()?;
+            assert_eq!(received, data, "seed {}", seed);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+//! Typed channel over the pipe.
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::io;
+
+use crate::{msg, AnonPipeRead, AnonPipeWrite};
+
+/// Default maximum frame size. 1 MiB.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// Create typed channel over the pair.
+///
+/// Each value is encoded with bincode and sent as a length-prefixed message
+/// (see [`AnonPipeWrite::send_msg`]).
+///
+/// ```no_run
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let (r, w) = tokio_anon_pipe::anon_pipe().await?;
+/// let (mut tx, mut rx) = tokio_anon_pipe::typed_channel::<String>(r, w);
+/// tx.send(&"Hello".to_owned()).await?;
+/// assert_eq!(rx.recv().await?.as_deref(), Some("Hello"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn typed_channel<T>(
+    read: AnonPipeRead,
+    write: AnonPipeWrite,
+) -> (TypedSender<T>, TypedReceiver<T>)
+where
+    T: Serialize + DeserializeOwned,
+{
+    (TypedSender::new(write), TypedReceiver::new(read))
+}
+
+/// Sending half of [`typed_channel`].
+pub struct TypedSender<T> {
+    inner: AnonPipeWrite,
+    max_frame_size: usize,
+    _phantom: PhantomData<fn(&T)>,
+}
+
+impl<T> fmt::Debug for TypedSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedSender")
+            .field("inner", &self.inner)
+            .field("max_frame_size", &self.max_frame_size)
+            .finish()
+    }
+}
+
+impl<T> TypedSender<T>
+where
+    T: Serialize,
+{
+    /// Wrap the write half.
+    pub fn new(inner: AnonPipeWrite) -> Self {
+        Self {
+            inner,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Set maximum encoded size of a value. Default is [`DEFAULT_MAX_FRAME_SIZE`].
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Send `value`.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if the encoded value exceeds
+    /// the maximum frame size.
+    pub async fn send(&mut self, value: &T) -> io::Result<()> {
+        let frame = bincode::serialize(value)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        if frame.len() > self.max_frame_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame length {} exceeds {}",
+                    frame.len(),
+                    self.max_frame_size
+                ),
+            ));
+        }
+        self.inner.send_msg(&frame).await
+    }
+
+    /// Unwrap the write half.
+    pub fn into_inner(self) -> AnonPipeWrite {
+        self.inner
+    }
+}
+
+/// Receiving half of [`typed_channel`].
+pub struct TypedReceiver<T> {
+    inner: AnonPipeRead,
+    max_frame_size: usize,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> fmt::Debug for TypedReceiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedReceiver")
+            .field("inner", &self.inner)
+            .field("max_frame_size", &self.max_frame_size)
+            .finish()
+    }
+}
+
+impl<T> TypedReceiver<T>
+where
+    T: DeserializeOwned,
+{
+    /// Wrap the read half.
+    pub fn new(inner: AnonPipeRead) -> Self {
+        Self {
+            inner,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Set maximum accepted frame size. Default is [`DEFAULT_MAX_FRAME_SIZE`].
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Receive next value.
+    ///
+    /// Returns `Ok(None)` on EOF at a frame boundary. Fails with
+    /// `ErrorKind::InvalidData` if the frame exceeds the maximum frame size
+    /// or can not be decoded, and with `ErrorKind::UnexpectedEof` on EOF in
+    /// the middle of a frame.
+    pub async fn recv(&mut self) -> io::Result<Option<T>> {
+        let frame = match msg::read_msg(&mut self.inner, self.max_frame_size).await? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        let value = bincode::deserialize(&frame)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Some(value))
+    }
+
+    /// Unwrap the read half.
+    pub fn into_inner(self) -> AnonPipeRead {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use serde::Deserialize;
+    use tokio::io::AsyncWriteExt;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Unit,
+        Pair(u32, String),
+        Nested { inner: Option<Box<Kind>> },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        name: String,
+        tags: Vec<String>,
+        kind: Kind,
+    }
+
+    #[tokio::test]
+    async fn test_typed_channel() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        let (mut tx, mut rx) = typed_channel::<Message>(r, w);
+
+        let messages = vec![
+            Message {
+                name: "unit".into(),
+                tags: vec![],
+                kind: Kind::Unit,
+            },
+            Message {
+                name: "nested".into(),
+                tags: vec!["a".into(), "b".into()],
+                kind: Kind::Nested {
+                    inner: Some(Box::new(Kind::Pair(1, "one".into()))),
+                },
+            },
+        ];
+        for message in &messages {
+            tx.send(message).await?;
+        }
+        drop(tx);
+
+        for message in messages {
+            assert_eq!(rx.recv().await?, Some(message));
+        }
+        assert_eq!(rx.recv().await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_typed_channel_corrupt() -> io::Result<()> {
+        let (r, mut w) = anon_pipe().await?;
+        let mut rx = TypedReceiver::<Message>::new(r);
+
+        w.write_all(&[1, 0, 0, 0, 0xff]).await?;
+        let err = rx.recv().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_typed_channel_max_frame_size() -> io::Result<()> {
+        let (r, w) = anon_pipe().await?;
+        let mut tx = TypedSender::<String>::new(w);
+        let mut rx = TypedReceiver::<String>::new(r).max_frame_size(8);
+
+        tx.send(&"too long for the receiver".to_owned()).await?;
+        let err = rx.recv().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut tx = tx.max_frame_size(8);
+        let err = tx.send(&"too long".to_owned()).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+}
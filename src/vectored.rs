@@ -0,0 +1,91 @@
+//! Read scattered into several buffers.
+use std::io::IoSliceMut;
+
+use tokio::io::{self, AsyncReadExt};
+
+use crate::AnonPipeRead;
+
+impl AnonPipeRead {
+    /// Read into `bufs` in order, e.g. a header into a small buffer and the
+    /// body into a large one.
+    ///
+    /// Waits for the first non-empty buffer to receive data, like `read`.
+    /// If it is filled, the following buffers are filled with what arrived
+    /// already, without waiting, until one is not filled. Returns the
+    /// number of bytes read: it may fill fewer than all buffers, and a
+    /// buffer only partly, but always fills them in order, each before the
+    /// next. Returns `0` at EOF, or if all buffers are empty.
+    ///
+    /// An error after some bytes were read ends the read; the next one
+    /// reports it.
+    pub async fn read_vectored_async(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let mut bufs = bufs.iter_mut().filter(|buf| !buf.is_empty());
+        let first = match bufs.next() {
+            Some(first) => first,
+            None => return Ok(0),
+        };
+        let mut total = self.read(first).await?;
+        if total < first.len() {
+            return Ok(total);
+        }
+        for buf in bufs {
+            match self.try_read(buf) {
+                Ok(n) => {
+                    total += n;
+                    if n < buf.len() {
+                        break;
+                    }
+                }
+                // nothing arrived yet, or the error the next read reports.
+                Err(..) => break,
+            }
+        }
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anon_pipe;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_read_vectored_async() -> io::Result<()> {
+        let payload = (0..20).collect::<Vec<u8>>();
+        let (mut r, mut w) = anon_pipe().await?;
+        w.write_all(&payload).await?;
+        w.close().await?;
+
+        let (mut header, mut middle, mut body) = ([0; 3], [0; 5], [0xff; 100]);
+        let mut bufs = [
+            IoSliceMut::new(&mut header),
+            IoSliceMut::new(&mut []),
+            IoSliceMut::new(&mut middle),
+            IoSliceMut::new(&mut body),
+        ];
+        assert_eq!(r.read_vectored_async(&mut bufs).await?, 20);
+        assert_eq!(header, payload[..3]);
+        assert_eq!(middle, payload[3..8]);
+        assert_eq!(body[..12], payload[8..]);
+        assert!(body[12..].iter().all(|b| *b == 0xff));
+
+        let mut bufs = [IoSliceMut::new(&mut header)];
+        assert_eq!(r.read_vectored_async(&mut bufs).await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_vectored_async_short() -> io::Result<()> {
+        let (mut r, mut w) = anon_pipe().await?;
+        w.write_all(b"He").await?;
+
+        let (mut header, mut body) = ([0; 3], [0; 5]);
+        let mut bufs = [IoSliceMut::new(&mut header), IoSliceMut::new(&mut body)];
+        // the first buffer is not filled, the second untouched.
+        assert_eq!(r.read_vectored_async(&mut bufs).await?, 2);
+        assert_eq!(&header, b"He\0");
+        assert_eq!(body, [0; 5]);
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+//! Hand pipe ends to another process.
+//!
+//! Runs without the libtest harness: the test binary re-executes itself with
+//! `child <mode>` as the peer process, whose stdout must stay clean.
+
+#[cfg(windows)]
+mod imp {
+    use std::io::{Read, Write};
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_anon_pipe::AnonPipeBuilder;
+
+    const LEN: usize = 100_000;
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|n| (n * 31 % 251) as u8).collect()
+    }
+
+    /// FNV-1a.
+    fn digest(data: &[u8]) -> String {
+        let hash = data.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+            (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        format!("{:016x}", hash)
+    }
+
+    fn child(args: &[String]) -> std::io::Result<()> {
+        match args {
+            [mode] if mode == "digest-stdin" => {
+                let mut buf = vec![];
+                std::io::stdin().read_to_end(&mut buf)?;
+                print!("{}", digest(&buf));
+            }
+            [mode] if mode == "emit-stdout" => {
+                std::io::stdout().write_all(&data(LEN))?;
+            }
+            [mode, name] if mode == "connect" => {
+                let mut pipe = std::fs::OpenOptions::new().write(true).open(name)?;
+                pipe.write_all(&data(LEN))?;
+            }
+            _ => panic!("unknown child mode {:?}", args),
+        }
+        Ok(())
+    }
+
+    fn spawn(args: &[&str], stdin: Stdio, stdout: Stdio) -> std::io::Result<std::process::Child> {
+        Command::new(std::env::current_exe()?)
+            .arg("child")
+            .args(args)
+            .stdin(stdin)
+            .stdout(stdout)
+            .spawn()
+    }
+
+    async fn test_inherited_stdin() -> anyhow::Result<()> {
+        let listener = AnonPipeBuilder::new().listener()?;
+        // synchronous handle, not registered with the reactor.
+        let end = std::fs::OpenOptions::new()
+            .read(true)
+            .open(listener.name())?;
+        let mut pipe = listener.accept().await?;
+        let child = spawn(&["digest-stdin"], end.into(), Stdio::piped())?;
+
+        pipe.write_all(&data(LEN)).await?;
+        drop(pipe);
+        let output = tokio::time::timeout(
+            TIMEOUT,
+            tokio::task::spawn_blocking(move || child.wait_with_output()),
+        )
+        .await???;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout)?, digest(&data(LEN)));
+        Ok(())
+    }
+
+    async fn test_inherited_stdout() -> anyhow::Result<()> {
+        let mut server = AnonPipeBuilder::new().server()?;
+        let end = std::fs::OpenOptions::new()
+            .write(true)
+            .open(server.name())?;
+        let mut pipe = server.accept_next().await?;
+        let mut child = spawn(&["emit-stdout"], Stdio::null(), end.into())?;
+
+        let mut buf = vec![];
+        tokio::time::timeout(TIMEOUT, pipe.read_to_end(&mut buf)).await??;
+        assert!(child.wait()?.success());
+        assert_eq!(digest(&buf), digest(&data(LEN)));
+        Ok(())
+    }
+
+    async fn test_connect_by_name() -> anyhow::Result<()> {
+        let mut server = AnonPipeBuilder::new().server()?;
+        let mut child = spawn(&["connect", server.name()], Stdio::null(), Stdio::null())?;
+
+        let mut pipe = tokio::time::timeout(TIMEOUT, server.accept_next()).await??;
+        let mut buf = vec![];
+        tokio::time::timeout(TIMEOUT, pipe.read_to_end(&mut buf)).await??;
+        assert!(child.wait()?.success());
+        assert_eq!(digest(&buf), digest(&data(LEN)));
+        Ok(())
+    }
+
+    pub(crate) fn main() {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        if args.first().map(String::as_str) == Some("child") {
+            child(&args[1..]).unwrap();
+            return;
+        }
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            test_inherited_stdin().await.unwrap();
+            println!("test test_inherited_stdin ... ok");
+            test_inherited_stdout().await.unwrap();
+            println!("test test_inherited_stdout ... ok");
+            test_connect_by_name().await.unwrap();
+            println!("test test_connect_by_name ... ok");
+        });
+
+        #[cfg(feature = "handle-audit")]
+        {
+            let live = tokio_anon_pipe::live_pipes();
+            assert!(live.is_empty(), "leaked ends: {:#?}", live);
+            println!("test no_leaked_ends ... ok");
+        }
+    }
+}
+
+fn main() {
+    #[cfg(windows)]
+    imp::main();
+}